@@ -1,15 +1,24 @@
+use crate::epoch::Scheduler;
 use crate::module::{Module, Result, Health};
-use std::sync::{atomic::{AtomicBool, Ordering}, Arc};
+use std::sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 /// Minimal runtime shell that manages registered modules.
 pub struct Runtime {
     modules: Vec<Box<dyn Module>>,
+    startup_grace: Duration,
+    started_at: Option<Instant>,
 }
 
 impl Runtime {
-    pub fn new() -> Self { Self { modules: Vec::new() } }
+    pub fn new() -> Self { Self { modules: Vec::new(), startup_grace: Duration::ZERO, started_at: None } }
+
+    /// For this long after `start_all`, Unhealthy modules are reported as Degraded
+    /// so slow warm-ups don't trip health gates.
+    pub fn set_startup_grace(&mut self, grace: Duration) {
+        self.startup_grace = grace;
+    }
 
     pub fn register<M: Module + 'static>(&mut self, m: M) {
         self.modules.push(Box::new(m));
@@ -17,21 +26,58 @@ impl Runtime {
 
     /// Start all modules in registration order.
     pub fn start_all(&mut self) -> Result<()> {
-        for m in self.modules.iter_mut() { m.start()?; }
+        for m in self.modules.iter_mut() {
+            let _span = crate::telemetry::module_span(m.name());
+            m.start()?;
+        }
+        self.started_at = Some(Instant::now());
         Ok(())
     }
 
     /// Stop all modules in reverse order.
     pub fn stop_all(&mut self) -> Result<()> {
-        for m in self.modules.iter_mut().rev() { m.stop()?; }
+        for m in self.modules.iter_mut().rev() {
+            let _span = crate::telemetry::module_span(m.name());
+            m.stop()?;
+        }
         Ok(())
     }
 
+    /// Give queued and leased jobs up to `max_wait` to finish, then stop modules. Workers keep
+    /// dequeuing and completing through `shared` meanwhile; it is only locked to check progress.
+    /// That is why this takes the scheduler behind its mutex rather than as `&mut Scheduler`:
+    /// an exclusive borrow held for the whole wait would stop those workers, so the queue could
+    /// never drain. Leases still outstanding at the deadline are released so their jobs are
+    /// requeued for the next run. Modules are stopped either way; returns whether everything
+    /// finished in time.
+    pub fn shutdown_with_drain(&mut self, shared: &Mutex<Scheduler>, max_wait: Duration) -> Result<bool> {
+        let deadline = Instant::now() + max_wait;
+        let outstanding = || shared.lock().map(|sched| sched.depth() + sched.leased_count()).map_err(|_| "scheduler mutex poisoned");
+        while outstanding()? > 0 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        let drained = {
+            let mut sched = shared.lock().map_err(|_| "scheduler mutex poisoned")?;
+            let released = sched.release_leases();
+            let drained = sched.depth() == 0;
+            if !drained {
+                tracing::warn!(depth = sched.depth(), released, "runtime: gave up draining scheduler");
+            }
+            drained
+        };
+        self.stop_all()?;
+        Ok(drained)
+    }
+
     /// Aggregate health (first non-Healthy wins).
     pub fn overall_health(&self) -> Health {
+        let in_grace = self.started_at.is_some_and(|t| t.elapsed() < self.startup_grace);
         for m in &self.modules {
             match m.health() {
                 Health::Healthy => continue,
+                Health::Unhealthy { reason } if in_grace => {
+                    return Health::Degraded { reason: format!("{reason} (starting up)") };
+                }
                 other => return other,
             }
         }
@@ -40,6 +86,27 @@ impl Runtime {
 
     // Start modules and block until Ctrl-C, then stop modules. Returns Ok even if the Ctrl-C handler was already installed elsewhere.
     pub fn run_until_ctrlc(&mut self) -> Result<()> {
+        self.start_and_wait_for_ctrlc()?;
+
+        // Stop modules
+        tracing::info!("runtime: shutting down");
+        self.stop_all()?;
+        tracing::info!("runtime: stopped");
+        Ok(())
+    }
+
+    // Like `run_until_ctrlc`, but after Ctrl-C gives the scheduler's jobs up to `max_wait` to
+    // finish before stopping modules (see `shutdown_with_drain`). Returns whether they did.
+    pub fn run_until_ctrlc_draining(&mut self, shared: &Mutex<Scheduler>, max_wait: Duration) -> Result<bool> {
+        self.start_and_wait_for_ctrlc()?;
+
+        tracing::info!(max_wait_ms = max_wait.as_millis() as u64, "runtime: draining scheduler, then shutting down");
+        let drained = self.shutdown_with_drain(shared, max_wait)?;
+        tracing::info!(drained, "runtime: stopped");
+        Ok(drained)
+    }
+
+    fn start_and_wait_for_ctrlc(&mut self) -> Result<()> {
         // Start modules
         self.start_all()?;
         tracing::info!("runtime: started; press Ctrl-C to stop");
@@ -59,11 +126,6 @@ impl Runtime {
         while !shutdown.load(Ordering::SeqCst) {
             thread::sleep(Duration::from_millis(50));
         }
-
-        // Stop modules
-        tracing::info!("runtime: shutting down");
-        self.stop_all()?;
-        tracing::info!("runtime: stopped");
         Ok(())
     }
 
@@ -73,6 +135,12 @@ impl Runtime {
     }
 }
 
+impl Default for Runtime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -99,4 +167,86 @@ mod tests {
         rt.stop_all().unwrap();
         assert!(matches!(rt.overall_health(), Health::Degraded { .. }));
     }
-}
\ No newline at end of file
+
+    struct SlowStart;
+    impl Module for SlowStart {
+        fn name(&self) -> &str { "slow" }
+        fn start(&mut self) -> Result<()> { Ok(()) }
+        fn stop(&mut self) -> Result<()> { Ok(()) }
+        fn health(&self) -> Health { Health::Unhealthy { reason: "warming up".into() } }
+    }
+
+    #[test]
+    fn startup_grace_softens_unhealthy() {
+        let mut rt = Runtime::new();
+        rt.set_startup_grace(Duration::from_millis(50));
+        rt.register(SlowStart);
+
+        rt.start_all().unwrap();
+        assert!(matches!(rt.overall_health(), Health::Degraded { .. }));
+
+        thread::sleep(Duration::from_millis(70));
+        assert!(matches!(rt.overall_health(), Health::Unhealthy { .. }));
+    }
+
+    #[test]
+    fn shutdown_with_drain_stops_modules() {
+        let mut rt = Runtime::new();
+        rt.register(Demo { running: false });
+        rt.start_all().unwrap();
+
+        let sched = Mutex::new(Scheduler::new());
+        let started = Instant::now();
+        assert!(rt.shutdown_with_drain(&sched, Duration::from_secs(5)).unwrap());
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert!(matches!(rt.overall_health(), Health::Degraded { .. }));
+    }
+
+    #[test]
+    fn shutdown_with_drain_waits_for_workers() {
+        let mut rt = Runtime::new();
+        rt.register(Demo { running: false });
+        rt.start_all().unwrap();
+
+        let shared = Arc::new(Mutex::new(Scheduler::new()));
+        let job = {
+            let mut sched = shared.lock().unwrap();
+            sched.enqueue("email", "queued");
+            sched.enqueue("email", "in flight");
+            sched.dequeue("email", Duration::from_secs(60)).unwrap()
+        };
+        let worker = {
+            let shared = shared.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(30));
+                let mut sched = shared.lock().unwrap();
+                sched.complete(job.id).unwrap();
+                let next = sched.dequeue("email", Duration::from_secs(60)).unwrap();
+                sched.complete(next.id).unwrap();
+            })
+        };
+
+        let started = Instant::now();
+        assert!(rt.shutdown_with_drain(&shared, Duration::from_secs(5)).unwrap());
+        assert!(started.elapsed() < Duration::from_secs(1));
+        worker.join().unwrap();
+    }
+
+    #[test]
+    fn shutdown_with_drain_gives_up() {
+        let mut rt = Runtime::new();
+        rt.register(Demo { running: false });
+        rt.start_all().unwrap();
+
+        let sched = Mutex::new(Scheduler::new());
+        sched.lock().unwrap().enqueue("email", "never drained");
+        sched.lock().unwrap().dequeue("email", Duration::from_secs(60)).unwrap();
+
+        let started = Instant::now();
+        assert!(!rt.shutdown_with_drain(&sched, Duration::from_millis(50)).unwrap());
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        let sched = sched.lock().unwrap();
+        assert_eq!((sched.depth(), sched.leased_count()), (1, 0));
+        assert!(matches!(rt.overall_health(), Health::Degraded { .. }));
+    }
+}
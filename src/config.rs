@@ -1,17 +1,100 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use crate::epoch::RecurringJob;
 use crate::module::Result;
+use crate::sentinel::Rule;
+use crate::vaultline::{DurabilityPolicy, Level, RateLimit};
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Config {
     #[serde(default = "default_log_level")]
     pub log_level: String,
     #[serde(default = "default_data_dir")]
     pub data_dir: String,
+    #[serde(default)]
+    pub vault: VaultConfig,
+    /// `[[rules]]` entries: alerting rules evaluated against appended events.
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+    /// `[[schedules]]` entries: recurring jobs, registered on startup (entries removed here are unregistered).
+    #[serde(default)]
+    pub schedules: Vec<RecurringJob>,
+    /// On Ctrl-C, how long queued and leased jobs get to finish before modules stop (default 0:
+    /// stop right away; outstanding leases are still released for the next run).
+    #[serde(default)]
+    pub shutdown_drain_secs: u64,
+}
+
+/// `[vault]` section: Vaultline storage settings.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct VaultConfig {
+    /// Roll `event.log` over to an archive once it reaches this many bytes.
+    pub rotate_bytes: Option<u64>,
+    /// Also roll over when the UTC date changes, into `event-YYYY-MM-DD.ndjson` (gzipped with `compression`).
+    pub rotate_daily: bool,
+    /// Number of rotated archives to keep.
+    pub max_segments: Option<usize>,
+    /// Keep only the newest this-many events in memory; older ones stay on disk.
+    pub memory_cap: Option<usize>,
+    /// Prune events older than this many seconds.
+    pub max_age_secs: Option<u64>,
+    /// Prune all but the newest this-many events.
+    pub max_events: Option<usize>,
+    /// 64-hex-char AES-256 key for encrypting events at rest (`encryption` feature).
+    /// `HYPERION_VAULT_KEY` overrides it.
+    pub encryption_key: Option<String>,
+    /// 64-hex-char ed25519 seed for signing every event (`signing` feature).
+    /// `HYPERION_SIGNING_KEY` overrides it.
+    pub signing_key: Option<String>,
+    /// Per-source append cap, e.g. `{ per_sec = 100, sample_every = 10 }`; see [`RateLimit`].
+    pub rate_limit: Option<RateLimit>,
+    /// Collapse repeated (source, level, message) events within this many milliseconds.
+    pub rollup_window_ms: Option<u64>,
+    /// Store kv strings of at least this many bytes in `<data_dir>/blobs`, referenced by hash.
+    pub blob_min_bytes: Option<usize>,
+    /// Mask the values of these kv keys (case-insensitive, at any depth) before storing.
+    pub redact_keys: Vec<String>,
+    /// Mask matches of these regexes in messages and kv strings before storing.
+    pub redact_patterns: Vec<String>,
+    /// Write a CRC32 with every event so corrupt lines are caught on load.
+    pub checksums: bool,
+    /// Drop appends below this level (e.g. `"warn"`); levels outside trace..error are kept.
+    pub min_level: Option<Level>,
+    /// Link each record to the previous one by hash so `verify` can detect tampering.
+    pub hash_chain: bool,
+    /// Keep per-hour event counts in `event.log.summary` so stats don't scan every segment.
+    pub summaries: bool,
+    /// Number events with a monotonically increasing `seq`.
+    pub sequence: bool,
+    /// Lock `event.log` around appends, waiting at most this long for other writers.
+    pub lock_timeout_ms: Option<u64>,
+    /// POST stored events as JSON arrays to this URL (`webhook` feature).
+    pub webhook_url: Option<String>,
+    /// Events per webhook request (default 100).
+    pub webhook_batch_size: Option<usize>,
+    /// Ship stored events as OTLP/HTTP log records to this collector, e.g.
+    /// `http://localhost:4318` (`otel` feature).
+    pub otlp_endpoint: Option<String>,
+    /// Publish stored events to `kafka_topic` on these brokers (`kafka` feature).
+    pub kafka_brokers: Option<String>,
+    pub kafka_topic: Option<String>,
+    /// Also write each source to its own file under `<data_dir>/partitions`, e.g.
+    /// for per-component retention.
+    pub partition_by_source: bool,
+    /// Flush/fsync behaviour of appends, e.g. `"fsync_every_write"` or
+    /// `{ flush_every_n = 100 }`; see [`DurabilityPolicy`].
+    pub durability: Option<DurabilityPolicy>,
 }
 
 fn default_log_level() -> String { "info".to_string() }
 fn default_data_dir() -> String { "data".to_string() }
 
+impl Default for Config {
+    fn default() -> Self {
+        Self { log_level: default_log_level(), data_dir: default_data_dir(), vault: VaultConfig::default(), rules: Vec::new(), schedules: Vec::new(), shutdown_drain_secs: 0 }
+    }
+}
+
 /// Load configuration from `HYPERION_CONFIG` (TOML) if set, otherwise `config.toml`.
 /// If the file doesn't exist, return safe defaults.
 pub fn load_config() -> Result<Config> {
@@ -21,7 +104,7 @@ pub fn load_config() -> Result<Config> {
         let cfg: Config = toml::from_str(&content)?;
         Ok(cfg)
     } else {
-        Ok(Config { log_level: default_log_level(), data_dir: default_data_dir() })
+        Ok(Config::default())
     }
 }
 
@@ -35,18 +118,25 @@ mod tests {
         let cfg = load_config().unwrap();
         assert_eq!(cfg.log_level, "info");
         assert_eq!(cfg.data_dir, "data");
+        assert_eq!(cfg.shutdown_drain_secs, 0);
     }
 
     #[test]
     fn reads_file() {
         let path = std::env::temp_dir().join(format!("hyperion_cfg_{}.toml", std::process::id()));
         std::fs::write(&path, r#"log_level = "debug"
-data_dir = "test_data""#).unwrap();
+data_dir = "test_data"
+
+[vault]
+rotate_bytes = 1048576
+max_segments = 5"#).unwrap();
 
         unsafe { std::env::set_var("HYPERION_CONFIG", &path); }
         let cfg = load_config().unwrap();
         assert_eq!(cfg.log_level, "debug");
         assert_eq!(cfg.data_dir, "test_data");
+        assert_eq!(cfg.vault.rotate_bytes, Some(1_048_576));
+        assert_eq!(cfg.vault.max_segments, Some(5));
 
         let _ = std::fs::remove_file(path);
     }
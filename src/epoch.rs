@@ -1,6 +1,19 @@
 use crate::module::Result;
+use crate::vaultline::{Event, Filter, Vaultline};
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, VecDeque};
-use std::time::{Duration, Instant};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+mod cron;
+mod journal;
+pub use cron::{CronExpr, Recurring, RecurringJob, RecurringService};
+pub use journal::{JobOp, JobStore, JournalStore};
+#[cfg(feature = "sqlite")]
+pub use journal::SqliteJobStore;
 
 /// Minimum job representation.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -10,6 +23,162 @@ pub struct Job {
     pub payload: String,
     pub created_at: Instant,
     pub attempts: u32,
+    /// Higher values are dequeued first within a kind.
+    pub priority: i32,
+    /// Hidden from `dequeue` until this instant (delayed jobs, retry backoff).
+    pub visible_at: Option<Instant>,
+    /// Correlation id of the span the job was enqueued in; copied onto its transition events.
+    pub correlation_id: Option<String>,
+}
+
+/// Persistable form of a job (instants are process-local, so timing is reset on recovery).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JobRecord {
+    pub id: u64,
+    pub kind: String,
+    pub payload: String,
+    pub attempts: u32,
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+    /// Wall-clock ms before which the job stays hidden (a delayed job or retry backoff).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_at_ms: Option<u64>,
+}
+
+/// Current wall-clock time in ms since the Unix epoch.
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64)
+}
+
+impl From<&Job> for JobRecord {
+    fn from(job: &Job) -> Self {
+        Self {
+            id: job.id,
+            kind: job.kind.clone(),
+            payload: job.payload.clone(),
+            attempts: job.attempts,
+            priority: job.priority,
+            correlation_id: job.correlation_id.clone(),
+            run_at_ms: job.visible_at.and_then(|at| {
+                let wait = at.checked_duration_since(Instant::now())?;
+                Some(now_ms().saturating_add(wait.as_millis() as u64))
+            }),
+        }
+    }
+}
+
+impl JobRecord {
+    fn into_job(self) -> Job {
+        let wait = self.run_at_ms.map(|at| Duration::from_millis(at.saturating_sub(now_ms())));
+        Job {
+            id: self.id,
+            kind: self.kind,
+            payload: self.payload,
+            created_at: Instant::now(),
+            attempts: self.attempts,
+            priority: self.priority,
+            visible_at: wait.filter(|w| !w.is_zero()).map(|w| Instant::now() + w),
+            correlation_id: self.correlation_id,
+        }
+    }
+}
+
+/// Recoverable scheduler state. Leased jobs are captured as queued so they run again after a crash.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SchedulerSnapshot {
+    pub next_id: u64,
+    pub queued: Vec<JobRecord>,
+    #[serde(default)]
+    pub dead_letters: Vec<JobRecord>,
+}
+
+/// Which end of a kind's queue `dequeue` takes from among equal-priority jobs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Order {
+    /// Oldest first.
+    #[default]
+    Fifo,
+    /// Newest first, for latency-sensitive kinds.
+    Lifo,
+}
+
+/// Delay before a failed or expired job becomes visible again.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Backoff {
+    #[default]
+    None,
+    Fixed(Duration),
+    /// `base * 2^(attempts - 1)`, capped at `max`.
+    Exponential { base: Duration, max: Duration },
+}
+
+impl Backoff {
+    /// Delay before retrying a job that has made `attempts` attempts.
+    pub fn delay(&self, attempts: u32) -> Duration {
+        match *self {
+            Backoff::None => Duration::ZERO,
+            Backoff::Fixed(d) => d,
+            Backoff::Exponential { base, max } => {
+                let factor = 1u32.checked_shl(attempts.saturating_sub(1)).unwrap_or(u32::MAX);
+                base.saturating_mul(factor).min(max)
+            }
+        }
+    }
+}
+
+/// Per-kind handling of failed and expired jobs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Attempts allowed before a job is dead-lettered; `None` retries forever.
+    pub max_attempts: Option<u32>,
+    pub backoff: Backoff,
+    /// Pick each delay at random between half and all of the backoff, so jobs that
+    /// failed together don't all retry at the same moment.
+    pub jitter: bool,
+}
+
+impl RetryPolicy {
+    /// Delay before retrying a job that has made `attempts` attempts, jitter included.
+    pub fn delay(&self, attempts: u32) -> Duration {
+        let delay = self.backoff.delay(attempts);
+        if !self.jitter || delay.is_zero() {
+            return delay;
+        }
+        use std::hash::{BuildHasher, Hasher};
+        let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+        hasher.write_u32(attempts);
+        let half = delay / 2;
+        let spread = (delay - half).as_nanos() as u64;
+        half + Duration::from_nanos(hasher.finish() % spread.saturating_add(1))
+    }
+}
+
+/// What `fail` does with a job, as chosen by a fail decider.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailAction {
+    /// Re-queue (after the kind's backoff).
+    Retry,
+    /// Move to dead-letter for inspection.
+    DeadLetter,
+    /// Discard the job entirely; counted in `dropped_total`, not as a failure.
+    Drop,
+}
+
+type FailDecider = Box<dyn Fn(&Job) -> FailAction + Send>;
+
+/// Point-in-time scheduler figures. `*_total` counters are cumulative until `reset_counters`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SchedulerStats {
+    pub depth: usize,
+    pub leased: usize,
+    pub done: usize,
+    pub failed: usize,
+    pub enqueued_total: u64,
+    pub completed_total: u64,
+    pub failed_total: u64,
+    pub dropped_total: u64,
 }
 
 /// Leased job with expiration.
@@ -26,7 +195,21 @@ pub struct Scheduler {
     leased: HashMap<u64, Lease>,
     done: Vec<Job>,
     failed: Vec<Job>,
+    dead_letters: Vec<Job>,
+    retry_policies: HashMap<String, RetryPolicy>,
+    fail_decider: Option<FailDecider>,
     default_kind: String,
+    priority_aging: Option<Duration>,
+    orders: HashMap<String, Order>,
+    decision_log: Option<Arc<Mutex<Vaultline>>>,
+    transition_vault: Option<Arc<Mutex<Vaultline>>>,
+    history_strip_payload: bool,
+    store: Option<Box<dyn JobStore>>,
+    store_error: Option<String>,
+    enqueued_total: u64,
+    completed_total: u64,
+    failed_total: u64,
+    dropped_total: u64,
 }
 
 impl Scheduler {
@@ -38,42 +221,296 @@ impl Scheduler {
             leased: HashMap::new(),
             done: Vec::new(),
             failed: Vec::new(),
+            dead_letters: Vec::new(),
+            retry_policies: HashMap::new(),
+            fail_decider: None,
             default_kind: "default".into(),
+            priority_aging: None,
+            orders: HashMap::new(),
+            decision_log: None,
+            transition_vault: None,
+            history_strip_payload: false,
+            store: None,
+            store_error: None,
+            enqueued_total: 0,
+            completed_total: 0,
+            failed_total: 0,
+            dropped_total: 0,
+        }
+    }
+
+    /// Bump a queued job's effective priority by one for every `bump_after` it has waited,
+    /// so low-priority work can't starve behind a steady stream of urgent jobs.
+    pub fn set_priority_aging(&mut self, bump_after: Duration) {
+        self.priority_aging = (!bump_after.is_zero()).then_some(bump_after);
+    }
+
+    /// Set the dequeue order for a kind (FIFO unless configured).
+    pub fn set_order<S: Into<String>>(&mut self, kind: S, order: Order) {
+        self.orders.insert(kind.into(), order);
+    }
+
+    /// Record every dequeue decision (kind, reason, depth) as a debug event in `vault`.
+    pub fn set_decision_log(&mut self, vault: Arc<Mutex<Vaultline>>) {
+        self.decision_log = Some(vault);
+    }
+
+    /// Append an event to `vault` for every job state change, with `kv` holding
+    /// `job_id`, `kind`, `from` and `to` (new, queued, leased, done, failed, dead_lettered, dropped).
+    pub fn set_transition_vault(&mut self, vault: Arc<Mutex<Vaultline>>) {
+        self.transition_vault = Some(vault);
+    }
+
+    /// Scheduler backed by a journal at `<dir>/journal.ndjson`, recovering the jobs
+    /// recorded there. Jobs that were leased when the process stopped are queued again.
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        Self::with_store(JournalStore::open(dir.as_ref().join("journal.ndjson"))?)
+    }
+
+    /// Scheduler that recovers its jobs from `store`, compacts it, and records every
+    /// job state change there from then on.
+    pub fn with_store<S: JobStore + 'static>(mut store: S) -> Result<Self> {
+        let snapshot = store.load()?;
+        store.compact(&snapshot)?;
+        let recovered = snapshot.queued.len() + snapshot.dead_letters.len();
+        let mut sched = Self::from_snapshot(snapshot);
+        if recovered > 0 {
+            tracing::info!(queued = sched.depth(), dead_letters = sched.dead_letters.len(), "recovered jobs from store");
         }
+        sched.store = Some(Box::new(store));
+        Ok(sched)
+    }
+
+    /// Persist a state change. A store that can't keep up shouldn't stop the
+    /// queue, so the in-memory change stands and the failure is logged and kept
+    /// for `take_store_error` (which every `Result`-returning call runs last).
+    fn record(&mut self, op: JobOp) {
+        let Some(store) = &mut self.store else { return };
+        if let Err(e) = store.record(&op) {
+            tracing::warn!(error = %e, ?op, "job store write failed");
+            self.store_error.get_or_insert_with(|| e.to_string());
+        }
+    }
+
+    /// Report (and clear) the first job store write that failed since the last
+    /// call. Calls without a `Result` (`enqueue`, `dequeue`, ...) leave theirs here,
+    /// so callers that only use those should check now and then.
+    pub fn take_store_error(&mut self) -> Result<()> {
+        match self.store_error.take() {
+            Some(e) => Err(format!("job store write failed; jobs since may not survive a restart: {e}").into()),
+            None => Ok(()),
+        }
+    }
+
+    fn transition(&self, job: &Job, from: &str, to: &str) {
+        let Some(vault) = &self.transition_vault else { return };
+        let mut ev = Event::now("epoch", "info", format!("job {} {from} -> {to}", job.id))
+            .with("job_id", job.id)
+            .with("kind", job.kind.as_str())
+            .with("from", from)
+            .with("to", to);
+        ev.correlation_id = job.correlation_id.clone();
+        let ev = ev.in_current_span();
+        if let Ok(mut vault) = vault.lock() {
+            let _ = vault.append(ev);
+        }
+    }
+
+    /// Clear payloads of jobs moved into done/failed history, keeping id/kind/attempts.
+    pub fn set_history_strip_payload(&mut self, strip: bool) {
+        self.history_strip_payload = strip;
+    }
+
+    /// Copy of a job as it should be kept in history.
+    fn for_history(&self, mut job: Job) -> Job {
+        if self.history_strip_payload {
+            job.payload = String::new();
+        }
+        job
+    }
+
+    /// Set the retry policy for a kind (unlimited immediate retries unless configured).
+    pub fn set_retry_policy<S: Into<String>>(&mut self, kind: S, policy: RetryPolicy) {
+        self.retry_policies.insert(kind.into(), policy);
+    }
+
+    /// Let `decider` choose what `fail` does with each failed job, instead of the retry policy's
+    /// max-attempts check. Backoff still applies to retries.
+    pub fn set_fail_decider<F: Fn(&Job) -> FailAction + Send + 'static>(&mut self, decider: F) {
+        self.fail_decider = Some(Box::new(decider));
     }
 
     /// Enqueue a job to a given kind.
     pub fn enqueue<S: Into<String>, P: Into<String>>(&mut self, kind: S, payload: P) -> u64 {
+        self.enqueue_with_priority(kind, payload, 0)
+    }
+
+    /// Enqueue a job to a given kind with an explicit priority (higher runs first).
+    pub fn enqueue_with_priority<S: Into<String>, P: Into<String>>(&mut self, kind: S, payload: P, priority: i32) -> u64 {
+        let correlation_id = crate::telemetry::current_trace_ids().correlation_id;
+        self.push_new(kind.into(), payload.into(), priority, correlation_id, None)
+    }
+
+    /// Enqueue a job that stays hidden from `dequeue` until `run_at` (wall clock).
+    /// A time in the past makes it visible immediately.
+    pub fn enqueue_at<S: Into<String>, P: Into<String>>(&mut self, kind: S, payload: P, run_at: SystemTime) -> u64 {
+        let delay = run_at.duration_since(SystemTime::now()).unwrap_or_default();
+        self.enqueue_after(kind, payload, delay)
+    }
+
+    /// Enqueue a job that stays hidden from `dequeue` for `delay`.
+    pub fn enqueue_after<S: Into<String>, P: Into<String>>(&mut self, kind: S, payload: P, delay: Duration) -> u64 {
+        let correlation_id = crate::telemetry::current_trace_ids().correlation_id;
+        let visible_at = (!delay.is_zero()).then(|| Instant::now() + delay);
+        self.push_new(kind.into(), payload.into(), 0, correlation_id, visible_at)
+    }
+
+    /// Queue a brand-new job and record its `new -> queued` transition.
+    fn push_new(&mut self, kind: String, payload: String, priority: i32, correlation_id: Option<String>, visible_at: Option<Instant>) -> u64 {
         let id = self.next_id;
         self.next_id += 1;
+        self.enqueued_total += 1;
 
         let job = Job {
             id,
-            kind: kind.into(),
-            payload: payload.into(),
+            kind,
+            payload,
             created_at: Instant::now(),
             attempts: 0,
+            priority,
+            visible_at,
+            correlation_id,
         };
+        self.transition(&job, "new", "queued");
+        self.record(JobOp::Enqueue { job: JobRecord::from(&job) });
 
         self.queued
             .entry(job.kind.clone())
-            .or_insert_with(VecDeque::new)
+            .or_default()
             .push_back(job);
 
         id
     }
 
+    /// Re-enqueue the jobs recorded by events in `vault` matching `filter`, oldest first, e.g.
+    /// `Filter::new().source("halodeck").contains("submitted job")` after a crash. Events must carry
+    /// `kind` and `payload` in `kv` (plus an optional `priority`); others are skipped. Jobs whose last
+    /// `epoch` transition in the vault is `done`, `dead_lettered` or `dropped` (matched by `job_id`,
+    /// or by correlation id for jobs that were already replayed once) are not replayed. Replayed jobs
+    /// get new ids and keep the event's correlation id. Returns the new ids.
+    pub fn replay(&mut self, vault: &mut Vaultline, filter: Filter) -> Result<Vec<u64>> {
+        let mut by_job = HashMap::new();
+        let mut by_correlation = HashMap::new();
+        for event in vault.events_matching(Filter::new().source("epoch"))? {
+            let event = event?;
+            let Some(to) = event.kv_str("to") else { continue };
+            let finished = matches!(to, "done" | "dead_lettered" | "dropped");
+            if let Some(job_id) = event.kv_i64("job_id") {
+                by_job.insert(job_id, finished);
+            }
+            if let Some(correlation_id) = event.correlation_id {
+                by_correlation.insert(correlation_id, finished);
+            }
+        }
+
+        let mut ids = Vec::new();
+        for event in vault.events_matching(filter)? {
+            let event = event?;
+            let (Some(kind), Some(payload)) = (event.kv_str("kind"), event.kv_str("payload")) else { continue };
+            let finished = event.kv_i64("job_id").and_then(|id| by_job.get(&id)).copied().unwrap_or(false)
+                || event.correlation_id.as_ref().and_then(|id| by_correlation.get(id)).copied().unwrap_or(false);
+            if finished { continue; }
+            let priority = event.kv_i64("priority").and_then(|p| i32::try_from(p).ok()).unwrap_or(0);
+            ids.push(self.push_new(kind.to_string(), payload.to_string(), priority, event.correlation_id.clone(), None));
+        }
+        Ok(ids)
+    }
+
     /// Enqueue a job with the default kind.
     pub fn enqueue_default<P: Into<String>>(&mut self, payload: P) -> u64 {
         self.enqueue(self.default_kind.clone(), payload)
     }
 
+    /// Priority of a queued job after aging is applied.
+    fn effective_priority(&self, job: &Job, now: Instant) -> i64 {
+        let bump = match self.priority_aging {
+            Some(step) => (now.saturating_duration_since(job.created_at).as_nanos() / step.as_nanos()) as i64,
+            None => 0,
+        };
+        i64::from(job.priority).saturating_add(bump)
+    }
+
+    /// Index and effective priority of the job `dequeue` would pick for a kind.
+    fn pick(&self, kind: &str, now: Instant) -> Option<(usize, i64)> {
+        let lifo = self.orders.get(kind) == Some(&Order::Lifo);
+        let queue = self.queued.get(kind)?;
+        let mut best: Option<(usize, i64)> = None;
+        for (idx, job) in queue.iter().enumerate() {
+            if job.visible_at.is_some_and(|t| t > now) { continue; }
+            let prio = self.effective_priority(job, now);
+            if best.is_none_or(|(_, p)| prio > p || (lifo && prio == p)) {
+                best = Some((idx, prio));
+            }
+        }
+        best
+    }
+
     /// Dequeue a job with a lease. Returns None if no work available.
+    /// Picks the highest effective priority; ties go to the oldest job, or the newest for LIFO kinds.
     pub fn dequeue(&mut self, kind: &str, lease_duration: Duration) -> Option<Job> {
-        let queue = self.queued.get_mut(kind)?;
-        let mut job = queue.pop_front()?;
+        let (idx, prio) = self.pick(kind, Instant::now())?;
+        self.log_decision(kind, &format!("requested kind, effective priority {prio}"));
+        self.lease_at(kind, idx, lease_duration)
+    }
+
+    /// Like `dequeue`, also reporting whether this is the job's final allowed attempt
+    /// under the kind's retry policy.
+    pub fn dequeue_with_meta(&mut self, kind: &str, lease_duration: Duration) -> Option<(Job, bool)> {
+        let job = self.dequeue(kind, lease_duration)?;
+        let max_attempts = self.retry_policies.get(kind).and_then(|p| p.max_attempts);
+        let last = max_attempts.is_some_and(|max| job.attempts >= max);
+        Some((job, last))
+    }
+
+    /// Dequeue from whichever kind holds the most urgent job: highest effective priority,
+    /// then the longest-waiting job, then kind name.
+    pub fn dequeue_any(&mut self, lease_duration: Duration) -> Option<Job> {
+        use std::cmp::Reverse;
+        let now = Instant::now();
+        let mut best = None;
+        for kind in self.queued.keys() {
+            let Some((idx, prio)) = self.pick(kind, now) else { continue };
+            let candidate = (prio, Reverse(self.queued[kind][idx].created_at), Reverse(kind.as_str()), idx);
+            if best.is_none_or(|b| candidate > b) {
+                best = Some(candidate);
+            }
+        }
+        let (prio, _, Reverse(kind), idx) = best?;
+        let kind = kind.to_string();
+        let kinds = self.queued.values().filter(|q| !q.is_empty()).count();
+        self.log_decision(&kind, &format!("highest effective priority {prio} across {kinds} kinds, oldest first"));
+        self.lease_at(&kind, idx, lease_duration)
+    }
+
+    fn log_decision(&self, kind: &str, reason: &str) {
+        let Some(vault) = &self.decision_log else { return };
+        let depth = self.queued.get(kind).map_or(0, |q| q.len());
+        let ev = Event::now("epoch", "debug", format!("dequeue picked {kind}: {reason}"))
+            .with("kind", kind)
+            .with("reason", reason)
+            .with("depth", depth);
+        if let Ok(mut vault) = vault.lock() {
+            let _ = vault.append(ev);
+        }
+    }
+
+    /// Remove the job at `idx` of a kind's queue and lease it.
+    fn lease_at(&mut self, kind: &str, idx: usize, lease_duration: Duration) -> Option<Job> {
+        let mut job = self.queued.get_mut(kind)?.remove(idx)?;
 
         job.attempts = job.attempts.saturating_add(1);
+        self.transition(&job, "queued", "leased");
+        self.record(JobOp::Lease { id: job.id, lease_ms: lease_duration.as_millis() as u64 });
 
         let lease = Lease {
             job: job.clone(),
@@ -87,46 +524,107 @@ impl Scheduler {
     /// Mark a job as done; removes from leased.
     pub fn complete(&mut self, job_id: u64) -> Result<()> {
         if let Some(lease) = self.leased.remove(&job_id) {
-            self.done.push(lease.job);
-            Ok(())
+            self.transition(&lease.job, "leased", "done");
+            self.record(JobOp::Complete { id: job_id });
+            let job = self.for_history(lease.job);
+            self.done.push(job);
+            self.completed_total += 1;
+            self.take_store_error()
         } else {
             Err(format!("complete(): job {job_id} not leased/unknown").into())
         }
     }
 
-    /// Mark a job as failed; removes from leased and re-enqueues.
+    /// Re-enqueue a job after its backoff, or dead-letter it once the kind's max attempts are used up.
+    fn retry_or_dead_letter(&mut self, job: Job, from: &str) {
+        let max_attempts = self.retry_policies.get(&job.kind).and_then(|p| p.max_attempts);
+        if max_attempts.is_some_and(|max| job.attempts >= max) {
+            self.bury(job, from);
+        } else {
+            self.retry(job, from);
+        }
+    }
+
+    /// Re-enqueue a job, hidden for the kind's backoff delay.
+    fn retry(&mut self, mut job: Job, from: &str) {
+        self.transition(&job, from, "queued");
+        self.record(JobOp::Requeue { id: job.id });
+        let delay = self.retry_policies.get(&job.kind).map_or(Duration::ZERO, |p| p.delay(job.attempts));
+        job.visible_at = (!delay.is_zero()).then(|| Instant::now() + delay);
+        self.queued.entry(job.kind.clone()).or_default().push_back(job);
+    }
+
+    /// Move a job to dead-letter.
+    fn bury(&mut self, job: Job, from: &str) {
+        self.transition(&job, from, "dead_lettered");
+        self.record(JobOp::DeadLetter { id: job.id });
+        tracing::warn!(id = job.id, kind = %job.kind, attempts = job.attempts, "job dead-lettered");
+        self.dead_letters.push(job);
+    }
+
+    /// Mark a job as failed; removes from leased and re-enqueues, or dead-letters/drops it
+    /// per the fail decider or retry policy.
     pub fn fail(&mut self, job_id: u64) -> Result<()> {
-    if let Some(lease) = self.leased.remove(&job_id) {
-            let kind = lease.job.kind.clone();
-            self.queued
-                .entry(kind)
-                .or_insert_with(VecDeque::new)
-                .push_back(lease.job.clone());
-
-            self.failed.push(lease.job);
-            Ok(())
+        self.fail_as(job_id, None)
+    }
+
+    /// Mark a job as failed for good: it goes straight to dead-letter, whatever the
+    /// retry policy or fail decider would do.
+    pub fn fail_permanently(&mut self, job_id: u64) -> Result<()> {
+        self.fail_as(job_id, Some(FailAction::DeadLetter))
+    }
+
+    fn fail_as(&mut self, job_id: u64, action: Option<FailAction>) -> Result<()> {
+        if let Some(lease) = self.leased.remove(&job_id) {
+            let action = action.or_else(|| self.fail_decider.as_ref().map(|decide| decide(&lease.job)));
+            // A dropped job was discarded on purpose, so it stays out of the failure history.
+            if action == Some(FailAction::Drop) {
+                self.transition(&lease.job, "leased", "dropped");
+                self.record(JobOp::Drop { id: job_id });
+                tracing::info!(id = job_id, "failed job dropped");
+                self.dropped_total += 1;
+                return self.take_store_error();
+            }
+            self.transition(&lease.job, "leased", "failed");
+            self.record(JobOp::Fail { id: job_id });
+            match action {
+                Some(FailAction::Retry) => self.retry(lease.job.clone(), "failed"),
+                Some(FailAction::DeadLetter) => self.bury(lease.job.clone(), "failed"),
+                _ => self.retry_or_dead_letter(lease.job.clone(), "failed"),
+            }
+
+            let job = self.for_history(lease.job);
+            self.failed.push(job);
+            self.failed_total += 1;
+            self.take_store_error()
         } else {
             Err(format!("fail(): job {job_id} not leased/unknown").into())
         }
     }
 
-    /// Move expired leases back to their queues (retry).
+    /// Move expired leases back to their queues (retry), dead-lettering jobs out of attempts.
     pub fn reclaim_expired(&mut self) {
+        self.reclaim_expired_limited(usize::MAX);
+    }
+
+    /// Like `reclaim_expired`, but handles at most `max` expired leases (longest-expired first)
+    /// so callers can spread the work out. Returns how many were reclaimed.
+    pub fn reclaim_expired_limited(&mut self, max: usize) -> usize {
         let now = Instant::now();
-        let expired_ids: Vec<u64> = self
+        let mut expired: Vec<(Instant, u64)> = self
             .leased
             .iter()
-            .filter_map(|(&id, lease)| (lease.expires_at <= now).then_some(id))
+            .filter_map(|(&id, lease)| (lease.expires_at <= now).then_some((lease.expires_at, id)))
             .collect();
+        expired.sort_unstable();
+        expired.truncate(max);
 
-        for id in expired_ids {
+        for &(_, id) in &expired {
             if let Some(lease) = self.leased.remove(&id) {
-                self.queued
-                    .entry(lease.job.kind.clone())
-                    .or_insert_with(VecDeque::new)
-                    .push_back(lease.job);
+                self.retry_or_dead_letter(lease.job, "leased");
             }
         }
+        expired.len()
     }
 
     /// Queue depth across all kinds (visible work only).
@@ -134,12 +632,197 @@ impl Scheduler {
         self.queued.values().map(|q| q.len()).sum()
     }
 
+    /// Return every leased job to its queue (without counting it as a failure). Returns how many.
+    pub fn release_leases(&mut self) -> usize {
+        let mut leases: Vec<Lease> = self.leased.drain().map(|(_, lease)| lease).collect();
+        leases.sort_by_key(|l| l.job.id);
+        let count = leases.len();
+        for lease in leases {
+            self.transition(&lease.job, "leased", "queued");
+            self.record(JobOp::Requeue { id: lease.job.id });
+            self.queued.entry(lease.job.kind.clone()).or_default().push_back(lease.job);
+        }
+        count
+    }
+
+    /// Queue depth of a single kind.
+    pub fn depth_of(&self, kind: &str) -> usize {
+        self.queued.get(kind).map_or(0, |q| q.len())
+    }
+
     /// Number of leased (in-flight) jobs.
     pub fn leased_count(&self) -> usize {
         self.leased.len()
+    }
+
+    /// Move every queued job of `kind` to dead-letter without running it (leased jobs are
+    /// left alone). Returns how many were quarantined.
+    pub fn quarantine_kind(&mut self, kind: &str) -> usize {
+        let Some(queue) = self.queued.remove(kind) else { return 0 };
+        let count = queue.len();
+        for job in &queue {
+            self.transition(job, "queued", "dead_lettered");
+            self.record(JobOp::DeadLetter { id: job.id });
+        }
+        self.dead_letters.extend(queue);
+        tracing::warn!(kind, count, "quarantined kind");
+        count
+    }
+
+    /// Dead-lettered jobs of a kind, oldest first.
+    pub fn dead_letter(&self, kind: &str) -> Vec<&Job> {
+        self.dead_letters.iter().filter(|j| j.kind == kind).collect()
+    }
+
+    /// Every dead-lettered job, oldest first.
+    pub fn dead_letters(&self) -> &[Job] {
+        &self.dead_letters
+    }
+
+    /// Move a dead-lettered job back to its queue with a fresh set of attempts.
+    pub fn requeue_dead(&mut self, job_id: u64) -> Result<()> {
+        let idx = self
+            .dead_letters
+            .iter()
+            .position(|j| j.id == job_id)
+            .ok_or_else(|| format!("requeue_dead(): job {job_id} is not dead-lettered"))?;
+        let mut job = self.dead_letters.remove(idx);
+        self.transition(&job, "dead_lettered", "queued");
+        self.record(JobOp::RequeueDead { id: job_id });
+        tracing::info!(id = job_id, kind = %job.kind, attempts = job.attempts, "dead-lettered job requeued");
+        job.attempts = 0;
+        job.visible_at = None;
+        self.queued.entry(job.kind.clone()).or_default().push_back(job);
+        self.take_store_error()
+    }
+
+    /// Current queue figures and cumulative counters.
+    pub fn stats(&self) -> SchedulerStats {
+        SchedulerStats {
+            depth: self.depth(),
+            leased: self.leased_count(),
+            done: self.done.len(),
+            failed: self.failed.len(),
+            enqueued_total: self.enqueued_total,
+            completed_total: self.completed_total,
+            failed_total: self.failed_total,
+            dropped_total: self.dropped_total,
+        }
+    }
+
+    /// Zero the cumulative counters (for per-interval deltas) without touching jobs or history.
+    pub fn reset_counters(&mut self) {
+        self.enqueued_total = 0;
+        self.completed_total = 0;
+        self.failed_total = 0;
+        self.dropped_total = 0;
+    }
+
+    /// Capture queued, leased and dead-lettered jobs. Policies and history are not included.
+    pub fn snapshot(&self) -> SchedulerSnapshot {
+        let mut kinds: Vec<&String> = self.queued.keys().collect();
+        kinds.sort();
+        let mut queued: Vec<JobRecord> = kinds
+            .into_iter()
+            .flat_map(|k| self.queued[k].iter().map(JobRecord::from))
+            .collect();
+        let mut leased: Vec<&Lease> = self.leased.values().collect();
+        leased.sort_by_key(|l| l.job.id);
+        queued.extend(leased.into_iter().map(|l| JobRecord::from(&l.job)));
+        SchedulerSnapshot {
+            next_id: self.next_id,
+            queued,
+            dead_letters: self.dead_letters.iter().map(JobRecord::from).collect(),
+        }
+    }
+
+    /// Rebuild a scheduler from a snapshot; every captured job is queued again.
+    pub fn from_snapshot(snapshot: SchedulerSnapshot) -> Self {
+        let mut sched = Self::new();
+        sched.next_id = snapshot.next_id.max(1);
+        for record in snapshot.queued {
+            let job = record.into_job();
+            sched.next_id = sched.next_id.max(job.id + 1);
+            sched.queued.entry(job.kind.clone()).or_default().push_back(job);
+        }
+        sched.dead_letters = snapshot.dead_letters.into_iter().map(JobRecord::into_job).collect();
+        sched
+    }
+
+    /// Write the snapshot as JSON via `<path>.tmp` + rename, so readers never see a partial file.
+    pub fn write_snapshot<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let mut tmp = path.as_os_str().to_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        let file = std::fs::File::create(&tmp)?;
+        serde_json::to_writer(&file, &self.snapshot())?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Load a scheduler from a snapshot written by `write_snapshot`.
+    pub fn recover<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let snapshot: SchedulerSnapshot = serde_json::from_str(&content)?;
+        Ok(Self::from_snapshot(snapshot))
+    }
+
+    /// Summary of work state meant to be logged on shutdown: current queue/lease
+    /// figures, totals for the run, and the ids of any leases still outstanding.
+    pub fn shutdown_report(&self) -> serde_json::Value {
+        let mut still_leased: Vec<u64> = self.leased.keys().copied().collect();
+        still_leased.sort_unstable();
+        serde_json::json!({
+            "queued": self.depth(),
+            "leased": self.leased_count(),
+            "enqueued_total": self.enqueued_total,
+            "completed_total": self.completed_total,
+            "failed_total": self.failed_total,
+            "dropped_total": self.dropped_total,
+            "still_leased": still_leased,
+        })
     }          
 }
 
+/// Snapshot `shared` to `path` every `interval` until `shutdown` is set, then write a final
+/// snapshot and exit. Combined with `Scheduler::recover` this bounds job loss on a crash.
+pub fn spawn_snapshotter(
+    shared: Arc<Mutex<Scheduler>>,
+    path: PathBuf,
+    interval: Duration,
+    shutdown: Arc<AtomicBool>,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let tick = interval.min(Duration::from_millis(50));
+        let mut last = Instant::now();
+        loop {
+            let stopping = shutdown.load(Ordering::SeqCst);
+            if stopping || last.elapsed() >= interval {
+                let result = match shared.lock() {
+                    Ok(sched) => sched.write_snapshot(&path),
+                    Err(_) => Err("scheduler mutex poisoned".into()),
+                };
+                if let Err(e) = result {
+                    tracing::warn!(error = %e, path = %path.display(), "scheduler snapshot failed");
+                }
+                last = Instant::now();
+            }
+            if stopping {
+                break;
+            }
+            thread::sleep(tick);
+        }
+    })
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -205,4 +888,344 @@ mod tests {
         assert_eq!(job2.id, job_id);
         assert_eq!(job2.attempts, 2);
     }
+
+    #[test]
+    fn test_priority_within_kind() {
+        let mut sched = Scheduler::new();
+        let bulk1 = sched.enqueue("email", "newsletter 1");
+        let bulk2 = sched.enqueue("email", "newsletter 2");
+        let urgent1 = sched.enqueue_with_priority("email", "password reset", 10);
+        let urgent2 = sched.enqueue_with_priority("email", "2fa code", 10);
+        let low = sched.enqueue_with_priority("email", "digest", -1);
+
+        let order: Vec<u64> = std::iter::from_fn(|| sched.dequeue("email", Duration::from_secs(1)).map(|j| j.id)).collect();
+        assert_eq!(order, [urgent1, urgent2, bulk1, bulk2, low]);
+    }
+
+    #[test]
+    fn test_priority_aging() {
+        let mut sched = Scheduler::new();
+        sched.set_priority_aging(Duration::from_millis(30));
+        let low = sched.enqueue_with_priority("email", "digest", 0);
+
+        sleep(Duration::from_millis(70)); // two aging bumps
+        let _high = sched.enqueue_with_priority("email", "password reset", 1);
+
+        let job = sched.dequeue("email", Duration::from_secs(1)).unwrap();
+        assert_eq!(job.id, low);
+    }
+
+    #[test]
+    fn test_shutdown_report() {
+        let mut sched = Scheduler::new();
+        sched.enqueue("email", "a");
+        sched.enqueue("email", "b");
+        sched.enqueue("email", "c");
+
+        let done = sched.dequeue("email", Duration::from_secs(5)).unwrap();
+        sched.complete(done.id).unwrap();
+        let stuck = sched.dequeue("email", Duration::from_secs(5)).unwrap();
+
+        let report = sched.shutdown_report();
+        assert_eq!(report["queued"], 1);
+        assert_eq!(report["leased"], 1);
+        assert_eq!(report["enqueued_total"], 3);
+        assert_eq!(report["completed_total"], 1);
+        assert_eq!(report["failed_total"], 0);
+        assert_eq!(report["still_leased"], serde_json::json!([stuck.id]));
+    }
+
+    #[test]
+    fn test_lifo_order() {
+        let mut sched = Scheduler::new();
+        sched.set_order("latency", Order::Lifo);
+        let ids: Vec<u64> = (0..3).map(|i| sched.enqueue("latency", format!("tick {i}"))).collect();
+        let fifo_first = sched.enqueue("batch", "first");
+        sched.enqueue("batch", "second");
+
+        let job = sched.dequeue("latency", Duration::from_secs(1)).unwrap();
+        assert_eq!(job.id, ids[2]);
+        let job = sched.dequeue("latency", Duration::from_secs(1)).unwrap();
+        assert_eq!(job.id, ids[1]);
+
+        let job = sched.dequeue("batch", Duration::from_secs(1)).unwrap();
+        assert_eq!(job.id, fifo_first);
+    }
+
+    #[test]
+    fn test_reset_counters() {
+        let mut sched = Scheduler::new();
+        let a = sched.enqueue("email", "a");
+        let b = sched.enqueue("email", "b");
+        sched.enqueue("email", "c");
+        sched.dequeue("email", Duration::from_secs(5)).unwrap();
+        sched.dequeue("email", Duration::from_secs(5)).unwrap();
+        sched.complete(a).unwrap();
+        sched.fail(b).unwrap();
+
+        let before = sched.stats();
+        assert_eq!((before.enqueued_total, before.completed_total, before.failed_total), (3, 1, 1));
+
+        sched.reset_counters();
+        let after = sched.stats();
+        assert_eq!((after.enqueued_total, after.completed_total, after.failed_total), (0, 0, 0));
+        assert_eq!((after.depth, after.leased, after.done, after.failed), (before.depth, before.leased, before.done, before.failed));
+    }
+
+    #[test]
+    fn test_decision_log() {
+        let vault = Arc::new(Mutex::new(Vaultline::new_in_memory()));
+        let mut sched = Scheduler::new();
+        sched.set_decision_log(vault.clone());
+        sched.enqueue("email", "a");
+        sched.enqueue_with_priority("sms", "b", 5);
+
+        sched.dequeue("email", Duration::from_secs(1)).unwrap();
+        let job = sched.dequeue_any(Duration::from_secs(1)).unwrap();
+        assert_eq!(job.kind, "sms");
+
+        let vault = vault.lock().unwrap();
+        let kinds: Vec<&str> = vault.all().iter().map(|ev| ev.kv["kind"].as_str().unwrap()).collect();
+        assert_eq!(kinds, ["email", "sms"]);
+        assert!(vault.all().iter().all(|ev| ev.level == "debug" && ev.source == "epoch"));
+        assert_eq!(vault.all()[1].kv["depth"], 1);
+    }
+
+    #[test]
+    fn test_history_strip_payload() {
+        let mut sched = Scheduler::new();
+        sched.set_history_strip_payload(true);
+        let big = "x".repeat(64 * 1024);
+        let id = sched.enqueue("report", big.clone());
+
+        let job = sched.dequeue("report", Duration::from_secs(5)).unwrap();
+        assert_eq!(job.payload, big);
+        sched.complete(id).unwrap();
+
+        let archived = &sched.done[0];
+        assert_eq!((archived.id, archived.kind.as_str(), archived.attempts), (id, "report", 1));
+        assert!(archived.payload.is_empty());
+    }
+
+    #[test]
+    fn test_reclaim_dead_letters_after_max_attempts() {
+        let mut sched = Scheduler::new();
+        sched.set_retry_policy("email", RetryPolicy {
+            max_attempts: Some(2),
+            backoff: Backoff::Fixed(Duration::from_millis(30)),
+            ..Default::default()
+        });
+        let job_id = sched.enqueue("email", "Send welcome email");
+
+        sched.dequeue("email", Duration::from_millis(10)).unwrap();
+        sleep(Duration::from_millis(20));
+        sched.reclaim_expired();
+        assert_eq!(sched.depth(), 1);
+        assert!(sched.dequeue("email", Duration::from_millis(10)).is_none()); // backing off
+
+        sleep(Duration::from_millis(40));
+        let job = sched.dequeue("email", Duration::from_millis(10)).unwrap();
+        assert_eq!(job.attempts, 2);
+        sleep(Duration::from_millis(20));
+        sched.reclaim_expired();
+
+        assert_eq!(sched.depth(), 0);
+        assert_eq!(sched.leased_count(), 0);
+        let dead = sched.dead_letter("email");
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].id, job_id);
+    }
+
+    #[test]
+    fn test_retry_delay_with_jitter() {
+        let backoff = Backoff::Exponential { base: Duration::from_millis(100), max: Duration::from_secs(1) };
+        let delays: Vec<u64> = (1..=5).map(|n| backoff.delay(n).as_millis() as u64).collect();
+        assert_eq!(delays, [100, 200, 400, 800, 1000]);
+
+        let policy = RetryPolicy { max_attempts: Some(5), backoff, jitter: true };
+        let samples: Vec<Duration> = (0..50).map(|_| policy.delay(3)).collect();
+        assert!(samples.iter().all(|d| (Duration::from_millis(200)..=Duration::from_millis(400)).contains(d)));
+        assert!(samples.iter().any(|d| *d != samples[0]));
+        assert_eq!(RetryPolicy { jitter: true, ..Default::default() }.delay(3), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_reclaim_expired_limited() {
+        let mut sched = Scheduler::new();
+        for i in 0..5 {
+            sched.enqueue("email", format!("job {i}"));
+            sched.dequeue("email", Duration::from_millis(10)).unwrap();
+        }
+        sleep(Duration::from_millis(30));
+
+        assert_eq!(sched.reclaim_expired_limited(2), 2);
+        assert_eq!(sched.depth(), 2);
+        assert_eq!(sched.leased_count(), 3);
+    }
+
+    #[test]
+    fn test_quarantine_kind() {
+        let mut sched = Scheduler::new();
+        for i in 0..3 {
+            sched.enqueue("bad", format!("job {i}"));
+        }
+        sched.enqueue("good", "keep me");
+
+        assert_eq!(sched.quarantine_kind("bad"), 3);
+        assert_eq!(sched.depth_of("bad"), 0);
+        assert_eq!(sched.depth_of("good"), 1);
+        assert_eq!(sched.dead_letter("bad").len(), 3);
+        assert_eq!(sched.quarantine_kind("bad"), 0);
+    }
+
+    #[test]
+    fn test_snapshotter_and_recover() {
+        let path = std::env::temp_dir().join(format!("epoch_snapshot_{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let shared = Arc::new(Mutex::new(Scheduler::new()));
+        {
+            let mut sched = shared.lock().unwrap();
+            sched.enqueue("email", "a");
+            sched.enqueue_with_priority("email", "b", 3);
+            sched.dequeue("email", Duration::from_secs(30)).unwrap(); // leased "b"
+        }
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let handle = spawn_snapshotter(shared.clone(), path.clone(), Duration::from_millis(20), shutdown.clone());
+        sleep(Duration::from_millis(80));
+        assert!(path.exists());
+
+        let mut recovered = Scheduler::recover(&path).unwrap();
+        assert_eq!(recovered.depth_of("email"), 2);
+        let job = recovered.dequeue("email", Duration::from_secs(1)).unwrap();
+        assert_eq!((job.payload.as_str(), job.priority), ("b", 3));
+        assert_eq!(recovered.enqueue("email", "c"), 3);
+
+        shutdown.store(true, Ordering::SeqCst);
+        handle.join().unwrap();
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_dequeue_with_meta_final_attempt() {
+        let mut sched = Scheduler::new();
+        sched.set_retry_policy("email", RetryPolicy { max_attempts: Some(2), ..Default::default() });
+        let job_id = sched.enqueue("email", "Send welcome email");
+
+        let (job, last) = sched.dequeue_with_meta("email", Duration::from_secs(1)).unwrap();
+        assert_eq!((job.attempts, last), (1, false));
+        sched.fail(job_id).unwrap();
+
+        let (job, last) = sched.dequeue_with_meta("email", Duration::from_secs(1)).unwrap();
+        assert_eq!((job.attempts, last), (2, true));
+    }
+
+    #[test]
+    fn test_fail_decider() {
+        let mut sched = Scheduler::new();
+        sched.set_fail_decider(|job| if job.attempts > 1 { FailAction::DeadLetter } else { FailAction::Retry });
+        let job_id = sched.enqueue("email", "flaky");
+
+        sched.dequeue("email", Duration::from_secs(1)).unwrap();
+        sched.fail(job_id).unwrap();
+        assert_eq!(sched.depth_of("email"), 1);
+        assert!(sched.dead_letter("email").is_empty());
+
+        sched.dequeue("email", Duration::from_secs(1)).unwrap();
+        sched.fail(job_id).unwrap();
+        assert_eq!(sched.depth_of("email"), 0);
+        assert_eq!(sched.dead_letter("email")[0].id, job_id);
+        assert_eq!(sched.failed.len(), 2);
+    }
+
+    #[test]
+    fn test_fail_decider_drop_is_not_a_failure() {
+        let mut sched = Scheduler::new();
+        sched.set_fail_decider(|_| FailAction::Drop);
+        let job_id = sched.enqueue("email", "stale");
+
+        sched.dequeue("email", Duration::from_secs(1)).unwrap();
+        sched.fail(job_id).unwrap();
+        let stats = sched.stats();
+        assert_eq!((stats.depth, stats.failed, stats.failed_total, stats.dropped_total), (0, 0, 0, 1));
+        assert!(sched.dead_letter("email").is_empty());
+    }
+
+    #[test]
+    fn test_transition_events() {
+        let vault = Arc::new(Mutex::new(Vaultline::new_in_memory()));
+        let mut sched = Scheduler::new();
+        sched.set_transition_vault(vault.clone());
+
+        let job_id = sched.enqueue("email", "hello");
+        sched.dequeue("email", Duration::from_secs(1)).unwrap();
+        sched.complete(job_id).unwrap();
+
+        let vault = vault.lock().unwrap();
+        let transitions: Vec<(&str, &str)> = vault
+            .all()
+            .iter()
+            .map(|ev| (ev.kv["from"].as_str().unwrap(), ev.kv["to"].as_str().unwrap()))
+            .collect();
+        assert_eq!(transitions, [("new", "queued"), ("queued", "leased"), ("leased", "done")]);
+        assert!(vault.all().iter().all(|ev| ev.kv["job_id"] == job_id));
+    }
+
+    #[test]
+    fn test_replay_from_vault() {
+        let mut vault = Vaultline::new_in_memory();
+        vault.append(Event::now("halodeck", "info", "submitted job 1").with("kind", "email").with("payload", "hi")).unwrap();
+        vault.append(Event::now("halodeck", "info", "submitted job 2 without details")).unwrap();
+        let urgent = Event::now("halodeck", "info", "submitted job 3")
+            .with("kind", "email")
+            .with("payload", "urgent")
+            .with("priority", 5)
+            .with_correlation_id("req-3");
+        vault.append(urgent).unwrap();
+        vault.append(Event::now("epoch", "info", "job 1 done").with("kind", "email").with("payload", "hi")).unwrap();
+        vault.append(Event::now("halodeck", "info", "submitted job 4").with("job_id", 4).with("kind", "email").with("payload", "sent")).unwrap();
+        vault.append(Event::now("epoch", "info", "job 4 leased -> done").with("job_id", 4).with("to", "done")).unwrap();
+        let retried = Event::now("halodeck", "info", "submitted job 5").with("job_id", 5).with("kind", "email").with("payload", "retried");
+        vault.append(retried.with_correlation_id("req-5")).unwrap();
+        vault.append(Event::now("epoch", "info", "job 9 leased -> done").with("job_id", 9).with("to", "done").with_correlation_id("req-5")).unwrap();
+        let requeued = Event::now("halodeck", "info", "submitted job 6").with("job_id", 6).with("kind", "email").with("payload", "requeued");
+        vault.append(requeued).unwrap();
+        vault.append(Event::now("epoch", "info", "job 6 failed -> dead_lettered").with("job_id", 6).with("to", "dead_lettered")).unwrap();
+        vault.append(Event::now("epoch", "info", "job 6 dead_lettered -> queued").with("job_id", 6).with("to", "queued")).unwrap();
+
+        let mut sched = Scheduler::new();
+        let ids = sched.replay(&mut vault, Filter::new().source("halodeck").contains("submitted job")).unwrap();
+        assert_eq!(ids.len(), 3);
+        let first = sched.dequeue("email", Duration::from_secs(1)).unwrap();
+        assert_eq!((first.payload.as_str(), first.priority), ("urgent", 5));
+        assert_eq!(first.correlation_id.as_deref(), Some("req-3"));
+        assert_eq!(sched.dequeue("email", Duration::from_secs(1)).unwrap().payload, "hi");
+        assert_eq!(sched.dequeue("email", Duration::from_secs(1)).unwrap().payload, "requeued");
+        assert!(sched.dequeue("email", Duration::from_secs(1)).is_none());
+    }
+
+    #[test]
+    fn test_delayed_jobs() {
+        let mut sched = Scheduler::new();
+        let later = sched.enqueue_after("reminder", "later", Duration::from_millis(40));
+        let past = sched.enqueue_at("reminder", "overdue", SystemTime::now() - Duration::from_secs(60));
+        let tomorrow = sched.enqueue_at("reminder", "tomorrow", SystemTime::now() + Duration::from_secs(86_400));
+
+        assert_eq!(sched.dequeue("reminder", Duration::from_secs(1)).unwrap().id, past);
+        assert!(sched.dequeue("reminder", Duration::from_secs(1)).is_none());
+        sleep(Duration::from_millis(60));
+        assert_eq!(sched.dequeue("reminder", Duration::from_secs(1)).unwrap().id, later);
+
+        // The due time survives a snapshot round trip.
+        let snapshot = sched.snapshot();
+        let run_at = snapshot.queued[0].run_at_ms.unwrap();
+        assert_eq!(snapshot.queued[0].id, tomorrow);
+        assert!(run_at > now_ms() + 86_000_000);
+        let mut recovered = Scheduler::from_snapshot(snapshot);
+        let mut ids = [0; 2].map(|_| recovered.dequeue("reminder", Duration::from_secs(1)).unwrap().id);
+        ids.sort_unstable();
+        assert_eq!(ids, [later, past]);
+        assert!(recovered.dequeue("reminder", Duration::from_secs(1)).is_none());
+        assert_eq!(recovered.depth_of("reminder"), 1);
+    }
 }
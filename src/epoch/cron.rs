@@ -0,0 +1,430 @@
+use super::{now_ms, Scheduler};
+use crate::module::{Health, Module, Periodic, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const MINUTE_MS: u64 = 60_000;
+const DAY_MINUTES: u64 = 1_440;
+/// Far enough ahead to find any satisfiable schedule (Feb 29 on a given weekday
+/// recurs within 28 years).
+const SEARCH_DAYS: u64 = 366 * 28;
+
+/// Five-field cron schedule (`minute hour day-of-month month day-of-week`),
+/// evaluated in UTC. Fields take `*`, numbers, `a-b` ranges, `,` lists and `/n`
+/// steps; day-of-week runs 0-7 with both 0 and 7 meaning Sunday. As in classic
+/// cron, a job whose day-of-month and day-of-week are both restricted runs when
+/// either matches. `@hourly`, `@daily`, `@weekly`, `@monthly` and `@yearly` are
+/// accepted as shorthands.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct CronExpr {
+    source: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+/// Bitmask of the values `field` allows within `min..=max`.
+fn parse_field(field: &str, min: u64, max: u64) -> Result<u64> {
+    let mut mask = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u64>().ok().filter(|&s| s > 0).ok_or_else(|| format!("bad step in {part:?}"))?),
+            None => (part, 1),
+        };
+        let num = |s: &str| s.parse::<u64>().ok().filter(|n| (min..=max).contains(n)).ok_or_else(|| format!("{s:?} is outside {min}-{max}"));
+        let (lo, hi) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((lo, hi)) => (num(lo)?, num(hi)?),
+                // `5/15` means every 15 starting at 5.
+                None if step > 1 => (num(range)?, max),
+                None => (num(range)?, num(range)?),
+            },
+        };
+        if lo > hi {
+            return Err(format!("range {part:?} runs backwards").into());
+        }
+        for v in (lo..=hi).step_by(step as usize) {
+            mask |= 1 << v;
+        }
+    }
+    Ok(mask)
+}
+
+/// (month, day of month) for days since the Unix epoch.
+fn month_day(days: u64) -> (u64, u64) {
+    let z = days as i64 + 719_468;
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    (m as u64, d as u64)
+}
+
+impl CronExpr {
+    pub fn parse(expr: &str) -> Result<Self> {
+        let expanded = match expr.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            other => other,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!("cron expression {expr:?} needs 5 fields, found {}", fields.len()).into());
+        };
+        let field = |f: &str, min, max, name: &str| parse_field(f, min, max).map_err(|e| format!("cron {name} field: {e}"));
+        let mut weekdays = field(weekday, 0, 7, "day-of-week")?;
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & 0x7f;
+        }
+        Ok(Self {
+            source: expr.trim().to_string(),
+            minutes: field(minute, 0, 59, "minute")?,
+            hours: field(hour, 0, 23, "hour")?,
+            days: field(day, 1, 31, "day-of-month")?,
+            months: field(month, 1, 12, "month")?,
+            weekdays,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        })
+    }
+
+    fn runs_on(&self, days: u64) -> bool {
+        let (month, day) = month_day(days);
+        if self.months & (1 << month) == 0 {
+            return false;
+        }
+        // 1970-01-01 was a Thursday.
+        let on_day = self.days & (1 << day) != 0;
+        let on_weekday = self.weekdays & (1 << ((days + 4) % 7)) != 0;
+        if self.any_day || self.any_weekday { on_day && on_weekday } else { on_day || on_weekday }
+    }
+
+    /// First minute strictly after `after_ms` (ms since the Unix epoch) that the
+    /// schedule fires on, or None if it never does (e.g. `0 0 31 2 *`).
+    pub fn next_after(&self, after_ms: u64) -> Option<u64> {
+        let start = after_ms / MINUTE_MS + 1;
+        let first_day = start / DAY_MINUTES;
+        for days in first_day..first_day + SEARCH_DAYS {
+            if !self.runs_on(days) {
+                continue;
+            }
+            let from = if days == first_day { start % DAY_MINUTES } else { 0 };
+            let hit = (from..DAY_MINUTES).find(|m| self.hours & (1 << (m / 60)) != 0 && self.minutes & (1 << (m % 60)) != 0);
+            if let Some(m) = hit {
+                return Some((days * DAY_MINUTES + m) * MINUTE_MS);
+            }
+        }
+        None
+    }
+}
+
+impl FromStr for CronExpr {
+    type Err = crate::module::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+impl TryFrom<String> for CronExpr {
+    type Error = crate::module::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        Self::parse(&s)
+    }
+}
+
+impl From<CronExpr> for String {
+    fn from(expr: CronExpr) -> Self {
+        expr.source
+    }
+}
+
+impl fmt::Display for CronExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// A job enqueued whenever `cron` fires. `{name}` and `{scheduled_ms}` in the
+/// payload are replaced with the definition's name and the run's due time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecurringJob {
+    pub name: String,
+    pub cron: CronExpr,
+    pub kind: String,
+    #[serde(default)]
+    pub payload: String,
+    #[serde(default)]
+    pub priority: i32,
+    /// When the next run is due (ms since the Unix epoch); filled in on registration.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_run_ms: Option<u64>,
+}
+
+impl RecurringJob {
+    pub fn new<N: Into<String>, K: Into<String>, P: Into<String>>(name: N, cron: &str, kind: K, payload: P) -> Result<Self> {
+        Ok(Self {
+            name: name.into(),
+            cron: CronExpr::parse(cron)?,
+            kind: kind.into(),
+            payload: payload.into(),
+            priority: 0,
+            next_run_ms: None,
+        })
+    }
+
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    fn render(&self, scheduled_ms: u64) -> String {
+        self.payload.replace("{name}", &self.name).replace("{scheduled_ms}", &scheduled_ms.to_string())
+    }
+}
+
+/// Recurring job definitions, kept in a JSON file (when opened from one) along
+/// with each definition's next due time, so schedules and missed runs survive
+/// restarts. Call `tick` periodically to enqueue the runs that are due.
+#[derive(Debug, Default)]
+pub struct Recurring {
+    path: Option<PathBuf>,
+    jobs: BTreeMap<String, RecurringJob>,
+}
+
+impl Recurring {
+    /// Definitions held in memory only.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Definitions stored at `path` (e.g. `<data_dir>/epoch/recurring.json`),
+    /// loading any already there.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let jobs: Vec<RecurringJob> = match std::fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self { path: Some(path), jobs: jobs.into_iter().map(|job| (job.name.clone(), job)).collect() })
+    }
+
+    /// Add or replace the definition named `job.name`. Re-registering an unchanged
+    /// schedule keeps its pending due time, so restarts don't skip or repeat runs.
+    pub fn register(&mut self, mut job: RecurringJob) -> Result<()> {
+        job.next_run_ms = match self.jobs.get(&job.name) {
+            Some(old) if old.cron == job.cron && job.next_run_ms.is_none() => old.next_run_ms,
+            _ => job.next_run_ms.or_else(|| job.cron.next_after(now_ms())),
+        };
+        if job.next_run_ms.is_none() {
+            tracing::warn!(name = %job.name, cron = %job.cron, "recurring job never fires");
+        }
+        self.jobs.insert(job.name.clone(), job);
+        self.save()
+    }
+
+    /// Remove a definition. Returns whether it existed.
+    pub fn unregister(&mut self, name: &str) -> Result<bool> {
+        let removed = self.jobs.remove(name).is_some();
+        if removed {
+            self.save()?;
+        }
+        Ok(removed)
+    }
+
+    /// Register every one of `jobs` and unregister any other definition, e.g. to
+    /// follow `[[schedules]]` when an entry is removed from the config.
+    pub fn reconcile(&mut self, jobs: Vec<RecurringJob>) -> Result<()> {
+        let stale: Vec<String> = self.jobs.keys().filter(|name| !jobs.iter().any(|job| &job.name == *name)).cloned().collect();
+        for name in stale {
+            tracing::info!(name = %name, "unregistered recurring job");
+            self.unregister(&name)?;
+        }
+        jobs.into_iter().try_for_each(|job| self.register(job))
+    }
+
+    /// Definitions ordered by name.
+    pub fn list(&self) -> impl Iterator<Item = &RecurringJob> {
+        self.jobs.values()
+    }
+
+    /// Enqueue one job for every definition due at `now`. A definition that
+    /// missed several runs (e.g. while the process was down) runs once, then
+    /// resumes its schedule. Returns the new job ids.
+    pub fn tick(&mut self, sched: &mut Scheduler, now: SystemTime) -> Result<Vec<u64>> {
+        let now_ms = now.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64);
+        let mut ids = Vec::new();
+        for job in self.jobs.values_mut() {
+            let Some(due) = job.next_run_ms.filter(|&due| due <= now_ms) else { continue };
+            let id = sched.enqueue_with_priority(job.kind.clone(), job.render(due), job.priority);
+            tracing::info!(name = %job.name, id, kind = %job.kind, due, "enqueued recurring job");
+            job.next_run_ms = job.cron.next_after(now_ms);
+            ids.push(id);
+        }
+        if !ids.is_empty() {
+            self.save()?;
+        }
+        Ok(ids)
+    }
+
+    /// Write the definitions via `<path>.tmp` + rename.
+    fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else { return Ok(()) };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut tmp = path.as_os_str().to_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        let file = std::fs::File::create(&tmp)?;
+        serde_json::to_writer_pretty(&file, &self.jobs.values().collect::<Vec<_>>())?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+/// Runs `Recurring::tick` as a `Module`: `start` spawns a thread that enqueues due runs into
+/// `sched` every `interval`, `stop` joins it, and `health` reports a failed tick.
+pub struct RecurringService {
+    recurring: Arc<Mutex<Recurring>>,
+    sched: Arc<Mutex<Scheduler>>,
+    /// The first tick runs right away, catching up on runs missed while stopped.
+    task: Periodic,
+}
+
+impl RecurringService {
+    pub fn new(recurring: Recurring, sched: Arc<Mutex<Scheduler>>, interval: Duration) -> Self {
+        Self { recurring: Arc::new(Mutex::new(recurring)), sched, task: Periodic::new("recurring service", interval).immediate() }
+    }
+
+    pub fn recurring(&self) -> Arc<Mutex<Recurring>> {
+        self.recurring.clone()
+    }
+
+    /// Locks the definitions, then the scheduler. A failed job store write fails the tick.
+    fn tick(recurring: &Mutex<Recurring>, sched: &Mutex<Scheduler>) -> Result<Vec<u64>> {
+        let mut recurring = recurring.lock().map_err(|_| "recurring mutex poisoned")?;
+        let mut sched = sched.lock().map_err(|_| "scheduler mutex poisoned")?;
+        let enqueued = recurring.tick(&mut sched, SystemTime::now())?;
+        sched.take_store_error()?;
+        Ok(enqueued)
+    }
+}
+
+impl Module for RecurringService {
+    fn name(&self) -> &str { "recurring" }
+
+    fn start(&mut self) -> Result<()> {
+        let (recurring, sched) = (self.recurring.clone(), self.sched.clone());
+        self.task.start(move || Self::tick(&recurring, &sched).map(|_| ()))
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.task.stop()
+    }
+
+    fn health(&self) -> Health {
+        self.task.health("tick failed")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    // 2024-06-03 00:00 UTC, a Monday.
+    const MONDAY_MS: u64 = 1_717_372_800_000;
+    const HOUR_MS: u64 = 60 * MINUTE_MS;
+
+    #[test]
+    fn next_run_follows_the_schedule() {
+        let next = |expr: &str, after: u64| CronExpr::parse(expr).unwrap().next_after(after);
+        assert_eq!(next("*/15 * * * *", MONDAY_MS), Some(MONDAY_MS + 15 * MINUTE_MS));
+        assert_eq!(next("30 9 * * 1-5", MONDAY_MS), Some(MONDAY_MS + 9 * HOUR_MS + 30 * MINUTE_MS));
+        assert_eq!(next("0 12 * * 7", MONDAY_MS), Some(MONDAY_MS + 6 * 24 * HOUR_MS + 12 * HOUR_MS));
+        assert_eq!(next("@monthly", MONDAY_MS), Some(1_719_792_000_000)); // 2024-07-01
+        // Both day fields restricted: the 5th or any Monday, whichever comes first.
+        assert_eq!(next("0 0 5 * 1", MONDAY_MS), Some(MONDAY_MS + 2 * 24 * HOUR_MS));
+        assert_eq!(next("0 0 31 2 *", MONDAY_MS), None);
+        for bad in ["* * * *", "60 * * * *", "5-1 * * * *", "*/0 * * * *", "a * * * *"] {
+            assert!(CronExpr::parse(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn recurring_jobs_survive_restarts() {
+        let path = std::env::temp_dir().join(format!("epoch_recurring_{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let now = SystemTime::now();
+
+        let mut recurring = Recurring::open(&path).unwrap();
+        recurring.register(RecurringJob::new("digest", "0 * * * *", "email", "digest for {scheduled_ms}").unwrap().with_priority(2)).unwrap();
+        let due = recurring.list().next().unwrap().next_run_ms.unwrap();
+        let mut sched = Scheduler::new();
+        assert!(recurring.tick(&mut sched, now).unwrap().is_empty());
+
+        // Two missed hours produce one run, then the schedule resumes.
+        let mut reopened = Recurring::open(&path).unwrap();
+        reopened.register(RecurringJob::new("digest", "0 * * * *", "email", "digest for {scheduled_ms}").unwrap()).unwrap();
+        assert_eq!(reopened.list().next().unwrap().next_run_ms, Some(due));
+        let later = UNIX_EPOCH + Duration::from_millis(due + 2 * HOUR_MS + MINUTE_MS);
+        assert_eq!(reopened.tick(&mut sched, later).unwrap().len(), 1);
+        let job = sched.dequeue("email", Duration::from_secs(1)).unwrap();
+        assert_eq!(job.payload, format!("digest for {due}"));
+        assert_eq!(Recurring::open(&path).unwrap().list().next().unwrap().next_run_ms, Some(due + 3 * HOUR_MS));
+
+        assert!(reopened.unregister("digest").unwrap());
+        assert_eq!(Recurring::open(&path).unwrap().list().count(), 0);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn reconcile_follows_the_config() {
+        let mut recurring = Recurring::new();
+        recurring.register(RecurringJob::new("digest", "0 * * * *", "email", "").unwrap()).unwrap();
+        recurring.register(RecurringJob::new("cleanup", "@daily", "maintenance", "").unwrap()).unwrap();
+        recurring.reconcile(vec![RecurringJob::new("digest", "0 * * * *", "email", "").unwrap()]).unwrap();
+        assert_eq!(recurring.list().map(|job| job.name.as_str()).collect::<Vec<_>>(), ["digest"]);
+    }
+
+    #[test]
+    fn service_ticks_in_background() {
+        let mut recurring = Recurring::new();
+        let mut job = RecurringJob::new("digest", "* * * * *", "email", "due {scheduled_ms}").unwrap();
+        job.next_run_ms = Some(now_ms() - MINUTE_MS);
+        recurring.register(job).unwrap();
+        let sched = Arc::new(Mutex::new(Scheduler::new()));
+        let mut service = RecurringService::new(recurring, sched.clone(), Duration::from_secs(60));
+        service.start().unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while sched.lock().unwrap().depth() == 0 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(sched.lock().unwrap().depth_of("email"), 1);
+        assert_eq!(service.health(), Health::Healthy);
+        service.stop().unwrap();
+        assert!(service.recurring().lock().unwrap().list().next().unwrap().next_run_ms.unwrap() > now_ms());
+    }
+}
@@ -0,0 +1,539 @@
+use super::{JobRecord, SchedulerSnapshot};
+use crate::module::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// One job state change, as handed to a `JobStore`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum JobOp {
+    /// A new job was queued.
+    Enqueue { job: JobRecord },
+    /// The job was leased for `lease_ms`, using up one attempt.
+    Lease { id: u64, lease_ms: u64 },
+    /// The job went back to its queue (retry, expired or released lease).
+    Requeue { id: u64 },
+    Complete { id: u64 },
+    /// The job failed; a `Requeue` or `DeadLetter` follows.
+    Fail { id: u64 },
+    DeadLetter { id: u64 },
+    /// A dead-lettered job went back to its queue with its attempts reset.
+    RequeueDead { id: u64 },
+    /// The job was discarded by a fail decider (no `Fail` before it).
+    Drop { id: u64 },
+    /// Ids below this are taken, even by jobs no longer stored.
+    NextId { id: u64 },
+}
+
+/// Where a `Scheduler` persists its jobs. Plug one in with `Scheduler::with_store`.
+pub trait JobStore: Send {
+    /// Persist one state change.
+    fn record(&mut self, op: &JobOp) -> Result<()>;
+
+    /// Recover the stored jobs. Jobs that were leased come back queued, so they
+    /// run again after a crash.
+    fn load(&mut self) -> Result<SchedulerSnapshot>;
+
+    /// Bring the store in line with `snapshot`, the state just recovered from it
+    /// (leased jobs now queued). A journal rewrites itself to just these jobs.
+    fn compact(&mut self, snapshot: &SchedulerSnapshot) -> Result<()>;
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Queued,
+    Leased,
+    Dead,
+}
+
+/// Fold a sequence of ops into the jobs still live at the end: queued jobs in
+/// the order they (re)joined their queue, then leased jobs by id.
+pub(super) fn replay<I: IntoIterator<Item = JobOp>>(ops: I) -> SchedulerSnapshot {
+    let mut next_id = 1;
+    let mut jobs: BTreeMap<u64, (JobRecord, State, u64)> = BTreeMap::new();
+    let mut tick = 0;
+    for op in ops {
+        tick += 1;
+        match op {
+            JobOp::Enqueue { job } => {
+                next_id = next_id.max(job.id + 1);
+                jobs.insert(job.id, (job, State::Queued, tick));
+            }
+            JobOp::Lease { id, .. } => {
+                if let Some((job, state, _)) = jobs.get_mut(&id) {
+                    job.attempts = job.attempts.saturating_add(1);
+                    *state = State::Leased;
+                }
+            }
+            JobOp::Requeue { id } => {
+                if let Some((_, state, at)) = jobs.get_mut(&id) {
+                    *state = State::Queued;
+                    *at = tick;
+                }
+            }
+            JobOp::RequeueDead { id } => {
+                if let Some((job, state, at)) = jobs.get_mut(&id) {
+                    job.attempts = 0;
+                    *state = State::Queued;
+                    *at = tick;
+                }
+            }
+            JobOp::DeadLetter { id } => {
+                if let Some((_, state, at)) = jobs.get_mut(&id) {
+                    *state = State::Dead;
+                    *at = tick;
+                }
+            }
+            JobOp::Complete { id } | JobOp::Drop { id } => {
+                jobs.remove(&id);
+            }
+            JobOp::Fail { .. } => {}
+            JobOp::NextId { id } => next_id = next_id.max(id),
+        }
+    }
+    let mut queued: Vec<&(JobRecord, State, u64)> = jobs.values().filter(|(_, s, _)| *s == State::Queued).collect();
+    queued.sort_by_key(|(_, _, at)| *at);
+    let leased = jobs.values().filter(|(_, s, _)| *s == State::Leased);
+    let mut dead: Vec<&(JobRecord, State, u64)> = jobs.values().filter(|(_, s, _)| *s == State::Dead).collect();
+    dead.sort_by_key(|(_, _, at)| *at);
+    SchedulerSnapshot {
+        next_id,
+        queued: queued.into_iter().chain(leased).map(|(job, _, _)| job.clone()).collect(),
+        dead_letters: dead.into_iter().map(|(job, _, _)| job.clone()).collect(),
+    }
+}
+
+/// Ops that rebuild `snapshot` when replayed.
+pub(super) fn snapshot_ops(snapshot: &SchedulerSnapshot) -> Vec<JobOp> {
+    let mut ops = vec![JobOp::NextId { id: snapshot.next_id }];
+    ops.extend(snapshot.queued.iter().map(|job| JobOp::Enqueue { job: job.clone() }));
+    for job in &snapshot.dead_letters {
+        ops.push(JobOp::Enqueue { job: job.clone() });
+        ops.push(JobOp::DeadLetter { id: job.id });
+    }
+    ops
+}
+
+/// Take an exclusive lock on `<path>.lock`, held until the returned file is dropped.
+fn lock_exclusive(path: &Path, what: &str) -> Result<File> {
+    let mut lock_path = path.as_os_str().to_os_string();
+    lock_path.push(".lock");
+    let lock = OpenOptions::new().create(true).truncate(false).write(true).open(PathBuf::from(lock_path))?;
+    match lock.try_lock() {
+        Ok(()) => Ok(lock),
+        Err(std::fs::TryLockError::WouldBlock) => Err(format!("{what} {} is in use by another process", path.display()).into()),
+        Err(std::fs::TryLockError::Error(e)) => Err(e.into()),
+    }
+}
+
+/// Append-only journal of `JobOp`s, one JSON object per line. Each op is
+/// written straight to the file, so it survives the process exiting. The store
+/// holds an exclusive lock on `<path>.lock` (not the journal itself, which
+/// compaction replaces) until dropped, so a second process can't append to or
+/// compact a journal that is in use. Besides on open, the journal compacts itself
+/// once it reaches `compact_after` bytes and has doubled since its last compaction.
+pub struct JournalStore {
+    path: PathBuf,
+    file: File,
+    _lock: File,
+    len: u64,
+    compacted_len: u64,
+    compact_after: u64,
+}
+
+/// Default size a journal may grow to before it compacts itself.
+const COMPACT_AFTER_BYTES: u64 = 1 << 20;
+
+impl JournalStore {
+    /// Fails if another `JournalStore` (e.g. the daemon's) has `path` open.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let lock = lock_exclusive(&path, "job journal")?;
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let len = file.metadata()?.len();
+        Ok(Self { path, file, _lock: lock, len, compacted_len: 0, compact_after: COMPACT_AFTER_BYTES })
+    }
+
+    /// Compact once the journal reaches `bytes` (default 1 MiB) and has doubled
+    /// since it was last compacted.
+    pub fn compact_after(mut self, bytes: u64) -> Self {
+        self.compact_after = bytes;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl JobStore for JournalStore {
+    fn record(&mut self, op: &JobOp) -> Result<()> {
+        let mut line = serde_json::to_vec(op)?;
+        line.push(b'\n');
+        self.file.write_all(&line)?;
+        self.len += line.len() as u64;
+        if self.len >= self.compact_after && self.len >= self.compacted_len.saturating_mul(2) {
+            // Rebuilt from the file itself, so it matches what a restart would recover.
+            let snapshot = self.load()?;
+            self.compact(&snapshot)?;
+        }
+        Ok(())
+    }
+
+    fn load(&mut self) -> Result<SchedulerSnapshot> {
+        let mut ops = Vec::new();
+        for (i, line) in BufReader::new(File::open(&self.path)?).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() { continue; }
+            match serde_json::from_str(&line) {
+                Ok(op) => ops.push(op),
+                // Most likely a line torn by a crash mid-write.
+                Err(e) => tracing::warn!(path = %self.path.display(), line = i + 1, error = %e, "skipping unreadable journal line"),
+            }
+        }
+        Ok(replay(ops))
+    }
+
+    fn compact(&mut self, snapshot: &SchedulerSnapshot) -> Result<()> {
+        let mut tmp = self.path.as_os_str().to_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        let mut out = BufWriter::new(File::create(&tmp)?);
+        for op in snapshot_ops(snapshot) {
+            serde_json::to_writer(&mut out, &op)?;
+            out.write_all(b"\n")?;
+        }
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        std::fs::rename(&tmp, &self.path)?;
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        self.len = self.file.metadata()?.len();
+        self.compacted_len = self.len;
+        Ok(())
+    }
+}
+
+/// SQLite table `jobs` holding each job's current state (`queued`, `leased`,
+/// `failed`, `dead_lettered`, `done` or `dropped`), attempts, lease expiry and,
+/// for delayed jobs, when they become due.
+/// Finished jobs are kept, so `connection()` can answer ad-hoc questions about
+/// job history.
+///
+/// The table mirrors a `Scheduler` whose queues live in memory; it is not a
+/// shared queue. Only one process may drive a store at a time, so `open` holds
+/// an exclusive lock on `<path>.lock` until the store is dropped. Other
+/// processes can still read the database.
+#[cfg(feature = "sqlite")]
+pub struct SqliteJobStore {
+    conn: rusqlite::Connection,
+    _lock: Option<File>,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteJobStore {
+    /// Fails if another `SqliteJobStore` has `path` open.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let lock = lock_exclusive(path.as_ref(), "job database")?;
+        Self::init(rusqlite::Connection::open(path)?, Some(lock))
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        Self::init(rusqlite::Connection::open_in_memory()?, None)
+    }
+
+    fn init(conn: rusqlite::Connection, lock: Option<File>) -> Result<Self> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS jobs (
+                id       INTEGER PRIMARY KEY,
+                kind     TEXT NOT NULL,
+                payload  TEXT NOT NULL,
+                priority INTEGER NOT NULL DEFAULT 0,
+                correlation_id TEXT,
+                state    TEXT NOT NULL,
+                attempts INTEGER NOT NULL DEFAULT 0,
+                lease_expires_ms INTEGER,
+                run_at_ms INTEGER,
+                updated_ms INTEGER NOT NULL,
+                seq      INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS jobs_state ON jobs (state, kind);
+            CREATE TABLE IF NOT EXISTS scheduler_meta (
+                key   TEXT PRIMARY KEY,
+                value INTEGER NOT NULL
+            );",
+        )?;
+        Ok(Self { conn, _lock: lock })
+    }
+
+    pub fn connection(&self) -> &rusqlite::Connection {
+        &self.conn
+    }
+
+    fn now_ms() -> i64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as i64)
+    }
+
+    /// Move a job to `state`; `requeued` sends it to the back of the queue order.
+    fn set_state(&self, id: u64, state: &str, requeued: bool) -> Result<()> {
+        self.conn.execute(
+            "UPDATE jobs SET state = ?2, lease_expires_ms = NULL, updated_ms = ?3,
+                seq = CASE WHEN ?4 THEN (SELECT COALESCE(MAX(seq), 0) + 1 FROM jobs) ELSE seq END
+             WHERE id = ?1",
+            rusqlite::params![id as i64, state, Self::now_ms(), requeued],
+        )?;
+        Ok(())
+    }
+
+    fn set_next_id(conn: &rusqlite::Connection, id: u64) -> Result<()> {
+        conn.execute(
+            "INSERT INTO scheduler_meta (key, value) VALUES ('next_id', ?1)
+             ON CONFLICT (key) DO UPDATE SET value = MAX(value, excluded.value)",
+            [id as i64],
+        )?;
+        Ok(())
+    }
+
+    fn jobs_in(&self, states: &str, order: &str) -> Result<Vec<JobRecord>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT id, kind, payload, attempts, priority, correlation_id, run_at_ms FROM jobs WHERE state IN ({states}) ORDER BY {order}"
+        ))?;
+        let rows = stmt.query_map([], |row| {
+            Ok(JobRecord {
+                id: row.get::<_, i64>(0)? as u64,
+                kind: row.get(1)?,
+                payload: row.get(2)?,
+                attempts: row.get(3)?,
+                priority: row.get(4)?,
+                correlation_id: row.get(5)?,
+                run_at_ms: row.get::<_, Option<i64>>(6)?.map(|t| t as u64),
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl JobStore for SqliteJobStore {
+    fn record(&mut self, op: &JobOp) -> Result<()> {
+        match op {
+            JobOp::Enqueue { job } => {
+                self.conn.execute(
+                    "INSERT OR REPLACE INTO jobs (id, kind, payload, priority, correlation_id, state, attempts, run_at_ms, updated_ms, seq)
+                     VALUES (?1, ?2, ?3, ?4, ?5, 'queued', ?6, ?7, ?8, (SELECT COALESCE(MAX(seq), 0) + 1 FROM jobs))",
+                    rusqlite::params![
+                        job.id as i64,
+                        job.kind,
+                        job.payload,
+                        job.priority,
+                        job.correlation_id,
+                        job.attempts,
+                        job.run_at_ms.map(|t| t as i64),
+                        Self::now_ms(),
+                    ],
+                )?;
+            }
+            JobOp::Lease { id, lease_ms } => {
+                let now = Self::now_ms();
+                self.conn.execute(
+                    "UPDATE jobs SET state = 'leased', attempts = attempts + 1, lease_expires_ms = ?2, updated_ms = ?3 WHERE id = ?1",
+                    rusqlite::params![*id as i64, now.saturating_add(*lease_ms as i64), now],
+                )?;
+            }
+            JobOp::Requeue { id } => self.set_state(*id, "queued", true)?,
+            JobOp::Complete { id } => self.set_state(*id, "done", false)?,
+            JobOp::Fail { id } => self.set_state(*id, "failed", false)?,
+            JobOp::DeadLetter { id } => self.set_state(*id, "dead_lettered", true)?,
+            JobOp::RequeueDead { id } => {
+                self.conn.execute("UPDATE jobs SET attempts = 0 WHERE id = ?1", [*id as i64])?;
+                self.set_state(*id, "queued", true)?;
+            }
+            JobOp::Drop { id } => self.set_state(*id, "dropped", false)?,
+            JobOp::NextId { id } => Self::set_next_id(&self.conn, *id)?,
+        }
+        Ok(())
+    }
+
+    fn load(&mut self) -> Result<SchedulerSnapshot> {
+        let mut queued = self.jobs_in("'queued'", "seq")?;
+        queued.extend(self.jobs_in("'leased', 'failed'", "id")?);
+        let next_id: i64 = self.conn.query_row(
+            "SELECT MAX(COALESCE((SELECT value FROM scheduler_meta WHERE key = 'next_id'), 1),
+                        COALESCE((SELECT MAX(id) FROM jobs), 0) + 1)",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(SchedulerSnapshot {
+            next_id: next_id as u64,
+            queued,
+            dead_letters: self.jobs_in("'dead_lettered'", "seq")?,
+        })
+    }
+
+    // Requeue what was in flight; finished rows stay as history.
+    fn compact(&mut self, snapshot: &SchedulerSnapshot) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            "UPDATE jobs SET state = 'queued', lease_expires_ms = NULL, updated_ms = ?1,
+                seq = (SELECT COALESCE(MAX(seq), 0) FROM jobs) + id
+             WHERE state IN ('leased', 'failed')",
+            [Self::now_ms()],
+        )?;
+        Self::set_next_id(&tx, snapshot.next_id)?;
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::epoch::{RetryPolicy, Scheduler};
+    use std::time::Duration;
+
+    #[test]
+    fn jobs_survive_a_restart() {
+        let dir = std::env::temp_dir().join(format!("epoch_journal_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let mut sched = Scheduler::open(&dir).unwrap();
+        // A second opener (e.g. the CLI next to a running daemon) is turned away.
+        let err = Scheduler::open(&dir).err().unwrap();
+        assert!(err.to_string().contains("in use"), "{err}");
+        sched.set_retry_policy("email", RetryPolicy { max_attempts: Some(1), ..Default::default() });
+        let done = sched.enqueue("email", "done");
+        let dead = sched.enqueue("email", "dead");
+        let leased = sched.enqueue_with_priority("report", "leased", 2);
+        let waiting = sched.enqueue("report", "waiting");
+        sched.dequeue("email", Duration::from_secs(30)).unwrap();
+        sched.complete(done).unwrap();
+        sched.dequeue("email", Duration::from_secs(30)).unwrap();
+        sched.fail(dead).unwrap();
+        sched.dequeue("report", Duration::from_secs(30)).unwrap();
+        drop(sched);
+
+        // The leased job comes back queued with its attempt counted; finished jobs stay gone.
+        let mut sched = Scheduler::open(&dir).unwrap();
+        assert_eq!((sched.depth_of("email"), sched.depth_of("report")), (0, 2));
+        assert_eq!(sched.dead_letter("email")[0].id, dead);
+        let job = sched.dequeue("report", Duration::from_secs(30)).unwrap();
+        assert_eq!((job.id, job.attempts, job.priority), (leased, 2, 2));
+        assert_eq!(sched.enqueue("email", "next"), waiting + 1);
+        sched.complete(leased).unwrap();
+        drop(sched);
+
+        // Reopening compacts the journal to the live jobs.
+        let mut sched = Scheduler::open(&dir).unwrap();
+        assert_eq!(sched.depth(), 2);
+        let journal = std::fs::read_to_string(dir.join("journal.ndjson")).unwrap();
+        assert_eq!(journal.lines().count(), 5);
+
+        // A re-driven dead letter comes back with fresh attempts.
+        sched.requeue_dead(dead).unwrap();
+        drop(sched);
+        let sched = Scheduler::open(&dir).unwrap();
+        assert!(sched.dead_letters().is_empty());
+        assert_eq!(sched.snapshot().queued.iter().find(|j| j.id == dead).map(|j| j.attempts), Some(0));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn store_errors_surface_on_the_next_result() {
+        use super::{JobOp, JobStore};
+        use crate::epoch::SchedulerSnapshot;
+        use crate::module::Result;
+        struct Full;
+        impl JobStore for Full {
+            fn record(&mut self, _: &JobOp) -> Result<()> {
+                Err("disk full".into())
+            }
+            fn load(&mut self) -> Result<SchedulerSnapshot> {
+                Ok(SchedulerSnapshot { next_id: 1, queued: Vec::new(), dead_letters: Vec::new() })
+            }
+            fn compact(&mut self, _: &SchedulerSnapshot) -> Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut sched = Scheduler::with_store(Full).unwrap();
+        let id = sched.enqueue("email", "hello");
+        sched.dequeue("email", Duration::from_secs(30)).unwrap();
+        let err = sched.complete(id).unwrap_err();
+        assert!(err.to_string().contains("disk full"), "{err}");
+        // The completion itself went through, and the error is reported once.
+        assert_eq!(sched.stats().completed_total, 1);
+        assert!(sched.take_store_error().is_ok());
+    }
+
+    #[test]
+    fn journal_compacts_as_it_grows() {
+        use super::JournalStore;
+        let dir = std::env::temp_dir().join(format!("epoch_journal_growth_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("journal.ndjson");
+
+        let mut sched = Scheduler::with_store(JournalStore::open(&path).unwrap().compact_after(4096)).unwrap();
+        let kept = sched.enqueue("report", "kept");
+        for i in 0..200 {
+            let id = sched.enqueue("email", format!("message {i}"));
+            sched.dequeue("email", Duration::from_secs(30)).unwrap();
+            sched.complete(id).unwrap();
+        }
+        let leased = sched.enqueue("email", "leased");
+        sched.dequeue("email", Duration::from_secs(30)).unwrap();
+        // 600 ops would be well past 4 KiB; compaction keeps only the live jobs around.
+        assert!(std::fs::metadata(&path).unwrap().len() < 8192);
+        drop(sched);
+
+        let mut sched = Scheduler::open(&dir).unwrap();
+        assert_eq!(sched.depth(), 2);
+        assert_eq!(sched.dequeue("report", Duration::from_secs(30)).unwrap().id, kept);
+        assert_eq!(sched.dequeue("email", Duration::from_secs(30)).unwrap().id, leased);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn sqlite_store_keeps_queryable_state() {
+        use super::SqliteJobStore;
+        let path = std::env::temp_dir().join(format!("epoch_jobs_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut sched = Scheduler::with_store(SqliteJobStore::open(&path).unwrap()).unwrap();
+        let err = SqliteJobStore::open(&path).err().unwrap();
+        assert!(err.to_string().contains("in use"), "{err}");
+        let done = sched.enqueue("email", "done");
+        let leased = sched.enqueue("email", "leased");
+        sched.dequeue("email", Duration::from_secs(30)).unwrap();
+        sched.complete(done).unwrap();
+        sched.dequeue("email", Duration::from_secs(30)).unwrap();
+        drop(sched);
+
+        let store = SqliteJobStore::open(&path).unwrap();
+        let (state, attempts, expiry): (String, u32, Option<i64>) = store
+            .connection()
+            .query_row("SELECT state, attempts, lease_expires_ms FROM jobs WHERE id = ?1", [leased as i64], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .unwrap();
+        assert_eq!((state.as_str(), attempts, expiry.is_some()), ("leased", 1, true));
+
+        // Reopening requeues the leased job; the finished one stays as history.
+        let mut sched = Scheduler::with_store(store).unwrap();
+        assert_eq!(sched.depth_of("email"), 1);
+        let job = sched.dequeue("email", Duration::from_secs(30)).unwrap();
+        assert_eq!((job.id, job.attempts), (leased, 2));
+        assert_eq!(sched.enqueue("email", "next"), leased + 1);
+        drop(sched);
+        let store = SqliteJobStore::open(&path).unwrap();
+        let finished: i64 = store.connection().query_row("SELECT COUNT(*) FROM jobs WHERE state = 'done'", [], |row| row.get(0)).unwrap();
+        assert_eq!(finished, 1);
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(path.with_extension("db.lock"));
+    }
+}
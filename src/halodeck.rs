@@ -1,5 +1,10 @@
-use crate::{Result, Scheduler, Vaultline, Event};
+use crate::{telemetry, Result, Scheduler, Vaultline, Event};
+use crate::epoch::JobRecord;
+use crate::vaultline::{self, CompactOptions, ExportFormat, Filter, Format};
 use clap::{Parser, Subcommand};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 // CLI definition
 #[derive(Parser, Debug)]
@@ -18,17 +23,162 @@ pub enum Command {
     Logs {
         #[arg(long, default_value_t = 20)]
         tail: usize,
+        /// Only events at this level
+        #[arg(long)]
+        level: Option<String>,
+        /// Only events from this source
+        #[arg(long)]
+        source: Option<String>,
+        /// Only events whose message contains this text
+        #[arg(long)]
+        contains: Option<String>,
+        /// Only events tagged with this correlation id
+        #[arg(long)]
+        correlation_id: Option<String>,
+        /// Query such as 'level>=warn AND source=epoch AND message~"lease"'; the flags above narrow it further and must agree with it
+        #[arg(long)]
+        query: Option<String>,
     },
 
     // Submit a new job to queue
     Submit {
         kind: String,
         payload: String,
+        /// Higher runs first within the kind; equal priorities run oldest first
+        #[arg(long, default_value_t = 0, allow_negative_numbers = true)]
+        priority: i32,
+        /// Print {"id":N,"kind":"...","queued":true} so scripts can capture the job id
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Remove events older than a duration (e.g. "7d", "24h", "30m") from the log
+    Compact {
+        #[arg(long, value_parser = parse_duration)]
+        older_than: Duration,
+    },
+
+    /// Write events to a new CSV/JSON (or Parquet, with the `parquet` feature) file
+    Export {
+        output: PathBuf,
+        #[arg(long, value_enum, default_value_t = ExportFormat::Csv)]
+        format: ExportFormat,
+        /// Only events at this level
+        #[arg(long)]
+        level: Option<String>,
+        /// Only events from this source
+        #[arg(long)]
+        source: Option<String>,
+        /// Only events whose message contains this text
+        #[arg(long)]
+        contains: Option<String>,
+    },
+
+    /// Write every stored event to a self-contained, checksummed snapshot file
+    Snapshot {
+        output: PathBuf,
+    },
+
+    /// Rebuild an empty vaultline from a snapshot file
+    Restore {
+        input: PathBuf,
+    },
+
+    /// Summarize the log: counts by level and source, time span, disk and memory use
+    Stats {
+        /// Print the summary as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Copy a vaultline file into a new file using another record format
+    Convert {
+        input: PathBuf,
+        output: PathBuf,
+        #[arg(long, value_enum, default_value_t = Format::Ndjson)]
+        from: Format,
+        #[arg(long, value_enum)]
+        to: Format,
+    },
+
+    /// Maintenance commands for the vaultline files
+    Vault {
+        #[command(subcommand)]
+        command: VaultCommand,
+    },
+
+    /// Inspect and re-drive dead-lettered jobs
+    Dead {
+        #[command(subcommand)]
+        command: DeadCommand,
     },
 }
 
+#[derive(Subcommand, Debug)]
+pub enum DeadCommand {
+    /// List dead-lettered jobs, oldest first
+    List {
+        /// Only jobs of this kind
+        #[arg(long)]
+        kind: Option<String>,
+        /// Print one JSON record per job
+        #[arg(long)]
+        json: bool,
+    },
+    /// Put a dead-lettered job back on its queue with its attempts reset
+    Requeue { id: u64 },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum VaultCommand {
+    /// Check every segment for malformed lines, checksum failures, out-of-order
+    /// timestamps, sequence gaps and hash-chain breaks; fails if any are found
+    Verify {
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+// Parse a human duration: a whole number followed by s, m, h or d.
+fn parse_duration(s: &str) -> std::result::Result<Duration, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).ok_or_else(|| format!("missing unit in {s:?} (use s, m, h or d)"))?;
+    let (num, unit) = s.split_at(split);
+    let n: u64 = num.parse().map_err(|_| format!("invalid number in {s:?}"))?;
+    let scale = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86_400,
+        other => return Err(format!("unknown unit {other:?} (use s, m, h or d)")),
+    };
+    let secs = n.checked_mul(scale).ok_or_else(|| format!("duration {s:?} is too large"))?;
+    Ok(Duration::from_secs(secs))
+}
+
+// A `logs` flag combined with the `--query` clause for the same field. Both
+// must hold, so differing values are an error rather than one replacing the other.
+fn narrow(flag: &str, from_flag: Option<String>, from_query: Option<String>) -> Result<Option<String>> {
+    match (from_flag, from_query) {
+        (Some(a), Some(b)) if a != b => Err(format!("--{flag} {a:?} contradicts the query's {b:?}").into()),
+        (a, b) => Ok(a.or(b)),
+    }
+}
+
 impl Cli {
+    // Whether the command reads or changes the job queue. Others can run
+    // against the log alone, e.g. while the daemon holds the scheduler.
+    pub fn uses_scheduler(&self) -> bool {
+        matches!(self.command, Command::Status | Command::Submit { .. } | Command::Dead { .. })
+    }
+
     pub fn run(self, sched: &mut Scheduler, vault: &mut Vaultline) -> Result<()> {
+        self.run_to(sched, vault, &mut std::io::stdout())
+    }
+
+    // Same as `run`, writing command output to `out` instead of stdout.
+    pub fn run_to(self, sched: &mut Scheduler, vault: &mut Vaultline, out: &mut dyn Write) -> Result<()> {
         match self.command {
             Command::Status => {
                 let depth = sched.depth();
@@ -37,19 +187,134 @@ impl Cli {
                 let _ = vault.append(Event::now("halodeck", "info", format!("status depth={depth} leased={leased}")));
                 Ok(())
             }
-            Command::Logs { tail } => {
-                for event in vault.tail(tail) {
-                    println!(
+            Command::Logs { tail, level, source, contains, correlation_id, query } => {
+                let query = query.as_deref().map(Filter::parse).transpose()?.unwrap_or_default();
+                let filter = Filter {
+                    level: narrow("level", level, query.level)?,
+                    source: narrow("source", source, query.source)?,
+                    contains: narrow("contains", contains, query.contains)?,
+                    correlation_id: narrow("correlation-id", correlation_id, query.correlation_id)?,
+                    ..query
+                };
+                // Read backwards from the end of the log so only the tail is parsed.
+                for event in &vault.tail_disk_filtered(tail, filter)? {
+                    writeln!(
+                        out,
                         "{} [{}] {}: {}",
                         event.ts_ms, event.level, event.source, event.message
-                    );
+                    )?;
+                }
+                Ok(())
+            }
+            Command::Submit { kind, payload, priority, json } => {
+                // Everything the job does is tagged with this id, from submission to completion.
+                let correlation_id = telemetry::new_trace_id();
+                let _span = tracing::info_span!("submit", correlation_id = %correlation_id).entered();
+                let id = sched.enqueue_with_priority(kind.clone(), payload.clone(), priority);
+                // A job the journal didn't take would be lost when this process exits.
+                sched.take_store_error()?;
+                tracing::info!(id, kind = %kind, priority, "submitted job");
+                if json {
+                    writeln!(out, "{}", serde_json::json!({ "id": id, "kind": kind, "queued": true }))?;
+                }
+                // kind, payload and priority let `Scheduler::replay` re-enqueue the job after a crash.
+                let submitted = Event::now("halodeck", "info", format!("submitted job {id} to {kind}"))
+                    .with("job_id", id)
+                    .with("kind", kind)
+                    .with("payload", payload)
+                    .with("priority", priority);
+                let _ = vault.append(submitted.with_correlation_id(correlation_id));
+                Ok(())
+            }
+            Command::Compact { older_than } => {
+                let now_ms = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+                let cutoff = now_ms.saturating_sub(older_than.as_millis());
+                let stats = vault.compact_with(&CompactOptions { before_ms: Some(cutoff), ..Default::default() })?;
+                writeln!(out, "removed {} events ({} kept)", stats.removed(), stats.kept)?;
+                tracing::info!(removed = stats.removed(), kept = stats.kept, "compacted vaultline");
+                let _ = vault.append(Event::now("halodeck", "info", format!("compacted log, removed {} events", stats.removed())));
+                Ok(())
+            }
+            Command::Export { output, format, level, source, contains } => {
+                let filter = Filter { level, source, contains, ..Filter::default() };
+                let n = vault.export(format, &output, filter)?;
+                writeln!(out, "exported {n} events to {}", output.display())?;
+                Ok(())
+            }
+            Command::Snapshot { output } => {
+                let meta = vault.snapshot(&output)?;
+                writeln!(out, "snapshot of {} events written to {}", meta.events, output.display())?;
+                Ok(())
+            }
+            Command::Restore { input } => {
+                let meta = vault.restore(&input)?;
+                writeln!(out, "restored {} events from {}", meta.events, input.display())?;
+                Ok(())
+            }
+            Command::Stats { json } => {
+                let stats = vault.stats()?;
+                if json {
+                    writeln!(out, "{}", serde_json::to_string(&stats)?)?;
+                    return Ok(());
+                }
+                let span = match (stats.oldest_ms, stats.newest_ms) {
+                    (Some(oldest), Some(newest)) => format!("{oldest}..{newest}"),
+                    _ => "-".to_string(),
+                };
+                writeln!(out, "events: {} ({} in memory, ~{} bytes)", stats.total, stats.in_memory, stats.memory_bytes)?;
+                writeln!(out, "span: {span}")?;
+                writeln!(out, "disk: {} bytes in {} segments", stats.disk_bytes, stats.segments)?;
+                for (level, n) in &stats.by_level {
+                    writeln!(out, "level {level}: {n}")?;
+                }
+                for (source, n) in &stats.by_source {
+                    writeln!(out, "source {source}: {n}")?;
+                }
+                Ok(())
+            }
+            Command::Convert { input, output, from, to } => {
+                let n = vaultline::convert(&input, from, &output, to)?;
+                writeln!(out, "converted {n} events to {}", output.display())?;
+                Ok(())
+            }
+            Command::Vault { command: VaultCommand::Verify { json } } => {
+                let report = vault.verify()?;
+                if json {
+                    writeln!(out, "{}", serde_json::to_string(&report)?)?;
+                } else {
+                    writeln!(out, "records: {} in {} segments", report.records, report.segments)?;
+                    writeln!(out, "malformed: {}", report.malformed)?;
+                    writeln!(out, "checksum failures: {} ({} checksummed)", report.checksum_failures, report.checksummed)?;
+                    writeln!(out, "out of order: {}", report.out_of_order)?;
+                    writeln!(out, "seq gaps: {} ({} sequenced)", report.seq_gaps, report.sequenced)?;
+                    writeln!(out, "chain breaks: {} ({} chained)", report.breaks.len(), report.chained)?;
+                    for issue in &report.issues {
+                        writeln!(out, "  {issue}")?;
+                    }
+                }
+                if !report.is_intact() {
+                    return Err("vaultline failed verification".into());
+                }
+                Ok(())
+            }
+            Command::Dead { command: DeadCommand::List { kind, json } } => {
+                let jobs: Vec<&crate::Job> = match &kind {
+                    Some(kind) => sched.dead_letter(kind),
+                    None => sched.dead_letters().iter().collect(),
+                };
+                for job in jobs {
+                    if json {
+                        writeln!(out, "{}", serde_json::to_string(&JobRecord::from(job))?)?;
+                    } else {
+                        writeln!(out, "{} {} attempts={} {}", job.id, job.kind, job.attempts, job.payload)?;
+                    }
                 }
                 Ok(())
             }
-            Command::Submit { kind, payload } => {
-                let id = sched.enqueue(kind.clone(), payload.clone());
-                tracing::info!(id, kind = %kind, "submitted job");
-                let _ = vault.append(Event::now("halodeck", "info", format!("submitted job {id} to {kind}")));
+            Command::Dead { command: DeadCommand::Requeue { id } } => {
+                sched.requeue_dead(id)?;
+                writeln!(out, "requeued job {id}")?;
+                let _ = vault.append(Event::now("halodeck", "info", format!("requeued dead-lettered job {id}")).with("job_id", id));
                 Ok(())
             }
         }
@@ -76,7 +341,10 @@ mod tests {
         let args = vec!["halodeck", "logs", "--tail", "10"];
         let cli = Cli::parse_from(args);
         match cli.command {
-            Command::Logs { tail } => assert_eq!(tail, 10),
+            Command::Logs { tail, level, .. } => {
+                assert_eq!(tail, 10);
+                assert_eq!(level, None);
+            }
             _ => panic!("Expected Logs command"),
         }
     }
@@ -86,11 +354,298 @@ mod tests {
         let args = vec!["halodeck", "submit", "email", "Welcome to Hyperion!"];
         let cli = Cli::parse_from(args);
         match cli.command {
-            Command::Submit { kind, payload } => {
+            Command::Submit { kind, payload, priority, json } => {
                 assert_eq!(kind, "email");
                 assert_eq!(payload, "Welcome to Hyperion!");
+                assert_eq!(priority, 0);
+                assert!(!json);
             }
             _ => panic!("Expected Submit command"),
         }
     }
+
+    #[test]
+    fn test_cli_compact() {
+        let cli = Cli::parse_from(["halodeck", "compact", "--older-than", "7d"]);
+        match cli.command {
+            Command::Compact { older_than } => assert_eq!(older_than, Duration::from_secs(7 * 86_400)),
+            _ => panic!("Expected Compact command"),
+        }
+        assert_eq!(parse_duration("24h").unwrap(), Duration::from_secs(86_400));
+        assert!(parse_duration("24").is_err());
+        assert!(parse_duration("213503982334602d").is_err());
+        assert!(Cli::try_parse_from(["halodeck", "compact", "--older-than", "3w"]).is_err());
+    }
+
+    #[test]
+    fn test_run_compact() {
+        let log_path = std::env::temp_dir().join(format!("halodeck_compact_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&log_path);
+        let mut vault = Vaultline::new(&log_path).unwrap();
+        for i in 0..2 {
+            let mut old = Event::now("epoch", "info", format!("old {i}"));
+            old.ts_ms -= 10 * 86_400_000;
+            vault.append(old).unwrap();
+        }
+        let recent = Event::now("epoch", "info", "recent");
+        vault.append(recent.clone()).unwrap();
+
+        let mut out = Vec::new();
+        let cli = Cli::parse_from(["halodeck", "compact", "--older-than", "7d"]);
+        cli.run_to(&mut Scheduler::new(), &mut vault, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "removed 2 events (1 kept)\n");
+
+        let mut reloaded = Vaultline::new(&log_path).unwrap();
+        reloaded.load_from_disk().unwrap();
+        assert_eq!(reloaded.all()[0], recent);
+        assert!(reloaded.all().iter().all(|ev| !ev.message.starts_with("old")));
+        let _ = std::fs::remove_file(&log_path);
+    }
+
+    #[test]
+    fn test_cli_submit_json() {
+        let cli = Cli::parse_from(["halodeck", "submit", "--json", "email", "hi"]);
+        match cli.command {
+            Command::Submit { json, .. } => assert!(json),
+            _ => panic!("Expected Submit command"),
+        }
+
+        let mut sched = Scheduler::new();
+        sched.enqueue("email", "earlier job");
+        let mut out = Vec::new();
+        cli.run_to(&mut sched, &mut Vaultline::new_in_memory(), &mut out).unwrap();
+
+        let printed: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(printed, serde_json::json!({ "id": 2, "kind": "email", "queued": true }));
+        assert_eq!(sched.depth(), 2);
+    }
+
+    #[test]
+    fn test_submit_priority_jumps_the_queue() {
+        let mut sched = Scheduler::new();
+        let mut vault = Vaultline::new_in_memory();
+        sched.enqueue("email", "bulk");
+        Cli::parse_from(["halodeck", "submit", "--priority", "5", "email", "urgent"])
+            .run_to(&mut sched, &mut vault, &mut Vec::new())
+            .unwrap();
+        Cli::parse_from(["halodeck", "submit", "--priority", "-1", "email", "whenever"])
+            .run_to(&mut sched, &mut vault, &mut Vec::new())
+            .unwrap();
+
+        let order: Vec<String> = std::iter::from_fn(|| sched.dequeue("email", Duration::from_secs(5)).map(|j| j.payload)).collect();
+        assert_eq!(order, ["urgent", "bulk", "whenever"]);
+        assert_eq!(vault.all()[0].kv["priority"], 5);
+    }
+
+    #[test]
+    fn test_submit_correlates_job_events() {
+        use std::sync::{Arc, Mutex};
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let mut sched = Scheduler::new();
+        let transitions = Arc::new(Mutex::new(Vaultline::new_in_memory()));
+        sched.set_transition_vault(transitions.clone());
+        let mut vault = Vaultline::new_in_memory();
+        let submitted = || Filter::new().source("halodeck").contains("submitted job");
+        let subscriber = tracing_subscriber::registry().with(telemetry::TraceIdLayer);
+        tracing::subscriber::with_default(subscriber, || {
+            Cli::parse_from(["halodeck", "submit", "email", "hi"])
+                .run_to(&mut sched, &mut vault, &mut Vec::new())
+                .unwrap();
+        });
+
+        let id = vault.all()[0].correlation_id.clone().unwrap();
+        let leased = sched.dequeue("email", Duration::from_secs(5)).unwrap();
+        assert_eq!(leased.correlation_id.as_ref(), Some(&id));
+        let mut recovered = Scheduler::new();
+        let replayed = recovered.replay(&mut vault, submitted()).unwrap();
+        assert_eq!(replayed.len(), 1);
+        let job = recovered.dequeue("email", Duration::from_secs(5)).unwrap();
+        assert_eq!((job.payload.as_str(), job.correlation_id.as_ref()), ("hi", Some(&id)));
+
+        sched.complete(leased.id).unwrap();
+        let transitions = transitions.lock().unwrap();
+        assert_eq!(transitions.query(Filter::new().correlation_id(id)).count(), 3);
+        for event in transitions.all().to_vec() {
+            vault.append(event).unwrap();
+        }
+        assert!(Scheduler::new().replay(&mut vault, submitted()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_run_logs_filtered() {
+        let mut vault = Vaultline::new_in_memory();
+        let mut ev = Event::now("epoch", "error", "job 1 failed");
+        ev.ts_ms = 1;
+        vault.append(ev).unwrap();
+        let mut ev = Event::now("epoch", "info", "job 2 done");
+        ev.ts_ms = 2;
+        vault.append(ev).unwrap();
+        let mut ev = Event::now("auth", "error", "login failed");
+        ev.ts_ms = 3;
+        vault.append(ev).unwrap();
+
+        let cli = Cli::parse_from(["halodeck", "logs", "--level", "error", "--tail", "5"]);
+        let mut out = Vec::new();
+        cli.run_to(&mut Scheduler::new(), &mut vault, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "1 [error] epoch: job 1 failed\n3 [error] auth: login failed\n");
+    }
+
+    #[test]
+    fn test_run_logs_streams_from_disk() {
+        let log_path = std::env::temp_dir().join(format!("halodeck_logs_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&log_path);
+        let mut writer = Vaultline::new(&log_path).unwrap();
+        for i in 1..=3 {
+            let mut ev = Event::now("epoch", "error", format!("job {i} failed"));
+            ev.ts_ms = i;
+            writer.append(ev).unwrap();
+        }
+
+        let mut vault = Vaultline::new(&log_path).unwrap();
+        let mut out = Vec::new();
+        Cli::parse_from(["halodeck", "logs", "--tail", "2"]).run_to(&mut Scheduler::new(), &mut vault, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "2 [error] epoch: job 2 failed\n3 [error] epoch: job 3 failed\n");
+        assert!(vault.all().is_empty());
+
+        let mut out = Vec::new();
+        Cli::parse_from(["halodeck", "logs", "--query", r#"level>=warn AND message~"job 1""#])
+            .run_to(&mut Scheduler::new(), &mut vault, &mut out)
+            .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "1 [error] epoch: job 1 failed\n");
+        let bad = Cli::parse_from(["halodeck", "logs", "--query", "colour=red"]);
+        assert!(bad.run_to(&mut Scheduler::new(), &mut vault, &mut Vec::new()).is_err());
+        let contradicts = Cli::parse_from(["halodeck", "logs", "--source", "api", "--query", "source=epoch"]);
+        assert!(contradicts.run_to(&mut Scheduler::new(), &mut vault, &mut Vec::new()).is_err());
+        let _ = std::fs::remove_file(&log_path);
+    }
+
+    #[test]
+    fn test_cli_export() {
+        let cli = Cli::parse_from(["halodeck", "export", "out.json", "--format", "json", "--level", "error"]);
+        match cli.command {
+            Command::Export { output, format, level, .. } => {
+                assert_eq!(output, PathBuf::from("out.json"));
+                assert_eq!(format, ExportFormat::Json);
+                assert_eq!(level.as_deref(), Some("error"));
+            }
+            _ => panic!("Expected Export command"),
+        }
+    }
+
+    #[test]
+    fn test_run_snapshot_then_restore() {
+        let dir = std::env::temp_dir().join(format!("halodeck_snapshot_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut vault = Vaultline::new(dir.join("event.log")).unwrap();
+        vault.append(Event::now("epoch", "info", "job 1 done")).unwrap();
+        let snap = dir.join("backup.snap").to_string_lossy().into_owned();
+
+        let mut out = Vec::new();
+        Cli::parse_from(["halodeck", "snapshot", &snap]).run_to(&mut Scheduler::new(), &mut vault, &mut out).unwrap();
+        assert!(String::from_utf8(out).unwrap().starts_with("snapshot of 1 events"));
+        let mut fresh = Vaultline::new(dir.join("restored.log")).unwrap();
+        let mut out = Vec::new();
+        Cli::parse_from(["halodeck", "restore", &snap]).run_to(&mut Scheduler::new(), &mut fresh, &mut out).unwrap();
+        assert!(String::from_utf8(out).unwrap().starts_with("restored 1 events"));
+        assert_eq!(fresh.all(), vault.all());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_run_stats() {
+        let mut vault = Vaultline::new_in_memory();
+        vault.append(Event::now("auth", "error", "login failed")).unwrap();
+        vault.append(Event::now("epoch", "info", "job done")).unwrap();
+        let mut out = Vec::new();
+        Cli::parse_from(["halodeck", "stats"]).run_to(&mut Scheduler::new(), &mut vault, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("events: 2 (2 in memory"));
+        assert!(text.contains("level error: 1\n") && text.contains("source epoch: 1\n"));
+
+        let mut out = Vec::new();
+        Cli::parse_from(["halodeck", "stats", "--json"]).run_to(&mut Scheduler::new(), &mut vault, &mut out).unwrap();
+        let stats: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(stats["by_level"]["info"], 1);
+    }
+
+    #[test]
+    fn test_run_vault_verify() {
+        let path = std::env::temp_dir().join(format!("halodeck_verify_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut vault = Vaultline::new(&path).unwrap();
+        vault.set_checksums(true);
+        vault.set_sequencing(true);
+        vault.set_hash_chain(true);
+        for i in 1..=4 {
+            let mut ev = Event::now("epoch", "info", format!("job {i} done"));
+            ev.ts_ms = i;
+            vault.append(ev).unwrap();
+        }
+        let mut out = Vec::new();
+        Cli::parse_from(["halodeck", "vault", "verify"]).run_to(&mut Scheduler::new(), &mut vault, &mut out).unwrap();
+        assert!(String::from_utf8(out).unwrap().starts_with("records: 4 in 1 segments\nmalformed: 0\nchecksum failures: 0 (4 checksummed)"));
+
+        // Drop record 2, garble record 3's checksum and add a junk line.
+        let text = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        std::fs::write(&path, format!("{}\n{}\n{}\nnot json\n", lines[0], lines[2].replace("job 3", "job 9"), lines[3])).unwrap();
+        let report = vault.verify().unwrap();
+        assert_eq!((report.records, report.malformed, report.checksum_failures, report.seq_gaps, report.breaks.len()), (2, 1, 1, 1, 1));
+        assert!(report.issues[0].ends_with(":2: checksum mismatch"), "{:?}", report.issues);
+        let mut out = Vec::new();
+        assert!(Cli::parse_from(["halodeck", "vault", "verify", "--json"]).run_to(&mut Scheduler::new(), &mut vault, &mut out).is_err());
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json["seq_gaps"], 1);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_run_convert_round_trip() {
+        let dir = std::env::temp_dir().join(format!("halodeck_convert_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let (json, bin, back) = (dir.join("event.log"), dir.join("event.bin"), dir.join("back.log"));
+        let mut vault = Vaultline::new(&json).unwrap();
+        let mut ev = Event::now("epoch", "info", "job 1 done");
+        ev.kv = serde_json::json!({ "attempts": 2 });
+        vault.append(ev.clone()).unwrap();
+
+        let arg = |p: &PathBuf| p.to_string_lossy().into_owned();
+        let mut out = Vec::new();
+        Cli::parse_from(["halodeck", "convert", &arg(&json), &arg(&bin), "--to", "binary"])
+            .run_to(&mut Scheduler::new(), &mut Vaultline::new_in_memory(), &mut out).unwrap();
+        assert!(String::from_utf8(out).unwrap().starts_with("converted 1 events"));
+        let mut out = Vec::new();
+        Cli::parse_from(["halodeck", "convert", &arg(&bin), &arg(&back), "--from", "binary", "--to", "ndjson"])
+            .run_to(&mut Scheduler::new(), &mut Vaultline::new_in_memory(), &mut out).unwrap();
+
+        let mut binary = Vaultline::new_with_format(&bin, Format::Binary).unwrap();
+        binary.load_from_disk().unwrap();
+        assert_eq!(binary.all(), &[ev.clone()]);
+        let mut reloaded = Vaultline::new(&back).unwrap();
+        reloaded.load_from_disk().unwrap();
+        assert_eq!(reloaded.all(), &[ev]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_dead_letters_list_and_requeue() {
+        use crate::epoch::RetryPolicy;
+        let mut sched = Scheduler::new();
+        sched.set_retry_policy("email", RetryPolicy { max_attempts: Some(3), ..Default::default() });
+        let id = sched.enqueue("email", "bounced");
+        sched.dequeue("email", Duration::from_secs(5)).unwrap();
+        sched.fail_permanently(id).unwrap();
+        let mut vault = Vaultline::new_in_memory();
+
+        let mut out = Vec::new();
+        Cli::parse_from(["halodeck", "dead", "list", "--kind", "email", "--json"]).run_to(&mut sched, &mut vault, &mut out).unwrap();
+        let listed: JobRecord = serde_json::from_slice(&out).unwrap();
+        assert_eq!((listed.id, listed.attempts), (id, 1));
+
+        Cli::parse_from(["halodeck", "dead", "requeue", &id.to_string()]).run_to(&mut sched, &mut vault, &mut Vec::new()).unwrap();
+        assert!(sched.dead_letters().is_empty());
+        assert_eq!(sched.dequeue("email", Duration::from_secs(5)).unwrap().attempts, 1);
+        assert!(Cli::parse_from(["halodeck", "dead", "requeue", &id.to_string()]).run_to(&mut sched, &mut vault, &mut Vec::new()).is_err());
+    }
 }
@@ -5,12 +5,14 @@ pub mod axiom;
 pub mod vaultline;
 pub mod epoch;
 pub mod halodeck;
+pub mod sentinel;
 
 // Re-export key items for easier access
 pub use module::{Health, Module, Result, Error};
 pub use config::{Config, load_config};
 pub use telemetry::init_telemetry;
-pub use vaultline::{Vaultline, Event};
+pub use vaultline::{Vaultline, Event, Level};
 pub use axiom::{Runtime};
 pub use epoch::{Scheduler, Job};
+pub use sentinel::{Rule, Sentinel, SentinelService};
 pub use halodeck::{Cli as HaloCli, Command as HaloCommand};
\ No newline at end of file
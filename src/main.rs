@@ -1,11 +1,15 @@
 use std::path::Path;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use clap::Parser;
 
 use hyperion::{
     init_telemetry, load_config, Result, Runtime, Module, Health,
-    Vaultline, Event, Scheduler, HaloCli,
+    Vaultline, Event, Scheduler, HaloCli, Sentinel, SentinelService,
 };
+use hyperion::config::VaultConfig;
+use hyperion::epoch::{Recurring, RecurringService};
+use hyperion::vaultline::{PartitionedVaultline, VaultlineService};
 
 // Simple demo module
 struct Hello { running: bool }
@@ -29,17 +33,56 @@ fn main() -> Result<()> {
     // Vaultline (file-backed)
     let log_path = Path::new(&cfg.data_dir).join("event.log");
     let mut vault = Vaultline::new(&log_path)?;
-    let _ = vault.load_from_disk()?;
-
-    // Scheduler (in-memory)
-    let mut sched = Scheduler::new();
+    #[cfg(feature = "encryption")]
+    if let Some(key) = hyperion::vaultline::load_key(&cfg.vault)? {
+        vault.set_encryption_key(&key);
+    }
+    #[cfg(feature = "signing")]
+    if let Some(seed) = hyperion::vaultline::load_signing_key(&cfg.vault)? {
+        vault.set_signing_key(&seed);
+    }
+    vault.apply_config(&cfg.vault)?;
+    if cfg.vault.partition_by_source {
+        // The main vault already ships to the webhook / collector; partitions only store.
+        let mut partitions = PartitionedVaultline::open(Path::new(&cfg.data_dir).join("partitions"))?;
+        partitions.apply_config(&VaultConfig { webhook_url: None, otlp_endpoint: None, ..cfg.vault.clone() })?;
+        vault.add_sink(partitions);
+    }
+    #[cfg(feature = "kafka")]
+    if let (Some(brokers), Some(topic)) = (&cfg.vault.kafka_brokers, &cfg.vault.kafka_topic) {
+        vault.add_sink(hyperion::vaultline::KafkaSink::new(brokers, topic.clone())?);
+    }
+    vault.enforce_retention()?;
 
-    // CLI path
+    // CLI path (commands work against the file; nothing is loaded into memory). Queue commands
+    // need the journal, which a running daemon holds locked.
     if let Ok(cli) = HaloCli::try_parse() {
+        let mut sched = if cli.uses_scheduler() {
+            Scheduler::open(Path::new(&cfg.data_dir).join("epoch"))
+                .map_err(|e| format!("{e} (stop the daemon to manage jobs from the command line)"))?
+        } else {
+            Scheduler::new()
+        };
         cli.run(&mut sched, &mut vault)?;
+        vault.close()?;
         return Ok(());
     }
 
+    // Scheduler (journaled, so queued and leased jobs survive a restart)
+    let mut sched = Scheduler::open(Path::new(&cfg.data_dir).join("epoch"))?;
+
+    let recovery = vault.load_with_recovery()?;
+    if recovery.quarantined > 0 {
+        tracing::warn!(quarantined = recovery.quarantined, torn_tail = recovery.torn_tail, "vaultline recovered from corrupt records");
+    }
+
+    // Record the effective config for the daemon run
+    hyperion::telemetry::log_config(&mut vault, &cfg)?;
+
+    // Alerting rules watch everything appended from here on
+    let sentinel = Sentinel::new(cfg.rules.clone());
+    let alerts = (!sentinel.is_empty()).then(|| vault.subscribe());
+
     // Basic vaultline demo
     vault.append(Event::now("axiom", "info", "runtime starting"))?;
     vault.append(Event::now("hello", "info", "hello module warming up"))?;
@@ -48,6 +91,10 @@ fn main() -> Result<()> {
         tracing::info!(last_level = %last.level, last_source = %last.source, last_msg = %last.message, "vaultline tail(1)");
     }
 
+    // Recurring jobs (definitions and due times persist under data_dir/epoch; the runtime ticks them)
+    let mut recurring = Recurring::open(Path::new(&cfg.data_dir).join("epoch").join("recurring.json"))?;
+    recurring.reconcile(cfg.schedules.clone())?;
+
     // Epoch demo
     let _j1 = sched.enqueue("email", "Welcome to Hyperion!");
     let _j2 = sched.enqueue("email", "Send follow-up");
@@ -59,10 +106,19 @@ fn main() -> Result<()> {
     }
     tracing::info!(depth = sched.depth(), leased = sched.leased_count(), "scheduler status");
 
-    // Runtime demo
+    // Runtime demo (modules stop in reverse, so the sentinel's last drain lands before the vaultline closes)
+    let vault = Arc::new(Mutex::new(vault));
+    let sched = Arc::new(Mutex::new(sched));
     let mut rt = Runtime::new();
     rt.register(Hello { running: false });
-    rt.run_until_ctrlc()?;
+    rt.register(VaultlineService::new(vault.clone(), Duration::from_secs(1)));
+    rt.register(RecurringService::new(recurring, sched.clone(), Duration::from_secs(1)));
+    if let Some(rx) = alerts {
+        rt.register(SentinelService::new(sentinel, rx, sched.clone(), vault.clone(), Duration::from_secs(1)));
+    }
+    rt.run_until_ctrlc_draining(&sched, Duration::from_secs(cfg.shutdown_drain_secs))?;
+    let sched = sched.lock().map_err(|_| "scheduler mutex poisoned")?;
+    tracing::info!(report = %sched.shutdown_report(), "scheduler shutdown report");
 
     Ok(())
 }
@@ -1,3 +1,8 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Health {
     Healthy,
@@ -17,9 +22,94 @@ pub trait Module {
     fn health(&self) -> Health { Health::Healthy }
 }
 
+/// Background thread that runs a task every `interval` for a `Module`: `start` spawns it,
+/// `stop` joins it, and `health` reports the last failed run. Shutdown is checked at least
+/// every 50ms, so a long interval doesn't hold up `stop`.
+pub struct Periodic {
+    name: String,
+    interval: Duration,
+    immediate: bool,
+    shutdown: Arc<AtomicBool>,
+    last_error: Arc<Mutex<Option<String>>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Periodic {
+    pub fn new(name: impl Into<String>, interval: Duration) -> Self {
+        Self {
+            name: name.into(),
+            interval,
+            immediate: false,
+            shutdown: Arc::new(AtomicBool::new(false)),
+            last_error: Arc::new(Mutex::new(None)),
+            handle: None,
+        }
+    }
+
+    /// Run the task as soon as the thread starts instead of one interval later.
+    pub fn immediate(mut self) -> Self {
+        self.immediate = true;
+        self
+    }
+
+    /// Spawn the thread. Each run's error replaces the last one (a success clears it).
+    pub fn start<F: FnMut() -> Result<()> + Send + 'static>(&mut self, mut task: F) -> Result<()> {
+        if self.handle.is_some() {
+            return Err(format!("{} already running", self.name).into());
+        }
+        self.shutdown.store(false, Ordering::SeqCst);
+        let (name, shutdown, last_error) = (self.name.clone(), self.shutdown.clone(), self.last_error.clone());
+        let (interval, immediate) = (self.interval, self.immediate);
+        self.handle = Some(thread::spawn(move || {
+            let tick = interval.min(Duration::from_millis(50));
+            let mut last = (!immediate).then(Instant::now);
+            while !shutdown.load(Ordering::SeqCst) {
+                if last.is_none_or(|t| t.elapsed() >= interval) {
+                    let result = task();
+                    if let Err(ref e) = result {
+                        tracing::warn!(task = %name, error = %e, "periodic task failed");
+                    }
+                    if let Ok(mut slot) = last_error.lock() {
+                        *slot = result.err().map(|e| e.to_string());
+                    }
+                    last = Some(Instant::now());
+                }
+                thread::sleep(tick);
+            }
+        }));
+        Ok(())
+    }
+
+    /// Signal the thread and wait for it; a run in progress finishes first.
+    pub fn stop(&mut self) -> Result<()> {
+        let Some(handle) = self.handle.take() else {
+            return Err(format!("{} not running", self.name).into());
+        };
+        self.shutdown.store(true, Ordering::SeqCst);
+        handle.join().map_err(|_| format!("{} thread panicked", self.name))?;
+        Ok(())
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.handle.is_some()
+    }
+
+    /// Unhealthy with `"{failure}: {error}"` after a failed run, Degraded while stopped.
+    pub fn health(&self, failure: &str) -> Health {
+        if let Some(e) = self.last_error.lock().ok().and_then(|slot| slot.clone()) {
+            return Health::Unhealthy { reason: format!("{failure}: {e}") };
+        }
+        if self.handle.is_none() {
+            return Health::Degraded { reason: "stopped".into() };
+        }
+        Health::Healthy
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::atomic::AtomicUsize;
 
     struct TestModule {
         name: String,
@@ -59,4 +149,46 @@ mod tests {
         m.stop().unwrap();
         assert_eq!(m.health(), Health::Unhealthy { reason: "Not running".into() });
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn periodic_lifecycle() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let mut task = Periodic::new("counter", Duration::from_secs(60)).immediate();
+        assert!(task.stop().is_err());
+        assert_eq!(task.health("tick failed"), Health::Degraded { reason: "stopped".into() });
+
+        let counter = runs.clone();
+        task.start(move || {
+            // The first run fails, later ones succeed.
+            match counter.fetch_add(1, Ordering::SeqCst) {
+                0 => Err("boom".into()),
+                _ => Ok(()),
+            }
+        }).unwrap();
+        assert!(task.is_running());
+        assert!(task.start(|| Ok(())).is_err());
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while task.health("tick failed") == Health::Healthy && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(task.health("tick failed"), Health::Unhealthy { reason: "tick failed: boom".into() });
+
+        // A 60s interval still stops promptly, and a restart runs (and recovers) right away.
+        let started = Instant::now();
+        task.stop().unwrap();
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert!(!task.is_running());
+        let counter = runs.clone();
+        task.start(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while task.health("tick failed") != Health::Healthy && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(task.health("tick failed"), Health::Healthy);
+        task.stop().unwrap();
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,376 @@
+use crate::epoch::Scheduler;
+use crate::module::{Health, Module, Periodic, Result};
+use crate::vaultline::{Event, Level, Vaultline};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Source of the synthetic events written by `Action::Alert`. Rules only see
+/// them when they name this source explicitly, so alerts can't feed themselves.
+pub const ALERT_SOURCE: &str = "sentinel";
+
+/// One `[[rules]]` entry: fire `actions` when more than `threshold` matching
+/// events arrive within `window_secs` (by event timestamp).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rule {
+    pub name: String,
+    /// Only events from this source.
+    #[serde(default)]
+    pub source: Option<String>,
+    /// Only events at or above this level.
+    #[serde(default)]
+    pub min_level: Option<Level>,
+    /// Only events whose message contains this text.
+    #[serde(default)]
+    pub contains: Option<String>,
+    /// Fire once the window holds more than this many matches (0 fires on every match).
+    #[serde(default)]
+    pub threshold: usize,
+    #[serde(default = "default_window_secs")]
+    pub window_secs: u64,
+    /// Stay quiet for this long after firing; defaults to the window.
+    #[serde(default)]
+    pub cooldown_secs: Option<u64>,
+    pub actions: Vec<Action>,
+}
+
+fn default_window_secs() -> u64 { 60 }
+
+/// What a rule does when it fires, e.g. `{ type = "enqueue", kind = "pager" }`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Action {
+    /// Enqueue a job; the payload defaults to the alert as JSON.
+    Enqueue { kind: String, #[serde(default)] payload: Option<String> },
+    /// Append a synthetic event from `ALERT_SOURCE` (level defaults to warn).
+    Alert { #[serde(default)] level: Option<Level> },
+    /// POST the alert as JSON (`webhook` feature).
+    Webhook { url: String },
+}
+
+impl Rule {
+    fn matches(&self, ev: &Event) -> bool {
+        let own = ev.source == ALERT_SOURCE;
+        self.source.as_ref().map_or(!own, |s| &ev.source == s)
+            && self.min_level.as_ref().is_none_or(|min| ev.level.partial_cmp(min).is_some_and(|o| o.is_ge()))
+            && self.contains.as_ref().is_none_or(|c| ev.message.contains(c.as_str()))
+    }
+}
+
+/// A rule that fired, with the event that tipped it over.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Alert {
+    pub rule: String,
+    /// Matches inside the window when it fired.
+    pub count: usize,
+    pub window_secs: u64,
+    pub trigger: Event,
+    #[serde(skip)]
+    pub actions: Vec<Action>,
+}
+
+struct RuleState {
+    rule: Rule,
+    hits: VecDeque<u128>,
+    quiet_until: u128,
+}
+
+/// Watches events and turns rule matches into alerts.
+pub struct Sentinel {
+    rules: Vec<RuleState>,
+}
+
+impl Sentinel {
+    pub fn new(rules: Vec<Rule>) -> Self {
+        Self { rules: rules.into_iter().map(|rule| RuleState { rule, hits: VecDeque::new(), quiet_until: 0 }).collect() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Count `event` against every rule, returning the rules it made fire.
+    pub fn observe(&mut self, event: &Event) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+        for state in &mut self.rules {
+            if !state.rule.matches(event) {
+                continue;
+            }
+            let window_ms = u128::from(state.rule.window_secs) * 1000;
+            state.hits.push_back(event.ts_ms);
+            while state.hits.front().is_some_and(|&t| t.saturating_add(window_ms) <= event.ts_ms) {
+                state.hits.pop_front();
+            }
+            if state.hits.len() > state.rule.threshold && event.ts_ms >= state.quiet_until {
+                let cooldown = state.rule.cooldown_secs.unwrap_or(state.rule.window_secs);
+                state.quiet_until = event.ts_ms + u128::from(cooldown) * 1000;
+                alerts.push(Alert {
+                    rule: state.rule.name.clone(),
+                    count: state.hits.len(),
+                    window_secs: state.rule.window_secs,
+                    trigger: event.clone(),
+                    actions: state.rule.actions.clone(),
+                });
+                state.hits.clear();
+            }
+        }
+        alerts
+    }
+
+    /// Observe everything waiting on `rx` (see `Vaultline::subscribe`) and return
+    /// the alerts that fired, without acting on them.
+    pub fn pending(&mut self, rx: &Receiver<Event>) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            alerts.extend(self.observe(&event));
+        }
+        alerts
+    }
+
+    /// Observe everything waiting on `rx` and carry out the resulting alerts.
+    /// Returns how many fired; every alert is acted on even after one fails, and
+    /// the first failure is returned at the end.
+    pub fn drain(&mut self, rx: &Receiver<Event>, sched: &mut Scheduler, vault: &mut Vaultline) -> Result<usize> {
+        let alerts = self.pending(rx);
+        let mut first_err = None;
+        for alert in &alerts {
+            if let Err(e) = fire(alert, sched, vault) {
+                first_err.get_or_insert(e);
+            }
+        }
+        first_err.map_or(Ok(alerts.len()), Err)
+    }
+}
+
+/// Runs a `Sentinel` as a `Module`: `start` spawns a thread that drains the events received on
+/// `rx` (a `Vaultline::subscribe` of `vault`) into the rules every `interval`, `stop` drains
+/// once more and joins it, and `health` reports a failed alert action.
+pub struct SentinelService {
+    watch: Arc<Mutex<(Sentinel, Receiver<Event>)>>,
+    sched: Arc<Mutex<Scheduler>>,
+    vault: Arc<Mutex<Vaultline>>,
+    task: Periodic,
+}
+
+impl SentinelService {
+    pub fn new(sentinel: Sentinel, rx: Receiver<Event>, sched: Arc<Mutex<Scheduler>>, vault: Arc<Mutex<Vaultline>>, interval: Duration) -> Self {
+        Self { watch: Arc::new(Mutex::new((sentinel, rx))), sched, vault, task: Periodic::new("sentinel service", interval) }
+    }
+
+    /// Collects alerts under the watch lock, applies them under the scheduler and then the
+    /// vaultline lock, and posts webhooks once every lock is released, so a slow endpoint
+    /// never holds up appends or workers.
+    fn tick(watch: &Mutex<(Sentinel, Receiver<Event>)>, sched: &Mutex<Scheduler>, vault: &Mutex<Vaultline>) -> Result<()> {
+        let alerts = {
+            let mut watch = watch.lock().map_err(|_| "sentinel mutex poisoned")?;
+            let (sentinel, rx) = &mut *watch;
+            sentinel.pending(rx)
+        };
+        if alerts.is_empty() { return Ok(()) }
+        let mut first_err = None;
+        {
+            let mut sched = sched.lock().map_err(|_| "scheduler mutex poisoned")?;
+            let mut vault = vault.lock().map_err(|_| "vaultline mutex poisoned")?;
+            for alert in &alerts {
+                if let Err(e) = apply(alert, &mut sched, &mut vault) {
+                    first_err.get_or_insert(e);
+                }
+            }
+        }
+        for alert in &alerts {
+            notify(alert);
+        }
+        first_err.map_or(Ok(()), Err)
+    }
+}
+
+impl Module for SentinelService {
+    fn name(&self) -> &str { "sentinel" }
+
+    fn start(&mut self) -> Result<()> {
+        let (watch, sched, vault) = (self.watch.clone(), self.sched.clone(), self.vault.clone());
+        self.task.start(move || Self::tick(&watch, &sched, &vault))
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.task.stop()?;
+        Self::tick(&self.watch, &self.sched, &self.vault)
+    }
+
+    fn health(&self) -> Health {
+        self.task.health("alert action failed")
+    }
+}
+
+/// Run an alert's actions. Every action is attempted and the first failure is
+/// returned afterwards. A failing webhook is logged rather than returned so one
+/// unreachable endpoint doesn't mark the sentinel unhealthy.
+pub fn fire(alert: &Alert, sched: &mut Scheduler, vault: &mut Vaultline) -> Result<()> {
+    let applied = apply(alert, sched, vault);
+    notify(alert);
+    applied
+}
+
+/// The actions that go through the scheduler or the vaultline (everything but webhooks).
+fn apply(alert: &Alert, sched: &mut Scheduler, vault: &mut Vaultline) -> Result<()> {
+    tracing::warn!(rule = %alert.rule, count = alert.count, "alert rule fired");
+    let mut first_err = None;
+    for action in &alert.actions {
+        let result = match action {
+            Action::Enqueue { kind, payload } => {
+                let payload = match payload {
+                    Some(p) => Ok(p.clone()),
+                    None => serde_json::to_string(alert),
+                };
+                payload.map(|payload| { sched.enqueue(kind.clone(), payload); }).map_err(Into::into)
+            }
+            Action::Alert { level } => {
+                let level = level.clone().unwrap_or(Level::Warn);
+                let message = format!("rule {} fired: {} events in {}s", alert.rule, alert.count, alert.window_secs);
+                vault.append(Event::now(ALERT_SOURCE, level, message)
+                    .with("rule", alert.rule.clone())
+                    .with("count", alert.count)
+                    .with("trigger_source", alert.trigger.source.clone()))
+            }
+            Action::Webhook { .. } => Ok(()),
+        };
+        if let Err(e) = result {
+            tracing::warn!(rule = %alert.rule, error = %e, "alert action failed");
+            first_err.get_or_insert(e);
+        }
+    }
+    first_err.map_or(Ok(()), Err)
+}
+
+/// Post the alert to each of its webhook actions.
+fn notify(alert: &Alert) {
+    for action in &alert.actions {
+        if let Action::Webhook { url } = action {
+            post(url, alert);
+        }
+    }
+}
+
+#[cfg(feature = "webhook")]
+fn post(url: &str, alert: &Alert) {
+    let sent = serde_json::to_vec(alert).map_err(|e| e.to_string()).and_then(|body| {
+        ureq::post(url)
+            .timeout(std::time::Duration::from_secs(10))
+            .set("content-type", "application/json")
+            .send_bytes(&body)
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    });
+    if let Err(e) = sent {
+        tracing::warn!(rule = %alert.rule, url, error = %e, "alert webhook failed");
+    }
+}
+
+#[cfg(not(feature = "webhook"))]
+fn post(url: &str, alert: &Alert) {
+    tracing::warn!(rule = %alert.rule, url, "webhook alert actions need the `webhook` feature");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Instant;
+
+    #[test]
+    fn fires_on_burst_then_cools_down() {
+        let rules: Vec<Rule> = toml::from_str::<toml::Table>(r#"
+            [[rules]]
+            name = "epoch-errors"
+            source = "epoch"
+            min_level = "error"
+            threshold = 5
+            window_secs = 60
+            actions = [{ type = "enqueue", kind = "pager" }, { type = "alert" }]
+        "#).unwrap()["rules"].clone().try_into().unwrap();
+        let mut sentinel = Sentinel::new(rules);
+        let at = |level: &str, secs: u128| {
+            let mut ev = Event::now("epoch", level, "lease lost");
+            ev.ts_ms = secs * 1000;
+            ev
+        };
+        // Spread over more than a minute: never more than 5 in any window.
+        for i in 0..10 {
+            assert!(sentinel.observe(&at("error", 100 + i * 13)).is_empty());
+        }
+        assert!(sentinel.observe(&at("warn", 300)).is_empty());
+        let fired: Vec<Alert> = (0..6).flat_map(|i| sentinel.observe(&at("error", 400 + i))).collect();
+        assert_eq!(fired.len(), 1);
+        assert_eq!((fired[0].rule.as_str(), fired[0].count), ("epoch-errors", 6));
+        assert!((0..6).all(|i| sentinel.observe(&at("error", 410 + i)).is_empty()));
+
+        let (mut sched, mut vault) = (Scheduler::new(), Vaultline::new_in_memory());
+        let rx = vault.subscribe();
+        for i in 0..6 {
+            vault.append(at("error", 500 + i)).unwrap();
+        }
+        assert_eq!(sentinel.drain(&rx, &mut sched, &mut vault).unwrap(), 1);
+        assert_eq!(sched.depth(), 1);
+        let alert = vault.all().last().unwrap();
+        assert_eq!((alert.source.as_str(), alert.kv_str("rule")), (ALERT_SOURCE, Some("epoch-errors")));
+        // The alert event itself is ignored by the rule.
+        assert_eq!(sentinel.drain(&rx, &mut sched, &mut vault).unwrap(), 0);
+    }
+
+    #[test]
+    fn drain_keeps_going_after_a_failed_action() {
+        let rule = Rule {
+            name: "any-error".into(),
+            source: None,
+            min_level: Some(Level::Error),
+            contains: None,
+            threshold: 0,
+            window_secs: 60,
+            cooldown_secs: Some(0),
+            actions: vec![Action::Alert { level: None }, Action::Enqueue { kind: "pager".into(), payload: None }],
+        };
+        let mut sentinel = Sentinel::new(vec![rule]);
+        let (mut sched, mut vault) = (Scheduler::new(), Vaultline::new_in_memory());
+        let rx = vault.subscribe();
+        vault.append(Event::now("epoch", "error", "lease lost")).unwrap();
+        vault.append(Event::now("epoch", "error", "lease lost again")).unwrap();
+        // The alert events can't be written, but both pages still go out.
+        vault.set_hard_limit(2);
+        assert!(sentinel.drain(&rx, &mut sched, &mut vault).is_err());
+        assert_eq!(sched.depth_of("pager"), 2);
+    }
+
+    #[test]
+    fn service_drains_in_background() {
+        let rule = Rule {
+            name: "any-error".into(),
+            source: None,
+            min_level: Some(Level::Error),
+            contains: None,
+            threshold: 0,
+            window_secs: 60,
+            cooldown_secs: Some(0),
+            actions: vec![Action::Enqueue { kind: "pager".into(), payload: None }],
+        };
+        let mut vault = Vaultline::new_in_memory();
+        let rx = vault.subscribe();
+        let (sched, vault) = (Arc::new(Mutex::new(Scheduler::new())), Arc::new(Mutex::new(vault)));
+        let mut service = SentinelService::new(Sentinel::new(vec![rule]), rx, sched.clone(), vault.clone(), Duration::from_millis(20));
+        service.start().unwrap();
+
+        vault.lock().unwrap().append(Event::now("epoch", "error", "lease lost")).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while sched.lock().unwrap().depth() == 0 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(sched.lock().unwrap().depth_of("pager"), 1);
+        assert_eq!(service.health(), Health::Healthy);
+
+        // Whatever arrives right before stopping is still checked.
+        vault.lock().unwrap().append(Event::now("epoch", "error", "lease lost again")).unwrap();
+        service.stop().unwrap();
+        assert_eq!(sched.lock().unwrap().depth_of("pager"), 2);
+    }
+}
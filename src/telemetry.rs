@@ -1,6 +1,15 @@
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::{LookupSpan, Registry};
+use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::{fmt, filter::EnvFilter};
 use crate::config::Config;
 use crate::module::Result;
+use crate::vaultline::{Event, Vaultline};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Initialize global logging based on env or config.
 /// Order: HYPERION_LOG env -> cfg.log_level -> "info"
@@ -14,19 +23,220 @@ pub fn init_telemetry(cfg: &Config) -> Result<()> {
     let _ = fmt()
         .with_env_filter(filter)
         .with_target(true)
+        .finish()
+        .with(TraceIdLayer)
         .try_init();
 
-    tracing::info!("telemetry initialized");
+    let resource: Vec<String> = resource_attributes().iter().map(|(k, v)| format!("{k}={v}")).collect();
+    tracing::info!(resource = %resource.join(" "), "telemetry initialized");
     Ok(())
 }
 
+/// Attributes identifying this process (`service.name`, `service.version`, `host.name`,
+/// `process.pid`), shared by every exporter so their records line up.
+pub fn resource_attributes() -> Vec<(&'static str, String)> {
+    let mut attrs = vec![
+        ("service.name", env!("CARGO_PKG_NAME").to_string()),
+        ("service.version", env!("CARGO_PKG_VERSION").to_string()),
+        ("process.pid", std::process::id().to_string()),
+    ];
+    if let Ok(host) = std::env::var("HOSTNAME") {
+        attrs.push(("host.name", host));
+    }
+    attrs
+}
+
+/// Append the effective config to the vault (serialized into `kv`) for incident analysis.
+pub fn log_config(vault: &mut Vaultline, cfg: &Config) -> Result<()> {
+    let mut ev = Event::now("telemetry", "info", "effective config");
+    ev.kv = serde_json::to_value(cfg)?;
+    // Never write the vault key into the vault itself.
+    if let Some(key) = ev.kv.pointer_mut("/vault/encryption_key").filter(|k| !k.is_null()) {
+        *key = "<redacted>".into();
+    }
+    vault.append(ev)
+}
+
+/// `trace_id` / `correlation_id` fields recorded on a span.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TraceIds {
+    pub trace_id: Option<String>,
+    pub correlation_id: Option<String>,
+}
+
+impl TraceIds {
+    fn set(&mut self, field: &Field, value: String) {
+        match field.name() {
+            "trace_id" => self.trace_id = Some(value),
+            "correlation_id" => self.correlation_id = Some(value),
+            _ => {}
+        }
+    }
+}
+
+impl Visit for TraceIds {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.set(field, value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.set(field, format!("{value:?}"));
+    }
+}
+
+/// Remembers the `trace_id` / `correlation_id` fields of spans so [`current_trace_ids`]
+/// can find them. `init_telemetry` installs it; add it yourself to custom subscribers.
+pub struct TraceIdLayer;
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for TraceIdLayer {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut ids = TraceIds::default();
+        attrs.record(&mut ids);
+        if ids != TraceIds::default() && let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(ids);
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        let mut extensions = span.extensions_mut();
+        match extensions.get_mut::<TraceIds>() {
+            Some(ids) => values.record(ids),
+            None => {
+                let mut ids = TraceIds::default();
+                values.record(&mut ids);
+                if ids != TraceIds::default() {
+                    extensions.insert(ids);
+                }
+            }
+        }
+    }
+}
+
+/// Ids of the current span, each taken from the innermost span that carries it.
+/// Empty when no span is entered or [`TraceIdLayer`] is not installed.
+pub fn current_trace_ids() -> TraceIds {
+    tracing::Span::current()
+        .with_subscriber(|(id, dispatch)| {
+            let registry = dispatch.downcast_ref::<Registry>()?;
+            let mut ids = TraceIds::default();
+            for span in registry.span(id)?.scope() {
+                if let Some(found) = span.extensions().get::<TraceIds>() {
+                    ids.trace_id = ids.trace_id.or_else(|| found.trace_id.clone());
+                    ids.correlation_id = ids.correlation_id.or_else(|| found.correlation_id.clone());
+                }
+            }
+            Some(ids)
+        })
+        .flatten()
+        .unwrap_or_default()
+}
+
+/// A fresh id for a trace or correlation, unique within the process and unlikely to
+/// collide across processes.
+pub fn new_trace_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64);
+    format!("{nanos:016x}{:08x}{:08x}", std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed) as u32)
+}
+
+/// Enter a span tagging every record emitted inside it with `module = name`.
+/// Keep the guard alive for the duration of the work:
+///
+/// ```
+/// let _span = hyperion::telemetry::module_span("hello");
+/// tracing::info!("warming up"); // carries module="hello"
+/// ```
+pub fn module_span(name: &str) -> tracing::span::EnteredSpan {
+    tracing::info_span!("module", module = %name).entered()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     #[test]
     fn init_smoke() {
-        let cfg = Config { log_level: "debug".into(), data_dir: "data".into() };
+        let cfg = Config { log_level: "debug".into(), data_dir: "data".into(), ..Config::default() };
         let _ = init_telemetry(&cfg); // should not panic
         tracing::debug!("debug after init");
     }
-}
\ No newline at end of file
+
+    mod recording {
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id};
+        use tracing::{Event, Subscriber};
+        use tracing_subscriber::layer::{Context, Layer};
+        use tracing_subscriber::registry::LookupSpan;
+
+        struct ModuleField(String);
+
+        struct ModuleVisitor(Option<String>);
+        impl Visit for ModuleVisitor {
+            fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+                if field.name() == "module" { self.0 = Some(format!("{value:?}")); }
+            }
+        }
+
+        /// Records, per event, the `module` field of the closest enclosing span.
+        #[derive(Clone, Default)]
+        pub struct Recorder(pub Arc<Mutex<Vec<Option<String>>>>);
+
+        impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for Recorder {
+            fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+                let mut visitor = ModuleVisitor(None);
+                attrs.record(&mut visitor);
+                if let (Some(module), Some(span)) = (visitor.0, ctx.span(id)) {
+                    span.extensions_mut().insert(ModuleField(module));
+                }
+            }
+
+            fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+                let module = ctx.event_scope(event).and_then(|scope| {
+                    scope.into_iter().find_map(|span| span.extensions().get::<ModuleField>().map(|m| m.0.clone()))
+                });
+                self.0.lock().unwrap().push(module);
+            }
+        }
+    }
+
+    #[test]
+    fn module_span_tags_events() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let recorder = recording::Recorder::default();
+        let subscriber = tracing_subscriber::registry().with(recorder.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            let _span = module_span("hello");
+            tracing::info!("inside module");
+        });
+
+        assert_eq!(*recorder.0.lock().unwrap(), vec![Some("hello".to_string())]);
+    }
+
+    #[test]
+    fn current_trace_ids_walk_parent_spans() {
+        let subscriber = tracing_subscriber::registry().with(TraceIdLayer);
+        let ids = tracing::subscriber::with_default(subscriber, || {
+            let _outer = tracing::info_span!("submit", trace_id = "t-1", correlation_id = tracing::field::Empty).entered();
+            tracing::Span::current().record("correlation_id", "c-1");
+            let _inner = tracing::info_span!("enqueue", trace_id = "t-2").entered();
+            current_trace_ids()
+        });
+        assert_eq!(ids, TraceIds { trace_id: Some("t-2".into()), correlation_id: Some("c-1".into()) });
+        assert_eq!(current_trace_ids(), TraceIds::default());
+        assert_ne!(new_trace_id(), new_trace_id());
+    }
+
+    #[test]
+    fn log_config_appends_event() {
+        let cfg = Config { log_level: "warn".into(), data_dir: "/tmp/hyperion".into(), ..Config::default() };
+        let mut vault = Vaultline::new_in_memory();
+        log_config(&mut vault, &cfg).unwrap();
+
+        let ev = &vault.all()[0];
+        assert_eq!(ev.message, "effective config");
+        assert_eq!(ev.kv["log_level"], "warn");
+        assert_eq!(ev.kv["data_dir"], "/tmp/hyperion");
+    }
+}
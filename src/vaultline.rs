@@ -1,24 +1,123 @@
+mod archive;
+#[cfg(feature = "async")]
+mod async_vaultline;
+mod binary;
+mod blob;
+mod buffer;
+mod chain;
+mod export;
+mod index;
+#[cfg(feature = "kafka")]
+mod kafka;
+mod level;
+#[cfg(feature = "otel")]
+mod otlp;
+mod partition;
+mod query;
+mod rate_limit;
+mod redact;
+mod rollup;
+mod schema;
+mod summary;
+mod service;
+mod verify;
+mod writer;
+mod sink;
+#[cfg(feature = "webhook")]
+mod webhook;
+mod snapshot;
+mod store;
+#[cfg(feature = "encryption")]
+mod cipher;
+#[cfg(feature = "signing")]
+mod signing;
+#[cfg(feature = "tar")]
+mod tarball;
+
+use crate::config::VaultConfig;
 use crate::module::{Result};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::fs::{create_dir_all, OpenOptions};
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::LazyLock;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use buffer::EventBuffer;
+use index::EventIndex;
+pub use archive::{Archiver, DirObjectStore, ObjectStore};
+pub use blob::{blob_hash, BlobStore};
+pub use export::ExportFormat;
+pub use level::Level;
+pub use partition::PartitionedVaultline;
+pub use rate_limit::RateLimit;
+pub use redact::Redactor;
+#[cfg(feature = "async")]
+pub use async_vaultline::AsyncVaultline;
+#[cfg(feature = "s3")]
+pub use archive::S3ObjectStore;
+pub use schema::SCHEMA_VERSION;
+pub use summary::HourlyCount;
+pub use service::VaultlineService;
+pub use verify::VerifyReport;
+pub use writer::{Backpressure, BackgroundWriter};
+pub use sink::{EventSink, NdjsonSink};
+#[cfg(feature = "kafka")]
+pub use kafka::KafkaSink;
+#[cfg(feature = "webhook")]
+pub use webhook::WebhookSink;
+#[cfg(feature = "otel")]
+pub use otlp::OtlpSink;
+pub use snapshot::SnapshotMeta;
+pub use store::{EventStore, FileStore, MemoryStore};
+#[cfg(feature = "sqlite")]
+pub use store::SqliteStore;
+#[cfg(feature = "encryption")]
+pub use cipher::{load_key, parse_key};
+#[cfg(feature = "signing")]
+pub use signing::{load_signing_key, public_key, verify_event, SignatureReport};
 
 // Minimum shape for logging and auditing.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Event {
     pub ts_ms: u128,
     pub source: String,
-    pub level: String,
+    pub level: Level,
     pub message: String,
     #[serde(default)]
     pub kv: serde_json::Value,
+    // Position in the log when sequencing is on (from 1); 0 means unnumbered.
+    #[serde(default, skip_serializing_if = "is_unsequenced")]
+    pub seq: u64,
+    // Ties together the records of one logical operation, e.g. a submitted job.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<String>,
+    // Appends carrying a key the vaultline has already stored are skipped, so
+    // producers can retry safely.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotency_key: Option<String>,
+    // Hash of the previous record when the hash chain is on (see `Vaultline::verify`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prev_hash: Option<String>,
+    // Hex ed25519 signature over the rest of the record (`signing` feature).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    // Epoch ms after which the event is hidden from `query`/`tail` and dropped
+    // by compaction.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u128>,
+    // Shape the record was written in; older records are upgraded on load.
+    #[serde(default)]
+    pub schema_version: u32,
 }
 
 impl Event {
     // Create a new event with current timestamp and empty kv.
-    pub fn now<S: Into<String>, L: Into<String>, M: Into<String>>(source: S, level: L, message: M) -> Self {
+    pub fn now<S: Into<String>, L: Into<Level>, M: Into<String>>(source: S, level: L, message: M) -> Self {
         let ts_ms = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
@@ -29,193 +128,4836 @@ impl Event {
             level: level.into(),
             message: message.into(),
             kv: serde_json::Value::Null,
+            seq: 0,
+            correlation_id: None,
+            trace_id: None,
+            idempotency_key: None,
+            prev_hash: None,
+            signature: None,
+            expires_at: None,
+            schema_version: SCHEMA_VERSION,
+        }
+    }
+
+    pub fn with_correlation_id<S: Into<String>>(mut self, id: S) -> Self {
+        self.correlation_id = Some(id.into());
+        self
+    }
+
+    pub fn with_trace_id<S: Into<String>>(mut self, id: S) -> Self {
+        self.trace_id = Some(id.into());
+        self
+    }
+
+    pub fn with_idempotency_key<S: Into<String>>(mut self, key: S) -> Self {
+        self.idempotency_key = Some(key.into());
+        self
+    }
+
+    // Expire the event `ttl` after its timestamp.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.expires_at = Some(self.ts_ms.saturating_add(ttl.as_millis()));
+        self
+    }
+
+    pub fn is_expired(&self, now_ms: u128) -> bool {
+        self.expires_at.is_some_and(|t| t <= now_ms)
+    }
+
+    // Set `kv[key]`, turning a null (or non-object) kv into an object first.
+    pub fn with<K: Into<String>, V: Into<serde_json::Value>>(mut self, key: K, value: V) -> Self {
+        if !self.kv.is_object() {
+            self.kv = serde_json::Value::Object(serde_json::Map::new());
+        }
+        if let Some(map) = self.kv.as_object_mut() {
+            map.insert(key.into(), value.into());
         }
+        self
+    }
+
+    // `kv[key]` if present and of the requested type.
+    pub fn kv_str(&self, key: &str) -> Option<&str> {
+        self.kv.get(key)?.as_str()
+    }
+
+    pub fn kv_u64(&self, key: &str) -> Option<u64> {
+        self.kv.get(key)?.as_u64()
+    }
+
+    pub fn kv_i64(&self, key: &str) -> Option<i64> {
+        self.kv.get(key)?.as_i64()
+    }
+
+    pub fn kv_f64(&self, key: &str) -> Option<f64> {
+        self.kv.get(key)?.as_f64()
+    }
+
+    pub fn kv_bool(&self, key: &str) -> Option<bool> {
+        self.kv.get(key)?.as_bool()
+    }
+
+    // Fill unset ids from the current tracing span (see `telemetry::TraceIdLayer`).
+    pub fn in_current_span(mut self) -> Self {
+        let ids = crate::telemetry::current_trace_ids();
+        self.trace_id = self.trace_id.or(ids.trace_id);
+        self.correlation_id = self.correlation_id.or(ids.correlation_id);
+        self
     }
 }
 
-// Append-only event log with file backing.
-pub struct Vaultline {
-    mem: Vec<Event>,
-    file: Option<PathBuf>,
+fn now_ms() -> u128 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis())
 }
 
-impl Vaultline {
-    // Open or create a vaultline at the given path.
-    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let path = path.as_ref().to_path_buf();
-        if let Some(parent) = path.parent() {
-            create_dir_all(parent)?;
-        }
-        // Ensure the file exists, but do not eagerly load it into memory here.
-        // Use `load_from_disk` when the caller explicitly wants to populate memory.
-        let _file = OpenOptions::new().create(true).append(true).read(true).open(&path)?;
+fn is_unsequenced(seq: &u64) -> bool {
+    *seq == 0
+}
+
+// Predicates for `Vaultline::query`; unset fields match everything.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Filter {
+    pub level: Option<String>,
+    // Severity at or above this level; levels outside the order never match.
+    pub min_level: Option<Level>,
+    pub source: Option<String>,
+    pub contains: Option<String>,
+    pub correlation_id: Option<String>,
+    pub since_ms: Option<u128>,
+    pub until_ms: Option<u128>,
+    // Top-level `kv[key] == value`.
+    pub kv: Option<(String, serde_json::Value)>,
+}
 
-        Ok(Self { mem: Vec::new(), file: Some(path) })
+impl Filter {
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    // Append a new event to the vaultline.
-    pub fn append(&mut self, event: Event) -> Result<()> {
-        // Keep an in-memory copy of the original event (do not mutate the caller's event)
-        self.mem.push(event.clone());
+    // Exact level match (case-insensitive).
+    pub fn level<S: Into<String>>(mut self, level: S) -> Self {
+        self.level = Some(level.into());
+        self
+    }
 
-        // If file-backed, append an NDJSON line using the original event (do not normalize
-        // here so that stored events match what the caller provided).
-        if let Some(ref path) = self.file {
-            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
-            let line = serde_json::to_string(&event)?;
-            use std::io::Write as _;
-            file.write_all(line.as_bytes())?;
-            file.write_all(b"\n")?;
+    pub fn min_level<L: Into<Level>>(mut self, level: L) -> Self {
+        self.min_level = Some(level.into());
+        self
+    }
 
-            // Durability if env set
-            if std::env::var("HYPERION_STRICT_DURABILITY").as_deref() == Ok("1") {
-                file.sync_all()?;
-            }
+    pub fn source<S: Into<String>>(mut self, source: S) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    // Substring of the message.
+    pub fn contains<S: Into<String>>(mut self, needle: S) -> Self {
+        self.contains = Some(needle.into());
+        self
+    }
+
+    pub fn correlation_id<S: Into<String>>(mut self, id: S) -> Self {
+        self.correlation_id = Some(id.into());
+        self
+    }
+
+    // Inclusive lower bound on `ts_ms`.
+    pub fn since(mut self, ts_ms: u128) -> Self {
+        self.since_ms = Some(ts_ms);
+        self
+    }
+
+    // Exclusive upper bound on `ts_ms`.
+    pub fn until(mut self, ts_ms: u128) -> Self {
+        self.until_ms = Some(ts_ms);
+        self
+    }
+
+    pub fn kv<K: Into<String>, V: Into<serde_json::Value>>(mut self, key: K, value: V) -> Self {
+        self.kv = Some((key.into(), value.into()));
+        self
+    }
+
+    pub fn matches(&self, ev: &Event) -> bool {
+        self.level.as_ref().is_none_or(|l| ev.level == l.as_str())
+            && self.min_level.as_ref().is_none_or(|min| ev.level.partial_cmp(min).is_some_and(|o| o.is_ge()))
+            && self.source.as_ref().is_none_or(|s| &ev.source == s)
+            && self.contains.as_ref().is_none_or(|c| ev.message.contains(c.as_str()))
+            && self.correlation_id.as_ref().is_none_or(|id| ev.correlation_id.as_ref() == Some(id))
+            && self.since_ms.is_none_or(|t| ev.ts_ms >= t)
+            && self.until_ms.is_none_or(|t| ev.ts_ms < t)
+            && self.kv.as_ref().is_none_or(|(key, value)| ev.kv.get(key) == Some(value))
+    }
+}
+
+// Field names accepted by the exporters, in their default column order.
+pub const EVENT_FIELDS: [&str; 5] = ["ts_ms", "source", "level", "message", "kv"];
+
+// Keep only the requested fields of an event, in the requested order.
+fn project(event: &Event, fields: &[&str]) -> Result<serde_json::Map<String, serde_json::Value>> {
+    let serde_json::Value::Object(mut full) = serde_json::to_value(event)? else {
+        return Err("event did not serialize to an object".into());
+    };
+    let mut out = serde_json::Map::new();
+    for &field in fields {
+        let value = full.remove(field).ok_or_else(|| format!("unknown event field: {field}"))?;
+        out.insert(field.to_string(), value);
+    }
+    Ok(out)
+}
+
+// Quote a CSV cell when it contains a delimiter, quote or newline.
+fn csv_cell(value: &serde_json::Value) -> String {
+    let raw = match value {
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Null => String::new(),
+        other => other.to_string(),
+    };
+    if raw.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", raw.replace('"', "\"\""))
+    } else {
+        raw
+    }
+}
+
+// What a compaction pass should drop besides malformed lines.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompactOptions {
+    // Drop events with `ts_ms` strictly before this cutoff.
+    pub before_ms: Option<u128>,
+    // Keep only the first of several identical events.
+    pub dedupe: bool,
+    // Drop events below this level (unknown levels are kept).
+    pub min_level: Option<Level>,
+}
+
+// Outcome of a compaction pass over the on-disk log.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompactStats {
+    pub kept: usize,
+    pub malformed: usize,
+    pub expired: usize,
+    pub duplicates: usize,
+    pub filtered: usize,
+}
+
+impl CompactStats {
+    // Total records dropped from the file.
+    pub fn removed(&self) -> usize {
+        self.malformed + self.expired + self.duplicates + self.filtered
+    }
+}
+
+// What a vaultline holds, from `Vaultline::stats`. Counts and timestamps cover
+// every persisted event (local archives and the active file, or the store), or
+// the in-memory events when nothing is persisted.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct VaultlineStats {
+    pub total: usize,
+    pub by_level: BTreeMap<String, usize>,
+    pub by_source: BTreeMap<String, usize>,
+    pub oldest_ms: Option<u128>,
+    pub newest_ms: Option<u128>,
+    // Active file plus local archives.
+    pub disk_bytes: u64,
+    pub segments: usize,
+    pub in_memory: usize,
+    // Approximate bytes held by the in-memory events.
+    pub memory_bytes: usize,
+}
+
+impl VaultlineStats {
+    fn count(&mut self, ev: &Event) {
+        self.total += 1;
+        *self.by_level.entry(ev.level.to_string()).or_default() += 1;
+        *self.by_source.entry(ev.source.clone()).or_default() += 1;
+        self.oldest_ms = Some(self.oldest_ms.map_or(ev.ts_ms, |t| t.min(ev.ts_ms)));
+        self.newest_ms = Some(self.newest_ms.map_or(ev.ts_ms, |t| t.max(ev.ts_ms)));
+    }
+}
+
+// Rough heap plus inline size of an event.
+fn event_footprint(ev: &Event) -> usize {
+    let kv = if ev.kv.is_null() { 0 } else { ev.kv.to_string().len() };
+    std::mem::size_of::<Event>()
+        + ev.source.len()
+        + ev.message.len()
+        + kv
+        + ev.correlation_id.as_ref().map_or(0, String::len)
+        + ev.trace_id.as_ref().map_or(0, String::len)
+        + ev.idempotency_key.as_ref().map_or(0, String::len)
+}
+
+// How `Vaultline::search` interprets its query.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchOptions {
+    pub case_insensitive: bool,
+    // Treat the query as a regular expression instead of literal text.
+    pub regex: bool,
+    // Stop after this many matches.
+    pub limit: Option<usize>,
+}
+
+// Whether any string inside `value` (at any depth) satisfies `pred`.
+fn any_kv_string(value: &serde_json::Value, pred: &dyn Fn(&str) -> bool) -> bool {
+    match value {
+        serde_json::Value::String(s) => pred(s),
+        serde_json::Value::Array(items) => items.iter().any(|v| any_kv_string(v, pred)),
+        serde_json::Value::Object(map) => map.values().any(|v| any_kv_string(v, pred)),
+        _ => false,
+    }
+}
+
+// Outcome of merging an external NDJSON file into a vaultline.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportStats {
+    pub imported: usize,
+    pub duplicates: usize,
+    pub malformed: usize,
+}
+
+// Identity used to drop duplicates on import: the timestamp, source and
+// message. `seq` is left out: each host numbers its own log, so equal numbers
+// from different files say nothing about the events.
+#[derive(PartialEq, Eq, Hash)]
+struct ImportKey(u128, String, String);
+
+impl ImportKey {
+    fn of(ev: &Event) -> Self {
+        ImportKey(ev.ts_ms, ev.source.clone(), ev.message.clone())
+    }
+}
+
+// Merge imported events (sorted by timestamp) into existing ones, each tagged
+// with what the caller keeps for it. Returns the tags (None for imported
+// events), the merged events and the position of the first imported one.
+fn merge_imports<T>(existing: Vec<(T, Event)>, fresh: Vec<Event>) -> (Vec<Option<T>>, Vec<Event>, usize) {
+    let existing = existing.into_iter().map(|(tag, ev)| (Some(tag), ev)).collect();
+    let fresh = fresh.into_iter().map(|ev| (None, ev)).collect();
+    let merged = merge_by_ts(existing, fresh, |(_, ev)| ev.ts_ms);
+    let first = merged.iter().position(|(tag, _)| tag.is_none()).unwrap_or(merged.len());
+    let (tags, events) = merged.into_iter().unzip();
+    (tags, events, first)
+}
+
+// Merge `incoming` (sorted by timestamp) into `existing`, keeping the existing
+// order and placing each incoming item after everything not newer than it.
+fn merge_by_ts<T>(existing: Vec<T>, incoming: Vec<T>, ts: impl Fn(&T) -> u128) -> Vec<T> {
+    let mut merged = Vec::with_capacity(existing.len() + incoming.len());
+    let mut incoming = incoming.into_iter().peekable();
+    for item in existing {
+        while let Some(next) = incoming.next_if(|next| ts(next) < ts(&item)) {
+            merged.push(next);
         }
+        merged.push(item);
+    }
+    merged.extend(incoming);
+    merged
+}
 
-        Ok(())
+// When buffered appends are pushed to the OS. `every_n = 0` never flushes on a
+// count; the interval is checked on append (there is no background timer).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlushPolicy {
+    pub every_n: u32,
+    pub interval: Option<Duration>,
+}
+
+impl Default for FlushPolicy {
+    // Flush after every append so other readers see each event immediately.
+    fn default() -> Self {
+        Self { every_n: 1, interval: None }
     }
+}
 
-    // Retrieve the last n events in memory.
-    pub fn tail(&self, n: usize) -> Vec<&Event> {
-        let len = self.mem.len();
-        let start = len.saturating_sub(n);
-        self.mem[start..].iter().collect()
+// How hard `append` works to get events onto stable storage. Setting one also
+// sets the matching flush policy; the fsync variants flush before syncing.
+//
+// In config: `durability = "none"`, `"fsync_every_write"`,
+// `{ flush_every_n = 100 }` or `{ fsync_interval_ms = 1000 }`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DurabilityPolicy {
+    // Leave it to the write buffer; nothing is flushed until it fills or `flush()`.
+    None,
+    // Flush to the OS every N appends (no fsync).
+    FlushEveryN(u32),
+    // Flush and fsync on every append.
+    FsyncEveryWrite,
+    // Flush and fsync once this much time has passed since the last sync.
+    #[serde(rename = "fsync_interval_ms", with = "duration_ms")]
+    FsyncInterval(Duration),
+}
+
+impl Default for DurabilityPolicy {
+    fn default() -> Self {
+        Self::FlushEveryN(1)
     }
+}
 
-    // Create an in-memory only vaultline (no file).
-    pub fn new_in_memory() -> Self {
-        Self { mem: Vec::new(), file: None }
+mod duration_ms {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(d: &Duration, s: S) -> std::result::Result<S::Ok, S::Error> {
+        s.serialize_u64(d.as_millis() as u64)
     }
 
-    pub fn normalize_event(ev: &mut Event) {
-        if ev.level.is_empty() {
-            ev.level = "info".to_string();
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> std::result::Result<Duration, D::Error> {
+        u64::deserialize(d).map(Duration::from_millis)
+    }
+}
+
+// On-disk record encoding, chosen when the vaultline is opened.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    // One JSON event per line.
+    #[default]
+    Ndjson,
+    // Length-prefixed MessagePack frames with a CRC32 each; faster to load.
+    Binary,
+}
+
+// Vaultline failures callers may want to match on (downcast the crate `Error`).
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum VaultlineError {
+    // Another process held the append lock for longer than the configured timeout.
+    LockTimeout { path: PathBuf, waited: Duration },
+    // A `BackgroundWriter` queue was full under `Backpressure::Error`.
+    QueueFull { capacity: usize },
+}
+
+impl std::fmt::Display for VaultlineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::LockTimeout { path, waited } => {
+                write!(f, "timed out after {waited:?} waiting for the append lock on {}", path.display())
+            }
+            Self::QueueFull { capacity } => write!(f, "vaultline writer queue is full ({capacity} pending)"),
         }
-        if ev.source.is_empty() {
-            ev.source = "unknown".to_string();
+    }
+}
+
+impl std::error::Error for VaultlineError {}
+
+// Outcome of `Vaultline::erase`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EraseReport {
+    pub erased: usize,
+    pub segments_rewritten: usize,
+}
+
+// Outcome of `load_from_disk`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoadReport {
+    pub loaded: usize,
+    // Lines that could not be parsed (bad JSON, checksum mismatch, unknown schema).
+    pub skipped: usize,
+    // 1-based line number of the first skipped line.
+    pub first_error_line: Option<usize>,
+    // `line N: reason` for the first `LOAD_ERRORS_KEPT` skipped lines.
+    pub errors: Vec<String>,
+}
+
+const LOAD_ERRORS_KEPT: usize = 20;
+
+impl LoadReport {
+    // Skip a line that decoded to nothing, working out why.
+    fn skip(&mut self, line_no: usize, line: &str, checksums: bool) {
+        if self.errors.len() >= LOAD_ERRORS_KEPT {
+            return self.skip_because(line_no, String::new());
         }
-        if ev.message.is_empty() {
-            ev.message = "(no message)".to_string();
+        let reason = if checksums && !has_crc(line) {
+            "missing checksum".to_string()
+        } else if !crc_ok(line, false) {
+            "checksum mismatch".to_string()
+        } else {
+            match serde_json::from_str::<serde_json::Value>(line) {
+                Err(e) => e.to_string(),
+                Ok(_) => "not a valid event record".to_string(),
+            }
+        };
+        self.skip_because(line_no, reason);
+    }
+
+    fn skip_because(&mut self, line_no: usize, reason: String) {
+        self.skipped += 1;
+        self.first_error_line.get_or_insert(line_no);
+        if self.errors.len() < LOAD_ERRORS_KEPT {
+            self.errors.push(format!("line {line_no}: {reason}"));
         }
+    }
+}
 
-        if ev.ts_ms == 0 {
-            ev.ts_ms = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_millis();
-        }  
+// Outcome of `load_with_recovery`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecoveryReport {
+    pub loaded: usize,
+    // Corrupt lines moved to `<file>.quarantine`.
+    pub quarantined: usize,
+    // The file ended in a partial (torn) write.
+    pub torn_tail: bool,
+}
 
-        if ev.kv.is_null() {
-            ev.kv = serde_json::Value::Object(serde_json::Map::new());
-        }
+// Bounds on how much history a vaultline keeps, in memory and on disk.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    // Prune events older than this.
+    pub max_age: Option<Duration>,
+    // Keep only the newest this-many events.
+    pub max_events: Option<usize>,
+}
+
+// What `enforce_retention` last found in a segment, valid while the file's
+// size and mtime are unchanged.
+#[derive(Debug, Clone, Copy)]
+struct SegmentRange {
+    len: u64,
+    modified: SystemTime,
+    oldest_ms: u128,
+    events: usize,
+}
+
+const CRC_FIELD: &str = ",\"crc\":\"";
+
+// Append a `crc` field holding the CRC32 of the line as serialized without it.
+fn with_crc(json: &str) -> String {
+    let body = json.strip_suffix('}').unwrap_or(json);
+    format!("{body}{CRC_FIELD}{:08x}\"}}", crc32fast::hash(json.as_bytes()))
+}
+
+// A plaintext line's body before the `crc` field, and the CRC's hex digits.
+fn crc_parts(line: &str) -> Option<(&str, &str)> {
+    let body = line.strip_suffix("\"}")?;
+    let split = body.len().checked_sub(8)?;
+    let (rest, hex) = (body.get(..split)?, body.get(split..)?);
+    Some((rest.strip_suffix(CRC_FIELD)?, hex))
+}
+
+// Whether a plaintext line carries a CRC (see `with_crc`).
+fn has_crc(line: &str) -> bool {
+    crc_parts(line).is_some()
+}
+
+// Lines with a checksum must match it. Lines without one (or whose checksum
+// field is damaged beyond recognition) pass only when checksums are not
+// `required`.
+fn crc_ok(line: &str, required: bool) -> bool {
+    let Some((head, hex)) = crc_parts(line) else { return !required };
+    hex.bytes().all(|b| b.is_ascii_hexdigit())
+        && u32::from_str_radix(hex, 16).ok() == Some(crc32fast::hash(format!("{head}}}").as_bytes()))
+}
+
+// Checksum-verify and parse a plaintext NDJSON record, upgrading old schema
+// versions. With `checksums` a record without a CRC is rejected.
+fn parse_record(json: &str, checksums: bool) -> Result<Option<Event>> {
+    if !crc_ok(json, checksums) { return Ok(None) }
+    match serde_json::from_str(json) {
+        Ok(value) => schema::upgrade(value),
+        Err(_) => Ok(None),
     }
+}
 
-    // Load events from disk into memory (if file exists).
-    pub fn load_from_disk(&mut self) -> Result<usize> {
-        let Some(ref path) = self.file else { return Ok(0) };
-        if !path.exists() { return Ok(0) }
-        let f = OpenOptions::new().read(true).open(path)?;
-        let mut added = 0usize;
-        for line in BufReader::new(f).lines() {
-            let line = line?;
-            if line.trim().is_empty() { continue; }
-            if let Ok(ev) = serde_json::from_str::<Event>(&line) {
-                self.mem.push(ev);
-                added += 1;
+// Names of the fields `filter` constrains (`kv.<key>` for the kv match), for
+// erasure tombstones.
+fn erased_fields(filter: &Filter) -> Vec<String> {
+    let Filter { level, min_level, source, contains, correlation_id, since_ms, until_ms, kv } = filter;
+    let mut fields: Vec<String> = [
+        ("level", level.is_some()),
+        ("min_level", min_level.is_some()),
+        ("source", source.is_some()),
+        ("contains", contains.is_some()),
+        ("correlation_id", correlation_id.is_some()),
+        ("since_ms", since_ms.is_some()),
+        ("until_ms", until_ms.is_some()),
+    ]
+    .into_iter()
+    .filter(|(_, set)| *set)
+    .map(|(name, _)| name.to_string())
+    .collect();
+    fields.extend(kv.iter().map(|(key, _)| format!("kv.{key}")));
+    fields
+}
+
+// Copy every readable event from `src` (in `from` format) to a new file `dst` in
+// `to` format. Returns the number of events written.
+pub fn convert(src: &Path, from: Format, dst: &Path, to: Format) -> Result<usize> {
+    if !src.exists() {
+        return Err(format!("{} does not exist", src.display()).into());
+    }
+    if dst.exists() {
+        return Err(format!("refusing to overwrite {}", dst.display()).into());
+    }
+    let mut reader = Vaultline::new_with_format(src, from)?;
+    reader.load_from_disk()?;
+    let mut writer = Vaultline::new_with_format(dst, to)?;
+    writer.append_batch(reader.all().to_vec())
+}
+
+// Segments ending in `.gz` are gzip-compressed archives.
+fn is_compressed(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "gz")
+}
+
+// Visit the non-blank lines of a file from last to first, reading fixed-size
+// chunks backwards from the end, until `visit` returns false.
+fn read_lines_rev(path: &Path, mut visit: impl FnMut(&str) -> bool) -> Result<()> {
+    const CHUNK: u64 = 64 * 1024;
+    let mut file = std::fs::File::open(path)?;
+    let mut pos = file.metadata()?.len();
+    // Bytes after the last newline seen so far: the end of a line whose start
+    // is in an earlier chunk.
+    let mut carry = Vec::new();
+    while pos > 0 {
+        let len = CHUNK.min(pos);
+        pos -= len;
+        let mut buf = vec![0; len as usize];
+        file.seek(SeekFrom::Start(pos))?;
+        file.read_exact(&mut buf)?;
+        buf.extend_from_slice(&carry);
+        let mut lines = buf.split(|b| *b == b'\n');
+        let first = lines.next().unwrap_or_default().to_vec();
+        for line in lines.rev() {
+            let line = String::from_utf8_lossy(line);
+            if !line.trim().is_empty() && !visit(&line) {
+                return Ok(());
             }
         }
-        Ok(added)
+        carry = first;
+    }
+    let line = String::from_utf8_lossy(&carry);
+    if !line.trim().is_empty() {
+        visit(&line);
     }
+    Ok(())
+}
 
-    // Retrieve all events in memory.
-    pub fn all(&self) -> &[Event] {
-        &self.mem
+// Open a segment for line reading, decompressing `.gz` archives transparently.
+fn open_segment(path: &Path) -> Result<Box<dyn BufRead>> {
+    let file = std::fs::File::open(path)?;
+    if !is_compressed(path) {
+        return Ok(Box::new(BufReader::new(file)));
+    }
+    #[cfg(feature = "compression")]
+    {
+        Ok(Box::new(BufReader::new(flate2::read::GzDecoder::new(file))))
+    }
+    #[cfg(not(feature = "compression"))]
+    {
+        Err(format!("{} is compressed; rebuild with the `compression` feature", path.display()).into())
     }
 }
 
-// Unit tests for Vaultline
-#[cfg(test)]
-mod tests {
-    use super::*;
+// Decompress a gzipped segment held in memory.
+#[cfg(feature = "tar")]
+fn open_segment_bytes(bytes: &[u8]) -> Result<Box<dyn Read + '_>> {
+    #[cfg(feature = "compression")]
+    {
+        Ok(Box::new(flate2::read::GzDecoder::new(bytes)))
+    }
+    #[cfg(not(feature = "compression"))]
+    {
+        let _ = bytes;
+        Err("segment is compressed; rebuild with the `compression` feature".into())
+    }
+}
 
-    #[test]
-    fn test_event_creation() {
-        let ev = Event::now("test_source", "info", "This is a test");
-        assert_eq!(ev.source, "test_source");
-        assert_eq!(ev.level, "info");
-        assert_eq!(ev.message, "This is a test");
-        assert!(ev.ts_ms > 0);
-        assert!(ev.kv.is_null());
+// Write `lines` to `file`, gzip-encoding them when `compress` is set.
+fn write_segment(file: std::fs::File, lines: &[String], compress: bool) -> Result<()> {
+    let mut out: Box<dyn Write> = if compress {
+        #[cfg(feature = "compression")]
+        {
+            Box::new(flate2::write::GzEncoder::new(file.try_clone()?, flate2::Compression::default()))
+        }
+        #[cfg(not(feature = "compression"))]
+        {
+            return Err("compressed segments require the `compression` feature".into());
+        }
+    } else {
+        Box::new(file.try_clone()?)
+    };
+    for line in lines {
+        out.write_all(line.as_bytes())?;
+        out.write_all(b"\n")?;
     }
+    out.flush()?;
+    // Dropping the encoder writes the gzip trailer before we sync.
+    drop(out);
+    file.sync_all()?;
+    Ok(())
+}
 
-    #[test]
-    fn test_vaultline_in_memory() {
-        let mut vault = Vaultline::new_in_memory();
-        assert_eq!(vault.all().len(), 0);
+const DAY_MS: u128 = 86_400_000;
 
-        let ev1 = Event::now("src1", "info", "First event");
-        vault.append(ev1.clone()).unwrap();
-        assert_eq!(vault.all().len(), 1);
-        assert_eq!(vault.all()[0], ev1);
+// Rotation timestamp of an archive of the active file `file_name`: either
+// `<file_name>.<ts_ms>[.gz]` from size rotation or a daily archive (see
+// `daily_archive`) of the same stem, which counts as rotated at the end of its
+// day (plus N ms, so same-day archives keep their order).
+fn segment_stamp(name: &str, file_name: &str) -> Option<u128> {
+    if let Some(rest) = name.strip_prefix(file_name).and_then(|rest| rest.strip_prefix('.')) {
+        return rest.strip_suffix(".gz").unwrap_or(rest).parse().ok();
+    }
+    let (stem, day, n) = daily_archive(name)?;
+    (stem == file_stem(file_name)).then(|| (day + 1) * DAY_MS + n)
+}
 
-        let ev2 = Event::now("src2", "error", "Second event");
-        vault.append(ev2.clone()).unwrap();
-        assert_eq!(vault.all().len(), 2);
-        assert_eq!(vault.all()[1], ev2);
+// `event.log` -> `event`: the part of the name daily archives keep.
+fn file_stem(file_name: &str) -> &str {
+    file_name.split_once('.').map_or(file_name, |(stem, _)| stem)
+}
 
-        let tail = vault.tail(1);
-        assert_eq!(tail.len(), 1);
-        assert_eq!(tail[0], &ev2);
+// Stem, UTC day (days since the epoch) and counter of a daily archive named
+// `<stem>-YYYY-MM-DD[-N].ndjson[.gz]`.
+pub(crate) fn daily_archive(name: &str) -> Option<(&str, u128, u128)> {
+    let base = name.strip_suffix(".gz").unwrap_or(name).strip_suffix(".ndjson")?;
+    fn parse(base: &str) -> Option<(&str, u128)> {
+        let (stem, date) = base.split_at_checked(base.len().checked_sub(11)?)?;
+        let date = date.strip_prefix('-')?;
+        let (y, md) = date.split_once('-')?;
+        let (m, d) = md.split_once('-')?;
+        if (y.len(), m.len(), d.len()) != (4, 2, 2) { return None; }
+        let ms = civil_to_ms(y.parse().ok()?, m.parse().ok()?, d.parse().ok()?, 0)?;
+        Some((stem, ms / DAY_MS))
+    }
+    if let Some((stem, day)) = parse(base) {
+        return Some((stem, day, 0));
+    }
+    let (head, n) = base.rsplit_once('-')?;
+    let (stem, day) = parse(head)?;
+    Some((stem, day, n.parse().ok().filter(|&n| n > 0)?))
+}
 
-        let tail_all = vault.tail(5);
-        assert_eq!(tail_all.len(), 2);
+// `<stem>-YYYY-MM-DD[-N].ndjson` for UTC day `day`.
+fn daily_archive_name(stem: &str, day: u128, n: u128) -> String {
+    // Civil date from days since the epoch (inverse of `civil_to_ms`).
+    let z = day as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + i64::from(m <= 2);
+    match n {
+        0 => format!("{stem}-{y:04}-{m:02}-{d:02}.ndjson"),
+        n => format!("{stem}-{y:04}-{m:02}-{d:02}-{n}.ndjson"),
     }
+}
 
-    #[test]
-    fn test_vaultline_file_backed() {
-        let temp_dir = std::env::temp_dir();
-        let log_path = temp_dir.join(format!("vaultline_test_{}.log", std::process::id()));
-        if log_path.exists() {
-            let _ = std::fs::remove_file(&log_path);
-        }
+// Open a file that must not exist yet; the check and the create are one step,
+// so a file created concurrently is never overwritten.
+fn create_new(path: &Path) -> Result<std::fs::File> {
+    match OpenOptions::new().write(true).create_new(true).open(path) {
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Err(format!("refusing to overwrite {}", path.display()).into()),
+        other => Ok(other?),
+    }
+}
 
-        let mut vault = Vaultline::new(&log_path).unwrap();
-        assert_eq!(vault.all().len(), 0);
+// `<path><suffix>` next to the original file, e.g. `event.log.tmp`.
+fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
 
-        let ev1 = Event::now("file_src1", "info", "File event 1");
-        vault.append(ev1.clone()).unwrap();
-        assert_eq!(vault.all().len(), 1);
+// Append-only event log with file backing.
+pub struct Vaultline {
+    mem: EventBuffer,
+    file: Option<PathBuf>,
+    // Opened with `open_read_only`: every write path fails.
+    read_only: bool,
+    quarantine_skipped: bool,
+    min_level: Option<Level>,
+    source_min_levels: HashMap<String, Level>,
+    atomic_rewrite: bool,
+    hard_limit: Option<usize>,
+    // level -> (keep 1 in N, events seen so far)
+    level_sampling: HashMap<Level, (u64, u64)>,
+    dropped: HashMap<String, u64>,
+    rate_limiter: Option<rate_limit::RateLimiter>,
+    rollup_window: Option<u128>,
+    rollups: rollup::Rollup,
+    required_kv: HashMap<String, Vec<String>>,
+    max_archives: Option<usize>,
+    memory_cap: Option<usize>,
+    metrics: Option<HashMap<String, AtomicU64>>,
+    source_aliases: HashMap<String, String>,
+    omit_null_kv: bool,
+    rotate_bytes: Option<u64>,
+    rotate_daily: bool,
+    // UTC day of the last write to the active file, once known.
+    active_day: Option<u128>,
+    checksums: bool,
+    format: Format,
+    // The active file's store, holding its append handle open across appends;
+    // closed whenever the active file is rewritten or moved.
+    active: Option<FileStore>,
+    flush_policy: FlushPolicy,
+    lock_timeout: Option<Duration>,
+    unflushed: u32,
+    last_flush: Instant,
+    durability: DurabilityPolicy,
+    last_sync: Instant,
+    // Set by `with_store` in place of `active`.
+    store: Option<Box<dyn EventStore>>,
+    archiver: Option<Archiver>,
+    retention: RetentionPolicy,
+    segment_ranges: HashMap<PathBuf, SegmentRange>,
+    subscribers: Vec<Sender<Event>>,
+    sinks: Vec<Box<dyn EventSink>>,
+    hooks: Vec<Box<dyn FnMut(Event) -> Option<Event> + Send>>,
+    index: EventIndex,
+    sequencing: bool,
+    // Next sequence number to hand out; `seq_resumed` once it accounts for the disk.
+    next_seq: u64,
+    seq_resumed: bool,
+    // Idempotency keys already stored; loaded from `<file>.keys` (or rebuilt from
+    // the log) on the first keyed append.
+    idempotency_keys: Option<HashSet<String>>,
+    redactor: Option<Redactor>,
+    blob_store: Option<BlobStore>,
+    // kv strings at least this long are moved to `blob_store` on append.
+    blob_min_bytes: Option<usize>,
+    // Keep per-hour counts in the `<file>.summary` sidecar (see `set_summaries`).
+    summaries: bool,
+    hash_chain: bool,
+    // Hash of the newest stored record once the chain has been resumed from disk.
+    last_hash: Option<String>,
+    #[cfg(feature = "compression")]
+    compress_archives: bool,
+    #[cfg(feature = "encryption")]
+    cipher: Option<cipher::LineCipher>,
+    #[cfg(feature = "signing")]
+    signer: Option<signing::EventSigner>,
+}
 
-        let ev2 = Event::now("file_src2", "warn", "File event 2");
-        vault.append(ev2.clone()).unwrap();
-        assert_eq!(vault.all().len(), 2);
+impl Vaultline {
+    // Open or create a vaultline at the given path.
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::new_with_format(path, Format::Ndjson)
+    }
 
-        // Reload from disk
-        let mut vault_reload = Vaultline::new(&log_path).unwrap();
-        assert_eq!(vault_reload
-            .load_from_disk()
-            .unwrap(), 2);
-        assert_eq!(vault_reload.all().len(), 2);
-        assert_eq!(vault_reload.all()[0], ev1);
+    // Open or create a vaultline storing records in `format`.
+    pub fn new_with_format<P: AsRef<Path>>(path: P, format: Format) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
+        // Ensure the file exists, but do not eagerly load it into memory here.
+        // Use `load_from_disk` when the caller explicitly wants to populate memory.
+        let active = FileStore::open(&path)?;
+
+        let mut vault = Self::with_file(Some(path));
+        vault.format = format;
+        vault.active = Some(active);
+        Ok(vault)
+    }
+
+    // Open an existing vaultline for reading only. Nothing is ever created,
+    // written, renamed or locked: appends, rotation, retention, compaction and
+    // recovery fail instead, so tooling can read a live log without disturbing it.
+    pub fn open_read_only<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if !path.is_file() {
+            return Err(format!("vaultline {} does not exist", path.display()).into());
+        }
+        let mut vault = Self::with_file(Some(path));
+        vault.read_only = true;
+        Ok(vault)
+    }
+
+    // An in-memory vaultline holding every event of several vaultlines (e.g.
+    // one per node), archives included, ordered by timestamp. Overlapping time
+    // ranges interleave; equal timestamps keep the order of `paths`. Records
+    // present in more than one file are kept once. Each file numbers its own
+    // records, so the merged view is re-sequenced from 1 in merged order and
+    // `since(seq)` pages through it; `prev_hash` and signatures still refer to
+    // the original files. Files are opened read-only.
+    pub fn merged(paths: &[PathBuf]) -> Result<Self> {
+        let mut events = Vec::new();
+        let mut seen = HashSet::new();
+        for path in paths {
+            let mut source = Self::open_read_only(path)?;
+            let mut err = None;
+            source.scan_all(|ev| {
+                // Identical events serialize identically, which makes the JSON a usable identity.
+                match serde_json::to_string(ev) {
+                    Ok(identity) => {
+                        if seen.insert(identity) {
+                            events.push(ev.clone());
+                        }
+                    }
+                    Err(e) => err = Some(e),
+                }
+                err.is_none()
+            })?;
+            if let Some(e) = err {
+                return Err(e.into());
+            }
+        }
+        events.sort_by_key(|ev| ev.ts_ms);
+        for (seq, ev) in (1..).zip(&mut events) {
+            ev.seq = seq;
+        }
+        let mut merged = Self::new_in_memory();
+        merged.push_loaded(events, usize::MAX, None);
+        Ok(merged)
+    }
+
+    fn with_file(file: Option<PathBuf>) -> Self {
+        Self {
+            mem: EventBuffer::default(),
+            file,
+            read_only: false,
+            quarantine_skipped: false,
+            min_level: None,
+            source_min_levels: HashMap::new(),
+            atomic_rewrite: true,
+            hard_limit: None,
+            level_sampling: HashMap::new(),
+            dropped: HashMap::new(),
+            rate_limiter: None,
+            rollup_window: None,
+            rollups: rollup::Rollup::default(),
+            required_kv: HashMap::new(),
+            max_archives: None,
+            memory_cap: None,
+            metrics: None,
+            source_aliases: HashMap::new(),
+            omit_null_kv: false,
+            rotate_bytes: None,
+            rotate_daily: false,
+            active_day: None,
+            checksums: false,
+            format: Format::Ndjson,
+            active: None,
+            flush_policy: FlushPolicy::default(),
+            lock_timeout: None,
+            unflushed: 0,
+            last_flush: Instant::now(),
+            durability: DurabilityPolicy::default(),
+            last_sync: Instant::now(),
+            store: None,
+            archiver: None,
+            retention: RetentionPolicy::default(),
+            segment_ranges: HashMap::new(),
+            subscribers: Vec::new(),
+            sinks: Vec::new(),
+            hooks: Vec::new(),
+            index: EventIndex::default(),
+            sequencing: false,
+            next_seq: 1,
+            seq_resumed: false,
+            idempotency_keys: None,
+            redactor: None,
+            blob_store: None,
+            blob_min_bytes: None,
+            summaries: false,
+            hash_chain: false,
+            last_hash: None,
+            #[cfg(feature = "compression")]
+            compress_archives: false,
+            #[cfg(feature = "encryption")]
+            cipher: None,
+            #[cfg(feature = "signing")]
+            signer: None,
+        }
+    }
+
+    // Apply the `[vault]` config section. Fails when a redaction pattern does
+    // not compile.
+    pub fn apply_config(&mut self, cfg: &VaultConfig) -> Result<()> {
+        if let Some(bytes) = cfg.rotate_bytes {
+            self.set_rotate_bytes(bytes);
+        }
+        self.set_rotate_daily(cfg.rotate_daily);
+        if let Some(k) = cfg.max_segments {
+            self.set_max_archives(k);
+        }
+        if let Some(max) = cfg.memory_cap {
+            self.set_memory_cap(max);
+        }
+        self.set_retention(RetentionPolicy {
+            max_age: cfg.max_age_secs.map(Duration::from_secs),
+            max_events: cfg.max_events,
+        });
+        self.set_checksums(cfg.checksums);
+        self.set_min_level(cfg.min_level.clone());
+        self.set_sequencing(cfg.sequence);
+        self.set_hash_chain(cfg.hash_chain);
+        self.set_summaries(cfg.summaries);
+        self.set_rate_limit(cfg.rate_limit);
+        self.set_rollup_window(cfg.rollup_window_ms.map(Duration::from_millis));
+        if let Some(redactor) = Redactor::from_config(cfg)? {
+            self.set_redactor(Some(redactor));
+        }
+        if let Some(min) = cfg.blob_min_bytes {
+            if self.blob_store.is_none()
+                && let Some(dir) = self.file.as_deref().and_then(Path::parent)
+            {
+                self.set_blob_store(Some(BlobStore::new(dir.join("blobs"))));
+            }
+            self.set_blob_offload(Some(min));
+        }
+        if let Some(policy) = cfg.durability {
+            self.set_durability(policy);
+        }
+        if let Some(ms) = cfg.lock_timeout_ms {
+            self.set_append_lock(Some(Duration::from_millis(ms)));
+        }
+        #[cfg(feature = "webhook")]
+        if let Some(ref url) = cfg.webhook_url {
+            let sink = WebhookSink::new(url.clone());
+            self.add_sink(match cfg.webhook_batch_size {
+                Some(n) => sink.batch_size(n),
+                None => sink,
+            });
+        }
+        #[cfg(feature = "otel")]
+        if let Some(ref endpoint) = cfg.otlp_endpoint {
+            self.add_sink(OtlpSink::new(endpoint));
+        }
+        Ok(())
+    }
+
+    // Tag each written line with a CRC32 so corruption is detected on read.
+    pub fn set_checksums(&mut self, on: bool) {
+        self.checksums = on;
+    }
+
+    // Set the retention policy. It is enforced after each rotation and on `enforce_retention`.
+    pub fn set_retention(&mut self, policy: RetentionPolicy) {
+        self.retention = policy;
+    }
+
+    // Prune events outside the retention policy from memory and from every segment on disk,
+    // deleting archives that end up empty. Returns how many on-disk events were removed.
+    // Archives last modified before the age cutoff, or wholly older than the newest
+    // `max_events` records, are deleted outright rather than rewritten. An archive whose
+    // oldest event and count were recorded by an earlier run, and whose size and mtime
+    // have not changed since, is skipped without reading it while it still fits.
+    pub fn enforce_retention(&mut self) -> Result<usize> {
+        let policy = self.retention;
+        if policy == RetentionPolicy::default() { return Ok(0) }
+        self.require_writable("retention")?;
+        let cutoff = match policy.max_age {
+            Some(age) => SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis().saturating_sub(age.as_millis()),
+            None => 0,
+        };
+        self.mem.retain(|ev| ev.ts_ms >= cutoff);
+        if let Some(max) = policy.max_events {
+            let excess = self.mem.len().saturating_sub(max);
+            self.mem.drop_front(excess);
+        }
+        self.reindex();
+
+        let Some(active) = self.file.clone() else { return Ok(0) };
+        self.require_ndjson("retention")?;
+        self.close_writer()?;
+        let mut segments = self.archives()?;
+        segments.push(active.clone());
+        self.segment_ranges.retain(|path, _| segments.contains(path));
+
+        // Walk newest-first so `max_events` keeps the most recent records.
+        let mut budget = policy.max_events.unwrap_or(usize::MAX);
+        let mut removed = 0;
+        for seg in segments.into_iter().rev() {
+            let meta = match std::fs::metadata(&seg) {
+                Ok(meta) => meta,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            let modified = meta.modified()?;
+            let cached = self.segment_ranges.get(&seg).filter(|r| r.len == meta.len() && r.modified == modified).copied();
+            if seg != active && (budget == 0 || modified.duration_since(UNIX_EPOCH)?.as_millis() < cutoff) {
+                removed += match cached {
+                    Some(range) => range.events,
+                    None => self.read_segment(&seg)?.len(),
+                };
+                std::fs::remove_file(&seg)?;
+                self.segment_ranges.remove(&seg);
+                self.invalidate_summary()?;
+                continue;
+            }
+            if let Some(range) = cached.filter(|r| r.oldest_ms >= cutoff && r.events <= budget) {
+                budget -= range.events;
+                continue;
+            }
+
+            let lines = self.read_segment(&seg)?;
+            let total = lines.len();
+            let mut kept: Vec<String> = Vec::new();
+            let mut oldest_ms = u128::MAX;
+            for (line, ev) in lines.into_iter().rev() {
+                if ev.ts_ms >= cutoff && budget > 0 {
+                    budget -= 1;
+                    oldest_ms = oldest_ms.min(ev.ts_ms);
+                    kept.push(line);
+                }
+            }
+            if kept.len() != total {
+                removed += total - kept.len();
+                self.invalidate_summary()?;
+                kept.reverse();
+                if kept.is_empty() && seg != active {
+                    std::fs::remove_file(&seg)?;
+                    self.segment_ranges.remove(&seg);
+                    continue;
+                }
+                self.rewrite_file(&seg, &kept)?;
+            }
+            let meta = std::fs::metadata(&seg)?;
+            self.segment_ranges.insert(seg, SegmentRange { len: meta.len(), modified: meta.modified()?, oldest_ms, events: kept.len() });
+        }
+        Ok(removed)
+    }
+
+    // Rotate the active file once an append leaves it at or above `max_bytes`.
+    pub fn set_rotate_bytes(&mut self, max_bytes: u64) {
+        self.rotate_bytes = Some(max_bytes);
+    }
+
+    // Refuse appends once `max` events are held in memory (an error, not a silent drop).
+    pub fn set_hard_limit(&mut self, max: usize) {
+        self.hard_limit = Some(max);
+    }
+
+    // Keep at most `max` events in memory, dropping the oldest (they remain on disk).
+    // Eviction is amortized O(1), so a small cap costs nothing per append.
+    pub fn set_memory_cap(&mut self, max: usize) {
+        self.memory_cap = Some(max);
+        self.trim_to_cap();
+    }
+
+    fn trim_to_cap(&mut self) {
+        if let Some(max) = self.memory_cap
+            && self.mem.len() > max
+        {
+            let excess = self.mem.len() - max;
+            self.index.evict_front(&self.mem[..excess]);
+            self.mem.drop_front(excess);
+        }
+    }
+
+    // Buffer a stored event, keeping the index in step.
+    fn push_mem(&mut self, event: Event) {
+        self.next_seq = self.next_seq.max(event.seq + 1);
+        self.index.insert(self.mem.len(), &event);
+        self.mem.push(event);
+    }
+
+    // Rebuild the index after events were removed from the middle of the buffer.
+    fn reindex(&mut self) {
+        self.index = EventIndex::rebuild(&self.mem);
+    }
+
+    // Start cumulative per-level counters, incremented on every stored append.
+    // Unlike `level_counts`, these survive memory-cap drops.
+    pub fn enable_metrics(&mut self) {
+        self.metrics.get_or_insert_with(HashMap::new);
+    }
+
+    // Cumulative per-level append counts (empty unless metrics are enabled).
+    pub fn metric_counts(&self) -> HashMap<String, u64> {
+        self.metrics
+            .iter()
+            .flatten()
+            .map(|(level, n)| (level.clone(), n.load(Ordering::Relaxed)))
+            .collect()
+    }
+
+    // Counts by level and source, time span, disk and memory usage. Persisted
+    // segments are read one at a time; nothing is loaded into memory.
+    pub fn stats(&mut self) -> Result<VaultlineStats> {
+        let mut stats = VaultlineStats {
+            in_memory: self.mem.len(),
+            memory_bytes: self.mem.iter().map(event_footprint).sum(),
+            ..Default::default()
+        };
+        if let Some(active) = self.file.clone() {
+            let mut segments = self.archives()?;
+            segments.push(active);
+            for seg in segments.iter().filter(|seg| seg.exists()) {
+                stats.disk_bytes += std::fs::metadata(seg)?.len();
+                stats.segments += 1;
+            }
+        }
+        if self.summary_path().is_some() {
+            self.load_summary()?.fill(&mut stats);
+            return Ok(stats);
+        }
+        self.scan_all(|ev| {
+            stats.count(ev);
+            true
+        })?;
+        Ok(stats)
+    }
+
+    // Event counts per hour, source and level over everything persisted, for
+    // dashboards. Served from the summary sidecar when summaries are on.
+    pub fn hourly_counts(&mut self) -> Result<Vec<HourlyCount>> {
+        if self.summary_path().is_some() {
+            return Ok(self.load_summary()?.into_rows());
+        }
+        let mut counts = summary::Summary::default();
+        self.scan_all(|ev| {
+            counts.add(ev);
+            true
+        })?;
+        Ok(counts.into_rows())
+    }
+
+    // Maintain rolling counts per (source, level, hour) in `<file>.summary`
+    // as batches are stored, so `stats` and `hourly_counts` read that instead
+    // of every segment. The sidecar is built from the log on first use and
+    // rebuilt after anything that removes or rewrites stored events
+    // (retention, compaction, erasure, import, restore). File-backed
+    // vaultlines only; with a store, counts are always scanned.
+    pub fn set_summaries(&mut self, on: bool) {
+        self.summaries = on;
+    }
+
+    fn summary_path(&self) -> Option<PathBuf> {
+        self.file.as_ref().filter(|_| self.summaries && self.store.is_none()).map(|path| sibling_path(path, ".summary"))
+    }
+
+    // The sidecar's counts, rebuilding it from the log when it is missing or
+    // unreadable. Read-only vaultlines count from the log but never write the
+    // sidecar.
+    fn load_summary(&mut self) -> Result<summary::Summary> {
+        let Some(path) = self.summary_path() else { return Ok(summary::Summary::default()) };
+        if path.exists() {
+            if let Some(counts) = summary::Summary::read(&path, self.read_only)? {
+                return Ok(counts);
+            }
+            tracing::warn!(path = %path.display(), "summary sidecar is damaged; rebuilding it");
+        }
+        let mut counts = summary::Summary::default();
+        self.scan_all(|ev| {
+            counts.add(ev);
+            true
+        })?;
+        if !self.read_only {
+            counts.write(&path)?;
+        }
+        Ok(counts)
+    }
+
+    // Add a stored batch to the sidecar. A missing sidecar stays missing: the
+    // next read rebuilds it, this batch included.
+    fn record_summary(&self, batch: &[Event]) -> Result<()> {
+        match self.summary_path() {
+            Some(path) if path.exists() => summary::Summary::of(batch).append(&path),
+            _ => Ok(()),
+        }
+    }
+
+    // Drop the sidecar after stored events were removed or rewritten.
+    fn invalidate_summary(&self) -> Result<()> {
+        let Some(path) = self.file.as_ref().map(|path| sibling_path(path, ".summary")) else { return Ok(()) };
+        match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    // Visit every persisted event, oldest segment first: local archives then the
+    // active file (streamed), or the store; in-memory vaultlines visit what they
+    // hold. Stops early once `visit` returns false.
+    fn scan_all(&mut self, mut visit: impl FnMut(&Event) -> bool) -> Result<()> {
+        if let Some(ref mut store) = self.store {
+            for ev in store.scan()? {
+                if !visit(&ev) { break; }
+            }
+            return Ok(());
+        }
+        if self.file.is_none() {
+            for ev in self.mem.iter() {
+                if !visit(ev) { break; }
+            }
+            return Ok(());
+        }
+        for ev in self.iter_disk()? {
+            if !visit(&ev?) { break; }
+        }
+        Ok(())
+    }
+
+    // Events whose message or any kv string value (nested ones included) matches
+    // `query`, oldest first, searching every persisted segment (see `scan_all`).
+    pub fn search(&mut self, query: &str, opts: &SearchOptions) -> Result<Vec<Event>> {
+        let pattern = if opts.regex { query.to_string() } else { regex::escape(query) };
+        let re = regex::RegexBuilder::new(&pattern)
+            .case_insensitive(opts.case_insensitive)
+            .build()
+            .map_err(|e| format!("invalid search pattern {query:?}: {e}"))?;
+        let limit = opts.limit.unwrap_or(usize::MAX);
+        let mut hits = Vec::new();
+        self.scan_all(|ev| {
+            if re.is_match(&ev.message) || any_kv_string(&ev.kv, &|s| re.is_match(s)) {
+                hits.push(ev.clone());
+            }
+            hits.len() < limit
+        })?;
+        Ok(hits)
+    }
+
+    // Per-level counts of the events currently held in memory.
+    pub fn level_counts(&self) -> HashMap<String, usize> {
+        let mut counts = HashMap::new();
+        for ev in self.mem.iter() {
+            *counts.entry(ev.level.to_string()).or_default() += 1;
+        }
+        counts
+    }
+
+    // Rewrites (compaction) go through `<file>.tmp` + rename when enabled (default), so a
+    // crash mid-rewrite never leaves a half-written log.
+    pub fn set_atomic_rewrite(&mut self, atomic: bool) {
+        self.atomic_rewrite = atomic;
+    }
+
+    // Well-formed lines of a segment alongside their parsed events. Lines are
+    // returned as stored, so rewriting them keeps their encryption.
+    fn read_segment(&self, path: &Path) -> Result<Vec<(String, Event)>> {
+        let mut out = Vec::new();
+        for line in open_segment(path)?.lines() {
+            let line = line?;
+            if let Some(ev) = self.decode_line(&line)? {
+                out.push((line, ev));
+            }
+        }
+        Ok(out)
+    }
+
+    fn require_writable(&self, op: &str) -> Result<()> {
+        if self.read_only {
+            return Err(format!("{op} is not allowed on a read-only vaultline").into());
+        }
+        Ok(())
+    }
+
+    // Operations that rewrite segments line by line only understand NDJSON.
+    fn require_ndjson(&self, op: &str) -> Result<()> {
+        match self.format {
+            Format::Ndjson => Ok(()),
+            Format::Binary => Err(format!("{op} is not supported for binary-format vaultlines; convert to ndjson first").into()),
+        }
+    }
+
+    // Replace the whole backing file with the given NDJSON lines.
+    fn rewrite_file(&self, path: &Path, lines: &[String]) -> Result<()> {
+        self.require_writable("rewriting segments")?;
+        let target = if self.atomic_rewrite { sibling_path(path, ".tmp") } else { path.to_path_buf() };
+        write_segment(std::fs::File::create(&target)?, lines, is_compressed(path))?;
+        if self.atomic_rewrite {
+            std::fs::rename(&target, path)?;
+        }
+        Ok(())
+    }
+
+    // Rewrite the on-disk log keeping only well-formed, unexpired events.
+    pub fn compact(&mut self) -> Result<CompactStats> {
+        self.compact_with(&CompactOptions::default())
+    }
+
+    // Rewrite the on-disk log, dropping malformed lines and whatever `opts` selects.
+    // The same selection is applied to the in-memory events.
+    pub fn compact_with(&mut self, opts: &CompactOptions) -> Result<CompactStats> {
+        self.require_writable("compaction")?;
+        let mut stats = CompactStats::default();
+        let now = now_ms();
+        let expired = |ev: &Event| opts.before_ms.is_some_and(|cutoff| ev.ts_ms < cutoff) || ev.is_expired(now);
+        let filtered = |ev: &Event| opts.min_level.as_ref().is_some_and(|min| ev.level < *min);
+        // Identical events serialize identically, which makes the JSON a usable identity.
+        let identity = |ev: &Event| serde_json::to_string(ev).unwrap_or_default();
+
+        let mut seen = HashSet::new();
+        self.mem.retain(|ev| !expired(ev) && !filtered(ev) && (!opts.dedupe || seen.insert(identity(ev))));
+        self.reindex();
+        if let Some(ref mut store) = self.store {
+            let mut seen = HashSet::new();
+            let mut kept = Vec::new();
+            for ev in store.scan()? {
+                if expired(&ev) {
+                    stats.expired += 1;
+                } else if filtered(&ev) {
+                    stats.filtered += 1;
+                } else if opts.dedupe && !seen.insert(identity(&ev)) {
+                    stats.duplicates += 1;
+                } else {
+                    kept.push(ev);
+                }
+            }
+            stats.kept = kept.len();
+            store.replace(&kept)?;
+            return Ok(stats);
+        }
+        let Some(path) = self.file.clone() else { return Ok(stats) };
+        if !path.exists() { return Ok(stats) }
+        self.require_ndjson("compaction")?;
+        self.close_writer()?;
+
+        let mut seen = HashSet::new();
+        let mut kept = Vec::new();
+        for line in BufReader::new(std::fs::File::open(&path)?).lines() {
+            let line = line?;
+            if line.trim().is_empty() { continue; }
+            match self.decode_line(&line)? {
+                Some(ev) if expired(&ev) => stats.expired += 1,
+                Some(ev) if filtered(&ev) => stats.filtered += 1,
+                Some(ev) if opts.dedupe && !seen.insert(identity(&ev)) => stats.duplicates += 1,
+                Some(_) => kept.push(line),
+                None => stats.malformed += 1,
+            }
+        }
+        stats.kept = kept.len();
+        self.rewrite_file(&path, &kept)?;
+        self.invalidate_summary()?;
+        Ok(stats)
+    }
+
+    // Remove every event matching `filter` from memory, the store or every
+    // segment on disk (archives included, rewritten only if they hold a match),
+    // then record a tombstone saying how many events were erased and which
+    // filter fields selected them; the values are left out so the tombstone
+    // does not keep the identifier being erased. The tombstone is stored even
+    // when nothing matched. Erasure breaks the hash chain at each removed
+    // record; lines that cannot be parsed are kept. Matching lines in
+    // `<file>.quarantine` that still parse are removed too, and the `.keys`
+    // index is dropped so it is rebuilt without the erased events' keys. A
+    // binary-format file is refused before anything changes. Copies outside
+    // the local log are not touched: segments uploaded by an archiver,
+    // tarballs written by `archive_before`, and payloads offloaded to the blob
+    // store must be erased separately.
+    pub fn erase(&mut self, filter: &Filter) -> Result<EraseReport> {
+        if *filter == Filter::default() {
+            return Err("erase needs a non-empty filter".into());
+        }
+        self.require_writable("erasure")?;
+        if self.store.is_none() && self.file.is_some() {
+            self.require_ndjson("erasure")?;
+        }
+        let mut report = EraseReport::default();
+        let in_mem = self.mem.len();
+        self.mem.retain(|ev| !filter.matches(ev));
+        if self.mem.len() != in_mem {
+            self.reindex();
+        }
+        if let Some(ref mut store) = self.store {
+            let mut events = store.scan()?;
+            let before = events.len();
+            events.retain(|ev| !filter.matches(ev));
+            report.erased = before - events.len();
+            if report.erased > 0 {
+                store.replace(&events)?;
+            }
+        } else if let Some(active) = self.file.clone() {
+            self.close_writer()?;
+            let mut segments = self.archives()?;
+            segments.push(active.clone());
+            for seg in segments.into_iter().filter(|seg| seg.exists()) {
+                let mut kept = Vec::new();
+                let mut erased = 0;
+                for line in open_segment(&seg)?.lines() {
+                    let line = line?;
+                    if line.trim().is_empty() { continue; }
+                    if let Some(mut ev) = self.decode_line(&line)? {
+                        self.canonicalize_source(&mut ev);
+                        if filter.matches(&ev) {
+                            erased += 1;
+                            continue;
+                        }
+                    }
+                    kept.push(line);
+                }
+                if erased > 0 {
+                    self.rewrite_file(&seg, &kept)?;
+                    self.invalidate_summary()?;
+                    report.erased += erased;
+                    report.segments_rewritten += 1;
+                }
+            }
+            let quarantine = sibling_path(&active, ".quarantine");
+            if quarantine.exists() {
+                let mut kept = Vec::new();
+                let mut erased = 0;
+                for line in BufReader::new(std::fs::File::open(&quarantine)?).lines() {
+                    let line = line?;
+                    // Quarantined lines often failed their checksum, so it is not checked here.
+                    match serde_json::from_str(&line).map(schema::upgrade) {
+                        Ok(Ok(Some(ev))) if filter.matches(&ev) => erased += 1,
+                        _ => kept.push(line),
+                    }
+                }
+                if erased > 0 {
+                    self.rewrite_file(&quarantine, &kept)?;
+                    report.erased += erased;
+                }
+            }
+            if report.erased > 0
+                && let Some(keys_path) = self.keys_path().filter(|path| path.exists())
+            {
+                std::fs::remove_file(keys_path)?;
+            }
+        } else {
+            report.erased = in_mem - self.mem.len();
+        }
+        if report.erased > 0 {
+            self.idempotency_keys = None;
+        }
+        let tombstone = Event::now("vaultline", Level::Warn, format!("erased {} events", report.erased))
+            .with("tombstone", true)
+            .with("erased", report.erased)
+            .with("fields", erased_fields(filter));
+        let batch = self.prepare(vec![tombstone])?;
+        self.persist(batch)?;
+        Ok(report)
+    }
+
+    // Merge the events of an external NDJSON file (`.gz` is decompressed) into
+    // the vaultline in timestamp order. Records are checksum-verified, upgraded,
+    // normalized and redacted like appends; malformed lines are skipped and
+    // events already present are dropped (see `ImportKey`). Imported events lose
+    // the `seq`, chain link and signature of the log they came from. Only the
+    // active file is rewritten; archives are left alone. Records before the
+    // first imported one keep their stored form; with sequencing or the hash
+    // chain on, the rest are renumbered and relinked (see `renumber`). Memory
+    // then holds the merged active file or store, as after `load_from_disk`.
+    pub fn import(&mut self, path: &Path) -> Result<ImportStats> {
+        self.require_writable("import")?;
+        let mut stats = ImportStats::default();
+        let mut incoming = Vec::new();
+        for line in open_segment(path)?.lines() {
+            let line = line?;
+            if line.trim().is_empty() { continue; }
+            match parse_record(&line, false)? {
+                Some(mut ev) => {
+                    Self::normalize_event(&mut ev);
+                    self.canonicalize_source(&mut ev);
+                    if let Some(ref redactor) = self.redactor {
+                        redactor.redact(&mut ev);
+                    }
+                    ev.seq = 0;
+                    ev.prev_hash = None;
+                    ev.signature = None;
+                    incoming.push(ev);
+                }
+                None => stats.malformed += 1,
+            }
+        }
+
+        let mut seen: HashSet<ImportKey> = self.mem.iter().map(ImportKey::of).collect();
+        if let Some(ref mut store) = self.store {
+            let existing = store.scan()?;
+            seen.extend(existing.iter().map(ImportKey::of));
+            let fresh = Self::fresh_imports(&mut seen, incoming, &mut stats);
+            if fresh.is_empty() { return Ok(stats) }
+            let (_, mut events, first) = merge_imports(existing.into_iter().map(|ev| ((), ev)).collect(), fresh);
+            self.renumber(&mut events, first)?;
+            if let Some(ref mut store) = self.store {
+                store.replace(&events)?;
+            }
+            self.reload_mem(events);
+        } else if let Some(path) = self.file.clone() {
+            self.require_ndjson("import")?;
+            self.close_writer()?;
+            let existing = if path.exists() { self.read_segment(&path)? } else { Vec::new() };
+            seen.extend(existing.iter().map(|(_, ev)| ImportKey::of(ev)));
+            let fresh = Self::fresh_imports(&mut seen, incoming, &mut stats);
+            if fresh.is_empty() { return Ok(stats) }
+            let (lines, mut events, first) = merge_imports(existing, fresh);
+            let renumbered = self.renumber(&mut events, first)?;
+            let lines = lines
+                .into_iter()
+                .zip(&events)
+                .enumerate()
+                .map(|(i, (line, ev))| match line {
+                    Some(line) if !(renumbered && i >= first) => Ok(line),
+                    _ => self.encode_line(ev),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            self.rewrite_file(&path, &lines)?;
+            self.invalidate_summary()?;
+            self.reload_mem(events);
+        } else {
+            let fresh = Self::fresh_imports(&mut seen, incoming, &mut stats);
+            if fresh.is_empty() { return Ok(stats) }
+            let held = self.mem.iter().cloned().map(|ev| ((), ev)).collect();
+            let (_, mut events, first) = merge_imports(held, fresh);
+            self.renumber(&mut events, first)?;
+            self.reload_mem(events);
+        }
+        Ok(stats)
+    }
+
+    // Give `events[first..]` the `seq` and chain links of records stored in
+    // that order, after an import placed new events among them. The run
+    // continues from `events[first - 1]`, or from where the first existing
+    // record in it started (the log's end when there is none). Renumbered
+    // records are re-signed when a signing key is set and otherwise lose a
+    // signature that would no longer match. Returns false when neither
+    // sequencing nor the hash chain is on and nothing was changed.
+    fn renumber(&mut self, events: &mut [Event], first: usize) -> Result<bool> {
+        if !self.sequencing && !self.hash_chain {
+            return Ok(false);
+        }
+        if self.sequencing && !self.seq_resumed {
+            self.resume_seq()?;
+        }
+        if self.hash_chain && self.last_hash.is_none() {
+            self.resume_chain()?;
+        }
+        let (before, tail) = events.split_at_mut(first);
+        let mut seq = match before.last() {
+            Some(prev) => prev.seq + 1,
+            None => tail.iter().map(|ev| ev.seq).find(|&seq| seq > 0).unwrap_or(self.next_seq),
+        };
+        let mut link = match before.last() {
+            Some(prev) => chain::record_hash(prev)?,
+            None => tail.iter().find_map(|ev| ev.prev_hash.clone()).or_else(|| self.last_hash.clone()).unwrap_or_else(|| chain::GENESIS.to_string()),
+        };
+        for ev in tail.iter_mut() {
+            if self.sequencing {
+                ev.seq = seq;
+                seq += 1;
+            }
+            if self.hash_chain {
+                ev.prev_hash = Some(std::mem::take(&mut link));
+            }
+            ev.signature = None;
+            #[cfg(feature = "signing")]
+            if let Some(ref signer) = self.signer {
+                signer.sign(ev)?;
+            }
+            if self.hash_chain {
+                link = chain::record_hash(ev)?;
+            }
+        }
+        if self.hash_chain {
+            self.last_hash = Some(link);
+        }
+        Ok(true)
+    }
+
+    // Replace the in-memory buffer with `events`.
+    fn reload_mem(&mut self, events: Vec<Event>) {
+        self.mem.retain(|_| false);
+        for ev in events {
+            self.push_mem(ev);
+        }
+        self.trim_to_cap();
+        self.reindex();
+    }
+
+    // Write every persisted event (local archives and the active file, or the
+    // store) to a new self-contained snapshot file at `path`; in-memory
+    // vaultlines snapshot what they hold. Records are stored decrypted.
+    pub fn snapshot(&mut self, path: &Path) -> Result<SnapshotMeta> {
+        self.flush_writes()?;
+        let (events, segments) = if let Some(ref mut store) = self.store {
+            (store.scan()?, 1)
+        } else if let Some(active) = self.file.clone() {
+            let mut segments = self.archives()?;
+            segments.push(active);
+            segments.retain(|seg| seg.exists());
+            let mut events = Vec::new();
+            for seg in &segments {
+                events.extend(self.segment_events(seg)?);
+            }
+            (events, segments.len())
+        } else {
+            (self.mem.to_vec(), 0)
+        };
+        let created_ms = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+        snapshot::write(path, &events, segments, created_ms)
+    }
+
+    // Rebuild an empty vaultline from a snapshot. Events are verified against the
+    // snapshot's checksums, then written to the active file (in this vaultline's
+    // format and encryption) or store, and held in memory.
+    pub fn restore(&mut self, path: &Path) -> Result<SnapshotMeta> {
+        self.require_writable("restore")?;
+        let (meta, events) = snapshot::read(path)?;
+        if !self.mem.is_empty() || !self.archives()?.is_empty() || self.iter_disk()?.next().is_some() {
+            return Err("refusing to restore into a vaultline that already holds events".into());
+        }
+        if let Some(ref mut store) = self.store {
+            store.append_batch(&events)?;
+        } else if self.file.is_some() {
+            let buf = self.encode_batch(&events)?;
+            self.writer()?.append_encoded(&buf)?;
+            self.sync()?;
+            self.invalidate_summary()?;
+        }
+        for event in events {
+            self.push_mem(event);
+        }
+        self.trim_to_cap();
+        Ok(meta)
+    }
+
+    // Drop already-seen events from `incoming`, sort the rest by timestamp and
+    // return a copy of them.
+    fn fresh_imports(seen: &mut HashSet<ImportKey>, mut incoming: Vec<Event>, stats: &mut ImportStats) -> Vec<Event> {
+        let before = incoming.len();
+        incoming.retain(|ev| seen.insert(ImportKey::of(ev)));
+        incoming.sort_by_key(|ev| ev.ts_ms);
+        stats.duplicates = before - incoming.len();
+        stats.imported = incoming.len();
+        incoming
+    }
+
+    // Drop appends below `level` from every source (None keeps all). Dropped
+    // events are counted in `dropped_counts`.
+    pub fn set_min_level(&mut self, level: Option<Level>) {
+        self.min_level = level;
+    }
+
+    // Drop appends from `source` whose level is below `level`.
+    pub fn set_source_min_level<S: Into<String>, L: Into<Level>>(&mut self, source: S, level: L) {
+        self.source_min_levels.insert(source.into(), level.into());
+    }
+
+    // Reject appends from `source` whose kv object lacks any of `keys`.
+    pub fn require_kv<S: Into<String>>(&mut self, source: S, keys: Vec<String>) {
+        self.required_kv.insert(source.into(), keys);
+    }
+
+    fn check_required_kv(&self, event: &Event) -> Result<()> {
+        let Some(keys) = self.required_kv.get(&event.source) else { return Ok(()) };
+        for key in keys {
+            if event.kv.get(key).is_none() {
+                return Err(format!("event from {:?} is missing required kv key {key:?}", event.source).into());
+            }
+        }
+        Ok(())
+    }
+
+    // Mask sensitive kv values and message text on append, before anything is
+    // stored, indexed or sent to sinks. Events already on disk are untouched.
+    pub fn set_redactor(&mut self, redactor: Option<Redactor>) {
+        self.redactor = redactor;
+    }
+
+    // Where attachments live (see `BlobStore`).
+    pub fn set_blob_store(&mut self, store: Option<BlobStore>) {
+        self.blob_store = store;
+    }
+
+    pub fn blob_store(&self) -> Option<&BlobStore> {
+        self.blob_store.as_ref()
+    }
+
+    // On append, move top-level kv strings of at least `min_bytes` into the
+    // blob store and keep only a reference in the log. Runs after redaction, so
+    // masked values never reach a blob. Needs `set_blob_store`. Blobs are stored
+    // in plaintext, so nothing is offloaded while an encryption key is set.
+    pub fn set_blob_offload(&mut self, min_bytes: Option<usize>) {
+        self.blob_min_bytes = min_bytes;
+    }
+
+    // Full bytes of an attachment referenced by `event.kv[key]`; None when the
+    // value is not a blob reference.
+    pub fn attachment(&self, event: &Event, key: &str) -> Result<Option<Vec<u8>>> {
+        match self.blob_store {
+            Some(ref store) => store.resolve(event, key),
+            None if event.kv.get(key).and_then(blob_hash).is_some() => Err("no blob store configured".into()),
+            None => Ok(None),
+        }
+    }
+
+    // Rewrite old source names to canonical ones (key -> value) on append and load.
+    pub fn set_source_aliases(&mut self, map: HashMap<String, String>) {
+        self.source_aliases = map;
+    }
+
+    fn canonicalize_source(&self, event: &mut Event) {
+        if let Some(canonical) = self.source_aliases.get(&event.source) {
+            event.source = canonical.clone();
+        }
+    }
+
+    // Leave the `kv` field out of written lines when it is null (it loads back as null).
+    pub fn set_omit_null_kv(&mut self, omit: bool) {
+        self.omit_null_kv = omit;
+    }
+
+    // Serialize an event to its NDJSON line, sealed when a key is set.
+    fn encode_line(&self, event: &Event) -> Result<String> {
+        let json = if self.omit_null_kv && event.kv.is_null() {
+            let mut value = serde_json::to_value(event)?;
+            if let Some(obj) = value.as_object_mut() {
+                obj.remove("kv");
+            }
+            serde_json::to_string(&value)?
+        } else {
+            serde_json::to_string(event)?
+        };
+        let json = if self.checksums { with_crc(&json) } else { json };
+        #[cfg(feature = "encryption")]
+        if let Some(ref cipher) = self.cipher {
+            return cipher.seal(&json);
+        }
+        Ok(json)
+    }
+
+    // Parse a stored line. Ok(None) means malformed JSON or a checksum mismatch;
+    // encrypted lines that cannot be opened are an error so they are never
+    // mistaken for garbage.
+    fn decode_line(&self, line: &str) -> Result<Option<Event>> {
+        parse_record(&self.unseal(line)?, self.checksums)
+    }
+
+    // Whether new lines are sealed with an encryption key.
+    fn encrypting(&self) -> bool {
+        #[cfg(feature = "encryption")]
+        return self.cipher.is_some();
+        #[cfg(not(feature = "encryption"))]
+        false
+    }
+
+    // Plaintext of a stored line, decrypting sealed ones.
+    fn unseal<'a>(&self, line: &'a str) -> Result<std::borrow::Cow<'a, str>> {
+        if line.starts_with("enc:") {
+            #[cfg(feature = "encryption")]
+            if let Some(ref cipher) = self.cipher {
+                return Ok(std::borrow::Cow::Owned(cipher.open(line)?));
+            }
+            return Err("vaultline holds encrypted records but no key is set".into());
+        }
+        Ok(std::borrow::Cow::Borrowed(line))
+    }
+
+    // Encrypt every record written from now on with AES-256-GCM.
+    #[cfg(feature = "encryption")]
+    pub fn set_encryption_key(&mut self, key: &[u8; 32]) {
+        self.cipher = Some(cipher::LineCipher::new(key));
+    }
+
+    // Re-encrypt the active file and all archives under `new_key`, then switch
+    // to it. Plaintext records are encrypted too. Returns records rewritten.
+    #[cfg(feature = "encryption")]
+    pub fn rotate_encryption_key(&mut self, new_key: &[u8; 32]) -> Result<usize> {
+        self.require_ndjson("key rotation")?;
+        self.close_writer()?;
+        let next = cipher::LineCipher::new(new_key);
+        let mut rewritten = 0;
+        if let Some(active) = self.file.clone() {
+            let mut segments = self.archives()?;
+            segments.push(active);
+            for seg in segments.into_iter().filter(|seg| seg.exists()) {
+                let mut lines = Vec::new();
+                for line in open_segment(&seg)?.lines() {
+                    let line = line?;
+                    if line.trim().is_empty() { continue; }
+                    let plain = if line.starts_with(cipher::PREFIX) {
+                        self.cipher.as_ref().ok_or("vaultline holds encrypted records but no key is set")?.open(&line)?
+                    } else {
+                        line
+                    };
+                    lines.push(next.seal(&plain)?);
+                }
+                rewritten += lines.len();
+                self.rewrite_file(&seg, &lines)?;
+            }
+        }
+        self.cipher = Some(next);
+        Ok(rewritten)
+    }
+
+    // Keep only the first of every `every` events at `level` (1 keeps all).
+    pub fn set_level_sampling<L: Into<Level>>(&mut self, level: L, every: u64) {
+        self.level_sampling.insert(level.into(), (every.max(1), 0));
+    }
+
+    // Collapse repeats of the same (source, level, message) arriving within
+    // `window` of the first into that one record, with `count` and `last_ts_ms`
+    // kv fields. Records are held back until their window has passed, measured
+    // by the timestamps of later appends; `flush_rollups` stores them early.
+    // With None, open records are released on the next append.
+    pub fn set_rollup_window(&mut self, window: Option<Duration>) {
+        self.rollup_window = window.map(|w| w.as_millis());
+    }
+
+    // Store every record still held by an open rollup window. Returns how many.
+    pub fn flush_rollups(&mut self) -> Result<usize> {
+        let open = self.rollups.drain();
+        if open.is_empty() { return Ok(0) }
+        let batch = self.prepare(open)?;
+        self.persist(batch)
+    }
+
+    // Cap appends per source (see `RateLimit`); None removes the cap.
+    pub fn set_rate_limit(&mut self, limit: Option<RateLimit>) {
+        self.rate_limiter = limit.map(rate_limit::RateLimiter::new);
+    }
+
+    // Per-source count of events dropped by level filters, sampling and rate limits.
+    pub fn dropped_counts(&self) -> HashMap<String, u64> {
+        self.dropped.clone()
+    }
+
+    // Advance the sampler for the event's level; false when this one is sampled out.
+    fn sample(&mut self, event: &Event) -> bool {
+        let Some((every, seen)) = self.level_sampling.get_mut(&event.level) else { return true };
+        let keep = *seen % *every == 0;
+        *seen += 1;
+        keep
+    }
+
+    // Whether an event passes the configured level thresholds.
+    fn passes_level_filter(&self, event: &Event) -> bool {
+        // Levels outside the severity order are never filtered.
+        let passes = |min: &Level| event.level.partial_cmp(min).is_none_or(|order| order.is_ge());
+        self.min_level.as_ref().is_none_or(passes) && self.source_min_levels.get(&event.source).is_none_or(passes)
+    }
+
+    // Append a new event to the vaultline.
+    pub fn append(&mut self, event: Event) -> Result<()> {
+        self.append_batch(vec![event]).map(|_| ())
+    }
+
+    // Append several events with a single write, applying the flush/durability
+    // policy and the rotation check once for the whole batch. Every event is
+    // checked before any is stored, so a rejected one (missing kv, hard limit)
+    // leaves the batch unwritten. Returns how many events were stored.
+    pub fn append_batch(&mut self, events: Vec<Event>) -> Result<usize> {
+        let batch = self.admit(events)?;
+        self.persist(batch)
+    }
+
+    // Write, store and fan out a batch that went through `admit`.
+    fn persist(&mut self, batch: Vec<Event>) -> Result<usize> {
+        if batch.is_empty() { return Ok(0) }
+
+        // If file-backed, append the records using the original events (do not normalize
+        // here so that stored events match what the caller provided).
+        if self.file.is_some() {
+            self.rotate_if_new_day()?;
+            let buf = self.encode_batch(&batch)?;
+            match self.lock_timeout {
+                Some(timeout) => self.write_locked(&buf, timeout)?,
+                None => self.writer()?.append_encoded(&buf)?,
+            }
+        }
+        if let Some(ref mut store) = self.store {
+            store.append_batch(&batch)?;
+        }
+        self.commit(&batch)?;
+
+        if self.file.is_some() {
+            self.unflushed = self.unflushed.saturating_add(batch.len() as u32);
+            if self.rotate_daily {
+                self.active_day = Some(now_ms() / DAY_MS);
+            }
+            let policy = self.flush_policy;
+            if (policy.every_n > 0 && self.unflushed >= policy.every_n)
+                || policy.interval.is_some_and(|every| self.last_flush.elapsed() >= every)
+            {
+                self.flush_writes()?;
+            }
+
+            if self.sync_due() {
+                self.sync()?;
+            }
+            self.rotate_if_due()?;
+        }
+        self.record_idempotency_keys(&batch)?;
+        self.record_summary(&batch)?;
+        self.fan_out(&batch);
+        Ok(batch.len())
+    }
+
+    // Hand a persisted batch to the sinks and subscribers. The batch is already
+    // stored, so a failing sink is logged rather than failing the append (a
+    // retry would store it twice); every other sink still gets the batch.
+    fn fan_out(&mut self, batch: &[Event]) {
+        for sink in &mut self.sinks {
+            if let Err(e) = sink.write(batch) {
+                tracing::warn!(error = %e, events = batch.len(), "sink rejected a stored batch");
+            }
+        }
+        for event in batch {
+            self.notify(event);
+        }
+    }
+
+    // Run `hook` on every appended event, after source aliasing and before
+    // redaction and filtering. Hooks run in the order added; returning None
+    // drops the event (counted in `dropped_counts`).
+    pub fn add_hook<F: FnMut(Event) -> Option<Event> + Send + 'static>(&mut self, hook: F) {
+        self.hooks.push(Box::new(hook));
+    }
+
+    // Also send every stored batch to `sink`, after the file or store has it.
+    // Sinks are best-effort mirrors: a failing sink is logged and skipped, the
+    // append still succeeds. The file, store and in-memory buffer are the
+    // vault's own storage, not sinks, and their errors do fail the append.
+    pub fn add_sink<S: EventSink + 'static>(&mut self, sink: S) {
+        self.sinks.push(Box::new(sink));
+    }
+
+    // Sidecar index of stored idempotency keys, one JSON string per line.
+    fn keys_path(&self) -> Option<PathBuf> {
+        self.file.as_ref().map(|path| sibling_path(path, ".keys"))
+    }
+
+    // Load the keys index, or rebuild it from every stored event (archives
+    // included) when it is missing, e.g. for logs written before keys existed.
+    // A read-only vaultline keeps the rebuilt index in memory only.
+    fn load_idempotency_keys(&mut self) -> Result<()> {
+        let mut seen: HashSet<String> = self.mem.iter().filter_map(|ev| ev.idempotency_key.clone()).collect();
+        if let Some(ref mut store) = self.store {
+            seen.extend(store.scan()?.into_iter().filter_map(|ev| ev.idempotency_key));
+        } else if let (Some(active), Some(keys_path)) = (self.file.clone(), self.keys_path()) {
+            if keys_path.exists() {
+                for line in BufReader::new(std::fs::File::open(&keys_path)?).lines() {
+                    let line = line?;
+                    if let Ok(key) = serde_json::from_str(&line) {
+                        seen.insert(key);
+                    }
+                }
+            } else {
+                self.flush_writes()?;
+                let mut segments = self.archives()?;
+                segments.push(active);
+                let mut found = Vec::new();
+                for seg in segments.iter().filter(|seg| seg.exists()) {
+                    found.extend(self.segment_events(seg)?.into_iter().filter_map(|ev| ev.idempotency_key));
+                }
+                if !self.read_only {
+                    let mut index = std::fs::File::create(&keys_path)?;
+                    for key in &found {
+                        writeln!(index, "{}", serde_json::to_string(key)?)?;
+                    }
+                }
+                seen.extend(found);
+            }
+        }
+        self.idempotency_keys = Some(seen);
+        Ok(())
+    }
+
+    // Append the keys of a stored batch to the sidecar index. The batch's
+    // records are flushed first, so the index never names a record that a
+    // crash could still lose from the write buffer.
+    fn record_idempotency_keys(&mut self, batch: &[Event]) -> Result<()> {
+        let Some(keys_path) = self.keys_path() else { return Ok(()) };
+        let mut keys = batch.iter().filter_map(|ev| ev.idempotency_key.as_deref()).peekable();
+        if keys.peek().is_none() { return Ok(()) }
+        if let Some(ref mut active) = self.active {
+            active.flush()?;
+        }
+        let mut index = BufWriter::new(OpenOptions::new().create(true).append(true).open(keys_path)?);
+        for key in keys {
+            writeln!(index, "{}", serde_json::to_string(key)?)?;
+        }
+        index.flush()?;
+        Ok(())
+    }
+
+    // Run the append pipeline up to (not including) persistence: aliases,
+    // hooks, redaction, level filters, sampling and rate limits, kv and hard-limit
+    // checks and rollups, then sequence numbers, chain links and signatures
+    // (see `prepare`). Returns the events to persist.
+    fn admit(&mut self, events: Vec<Event>) -> Result<Vec<Event>> {
+        self.require_writable("appending")?;
+        #[cfg(feature = "encryption")]
+        if self.cipher.is_some() && self.format == Format::Binary {
+            return Err("encryption is not supported with the binary format".into());
+        }
+        if self.idempotency_keys.is_none() && events.iter().any(|ev| ev.idempotency_key.is_some()) {
+            self.load_idempotency_keys()?;
+        }
+        let mut batch = Vec::with_capacity(events.len());
+        let mut batch_keys = HashSet::new();
+        'events: for mut event in events {
+            if let Some(ref key) = event.idempotency_key
+                && (self.idempotency_keys.as_ref().is_some_and(|seen| seen.contains(key)) || !batch_keys.insert(key.clone()))
+            {
+                continue;
+            }
+            self.canonicalize_source(&mut event);
+            for hook in &mut self.hooks {
+                let source = event.source.clone();
+                match hook(event) {
+                    Some(next) => event = next,
+                    None => {
+                        *self.dropped.entry(source).or_default() += 1;
+                        continue 'events;
+                    }
+                }
+            }
+            if let Some(ref redactor) = self.redactor {
+                redactor.redact(&mut event);
+            }
+            if !self.passes_level_filter(&event) || !self.sample(&event) {
+                *self.dropped.entry(event.source.clone()).or_default() += 1;
+                continue;
+            }
+            if let Some(ref mut limiter) = self.rate_limiter {
+                match limiter.check(&event) {
+                    rate_limit::Verdict::Keep => {}
+                    rate_limit::Verdict::KeepAfter(marker) => batch.push(*marker),
+                    rate_limit::Verdict::Drop => {
+                        *self.dropped.entry(event.source.clone()).or_default() += 1;
+                        continue;
+                    }
+                }
+            }
+            self.check_required_kv(&event)?;
+            batch.push(event);
+        }
+        if batch.is_empty() { return Ok(batch) }
+        if let Some(max) = self.hard_limit
+            && self.mem.len() + batch.len() > max
+        {
+            return Err(format!("vaultline full: hard limit of {max} events reached").into());
+        }
+        // Only events that will be stored reach the blob store, so rejected
+        // appends leave no orphan blobs behind.
+        if let (Some(store), Some(min)) = (&self.blob_store, self.blob_min_bytes)
+            && !self.encrypting()
+        {
+            for event in &mut batch {
+                store.offload(event, min)?;
+            }
+        }
+        if self.rollup_window.is_some() || !self.rollups.is_empty() {
+            batch = self.rollups.absorb(batch, self.rollup_window);
+            if batch.is_empty() { return Ok(batch) }
+        }
+        self.prepare(batch)
+    }
+
+    // Number, chain and sign events that passed `admit`. The vaultline itself
+    // is left as it was: `commit` advances it once the batch is stored, so a
+    // failed write leaves no gap in `seq`, no dangling chain link and no
+    // idempotency key marked as seen.
+    fn prepare(&mut self, mut batch: Vec<Event>) -> Result<Vec<Event>> {
+        if self.sequencing && !self.seq_resumed {
+            self.resume_seq()?;
+        }
+        if self.hash_chain && self.last_hash.is_none() {
+            self.resume_chain()?;
+        }
+        let mut next_seq = self.next_seq;
+        let mut last_hash = self.last_hash.clone();
+        for event in &mut batch {
+            if self.sequencing {
+                event.seq = next_seq;
+                next_seq += 1;
+            }
+            if let Some(ref mut last) = last_hash {
+                event.prev_hash = Some(std::mem::take(last));
+            }
+            #[cfg(feature = "signing")]
+            if let Some(ref signer) = self.signer {
+                signer.sign(event)?;
+            }
+            if let Some(ref mut last) = last_hash {
+                *last = chain::record_hash(event)?;
+            }
+        }
+        Ok(batch)
+    }
+
+    // Take a stored batch into the vaultline: chain position, seen idempotency
+    // keys, metrics and the in-memory buffer (`push_mem` moves `next_seq`).
+    fn commit(&mut self, batch: &[Event]) -> Result<()> {
+        if let (Some(last), Some(event)) = (self.last_hash.as_mut(), batch.last()) {
+            *last = chain::record_hash(event)?;
+        }
+        if let Some(ref mut seen) = self.idempotency_keys {
+            seen.extend(batch.iter().filter_map(|ev| ev.idempotency_key.clone()));
+        }
+        for event in batch {
+            if let Some(metrics) = self.metrics.as_mut() {
+                metrics.entry(event.level.to_string()).or_default().fetch_add(1, Ordering::Relaxed);
+            }
+            // Keep an in-memory copy of the original event (do not mutate the caller's event)
+            self.push_mem(event.clone());
+        }
+        self.trim_to_cap();
+        Ok(())
+    }
+
+    // Records for `batch` in the on-disk format, ready for a single write.
+    fn encode_batch(&self, batch: &[Event]) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        for event in batch {
+            match self.format {
+                Format::Ndjson => {
+                    buf.extend_from_slice(self.encode_line(event)?.as_bytes());
+                    buf.push(b'\n');
+                }
+                Format::Binary => binary::write_record(&mut buf, event)?,
+            }
+        }
+        Ok(buf)
+    }
+
+    // Number every stored event with a monotonically increasing `seq`, continuing
+    // after the highest one already persisted.
+    pub fn set_sequencing(&mut self, on: bool) {
+        self.sequencing = on;
+    }
+
+    // Move `next_seq` past the newest sequenced record on disk or in the store.
+    fn resume_seq(&mut self) -> Result<()> {
+        self.flush_writes()?;
+        let mut last = 0;
+        if let Some(ref mut store) = self.store {
+            last = store.scan()?.iter().map(|ev| ev.seq).max().unwrap_or(0);
+        } else if let Some(ref active) = self.file {
+            let mut segments = self.archives()?;
+            segments.push(active.clone());
+            for seg in segments.iter().rev().filter(|seg| seg.exists()) {
+                last = self.segment_events(seg)?.iter().map(|ev| ev.seq).max().unwrap_or(0);
+                if last > 0 { break; }
+            }
+        }
+        self.next_seq = self.next_seq.max(last + 1);
+        self.seq_resumed = true;
+        Ok(())
+    }
+
+    // Give every stored record a `prev_hash` linking it to the record before it,
+    // continuing from the newest record already persisted. `verify` then detects
+    // records modified or deleted in the middle of the log. Compaction and
+    // retention drop records and so break the chain where they cut.
+    pub fn set_hash_chain(&mut self, on: bool) {
+        self.hash_chain = on;
+        if !on {
+            self.last_hash = None;
+        }
+    }
+
+    // Start the chain after the newest persisted record (or from the genesis hash).
+    fn resume_chain(&mut self) -> Result<()> {
+        let mut last = None;
+        if self.is_persistent() {
+            self.scan_all(|ev| {
+                last = Some(ev.clone());
+                true
+            })?;
+        } else {
+            last = self.mem.last().cloned();
+        }
+        self.last_hash = Some(match last {
+            Some(ev) => chain::record_hash(&ev)?,
+            None => chain::GENESIS.to_string(),
+        });
+        Ok(())
+    }
+
+    // Check every persisted record, oldest segment first: each line is a
+    // well-formed record whose CRC (if any) matches, timestamps never go
+    // backwards, `seq` has no gaps and each `prev_hash` matches the record
+    // before it. The first record's links cannot be checked once older
+    // segments are gone, and deleting records from the very end of the log
+    // leaves no trace. Store-backed and in-memory vaultlines check records only.
+    pub fn verify(&mut self) -> Result<VerifyReport> {
+        let mut verifier = verify::Verifier::default();
+        let Some(active) = self.file.clone().filter(|_| self.store.is_none()) else {
+            verifier.segment();
+            let at = if self.store.is_some() { "store" } else { "memory" };
+            let mut err = None;
+            let mut n = 0;
+            self.scan_all(|ev| {
+                n += 1;
+                err = verifier.record(ev, at, n).err();
+                err.is_none()
+            })?;
+            return err.map_or(Ok(verifier.report), Err);
+        };
+        self.flush_writes()?;
+        let mut segments = self.archives()?;
+        segments.push(active);
+        for seg in segments.iter().filter(|seg| seg.exists()) {
+            verifier.segment();
+            let at = seg.file_name().unwrap_or_default().to_string_lossy().into_owned();
+            if self.format == Format::Binary {
+                // Frames failing their CRC are skipped by the reader and cannot be counted.
+                for (i, ev) in binary::read_records(seg)?.0.iter().enumerate() {
+                    verifier.record(ev, &at, i + 1)?;
+                }
+                continue;
+            }
+            for (i, line) in open_segment(seg)?.lines().enumerate() {
+                let line = line?;
+                if line.is_empty() { continue; }
+                let plain = match self.unseal(&line) {
+                    Ok(plain) => plain,
+                    Err(e) => {
+                        verifier.malformed(&at, i + 1, e.to_string());
+                        continue;
+                    }
+                };
+                if has_crc(&plain) {
+                    verifier.checksum(crc_ok(&plain, false), &at, i + 1);
+                }
+                if !crc_ok(&plain, false) { continue; }
+                match parse_record(&plain, false)? {
+                    // A readable record the log should have checksummed.
+                    Some(_) if self.checksums && !has_crc(&plain) => verifier.missing_checksum(&at, i + 1),
+                    Some(ev) => verifier.record(&ev, &at, i + 1)?,
+                    None => {
+                        let reason = match serde_json::from_str::<serde_json::Value>(&plain) {
+                            Err(e) => e.to_string(),
+                            Ok(_) => "not a valid event record".to_string(),
+                        };
+                        verifier.malformed(&at, i + 1, reason);
+                    }
+                }
+            }
+        }
+        Ok(verifier.report)
+    }
+
+    // Sign every event appended from now on with this ed25519 seed. Signing
+    // happens after sequencing and chaining, so `seq` and `prev_hash` are covered.
+    #[cfg(feature = "signing")]
+    pub fn set_signing_key(&mut self, seed: &[u8; 32]) {
+        self.signer = Some(signing::EventSigner::new(seed));
+    }
+
+    // Check the signature of every persisted record against `public_key`.
+    #[cfg(feature = "signing")]
+    pub fn verify_signatures(&mut self, public_key: &[u8; 32]) -> Result<SignatureReport> {
+        let mut report = SignatureReport::default();
+        let mut err = None;
+        self.scan_all(|ev| {
+            if ev.signature.is_some() {
+                report.signed += 1;
+                match signing::verify_event(ev, public_key) {
+                    Ok(true) => {}
+                    Ok(false) => report.invalid.push(report.records),
+                    Err(e) => err = Some(e),
+                }
+            }
+            report.records += 1;
+            err.is_none()
+        })?;
+        match err {
+            Some(e) => Err(e),
+            None => Ok(report),
+        }
+    }
+
+    // In-memory events numbered after `seq`, oldest first.
+    pub fn since(&self, seq: u64) -> &[Event] {
+        let start = self.mem.partition_point(|ev| ev.seq <= seq);
+        &self.mem[start..]
+    }
+
+    // The active file's store, opened once.
+    fn writer(&mut self) -> Result<&mut FileStore> {
+        if self.active.is_none() {
+            self.require_writable("appending")?;
+            let Some(ref path) = self.file else { return Err("vaultline has no backing file".into()) };
+            self.active = Some(FileStore::open(path)?);
+        }
+        Ok(self.active.as_mut().expect("store was just opened"))
+    }
+
+    // Write buffered appends through to the file, and have every sink push
+    // what it is batching (a failing sink is logged). Appends only do the
+    // former (see `flush_writes`), so sinks keep batching between flushes.
+    pub fn flush(&mut self) -> Result<()> {
+        self.flush_writes()?;
+        for sink in &mut self.sinks {
+            if let Err(e) = sink.flush() {
+                tracing::warn!(error = %e, "sink failed to flush");
+            }
+        }
+        Ok(())
+    }
+
+    // Write buffered appends through to the file, leaving sinks alone.
+    pub fn flush_writes(&mut self) -> Result<()> {
+        if let Some(ref mut active) = self.active {
+            active.flush()?;
+        }
+        self.unflushed = 0;
+        self.last_flush = Instant::now();
+        Ok(())
+    }
+
+    // Hold an exclusive advisory lock (flock / LockFileEx) on the active file while
+    // each batch is written, so other processes appending to the same file (e.g.
+    // `halodeck` next to the daemon) never interleave partial records. Batches then
+    // bypass the write buffer. Waits up to `timeout` for the lock before failing
+    // with `VaultlineError::LockTimeout`; `None` turns locking off.
+    pub fn set_append_lock(&mut self, timeout: Option<Duration>) {
+        self.lock_timeout = timeout;
+    }
+
+    fn write_locked(&mut self, buf: &[u8], timeout: Duration) -> Result<()> {
+        let path = self.file.clone().ok_or("vaultline has no backing file")?;
+        // Anything buffered before locking was turned on goes out first.
+        let file = self.writer()?.file_mut()?;
+        let deadline = Instant::now() + timeout;
+        loop {
+            match file.try_lock() {
+                Ok(()) => break,
+                Err(std::fs::TryLockError::WouldBlock) if Instant::now() < deadline => {
+                    std::thread::sleep(Duration::from_millis(5));
+                }
+                Err(std::fs::TryLockError::WouldBlock) => {
+                    return Err(VaultlineError::LockTimeout { path, waited: timeout }.into());
+                }
+                Err(std::fs::TryLockError::Error(e)) => return Err(e.into()),
+            }
+        }
+        let written = file.write_all(buf);
+        file.unlock()?;
+        Ok(written?)
+    }
+
+    // Whether the durability policy calls for an fsync after this append.
+    fn sync_due(&self) -> bool {
+        match self.durability {
+            DurabilityPolicy::FsyncEveryWrite => true,
+            DurabilityPolicy::FsyncInterval(every) => self.last_sync.elapsed() >= every,
+            DurabilityPolicy::None | DurabilityPolicy::FlushEveryN(_) => false,
+        }
+    }
+
+    // Flush buffered appends and fsync the active file.
+    pub fn sync(&mut self) -> Result<()> {
+        self.flush_writes()?;
+        if let Some(ref mut active) = self.active {
+            active.sync()?;
+        }
+        self.last_sync = Instant::now();
+        Ok(())
+    }
+
+    pub fn set_durability(&mut self, policy: DurabilityPolicy) {
+        self.durability = policy;
+        self.flush_policy = match policy {
+            DurabilityPolicy::None => FlushPolicy { every_n: 0, interval: None },
+            DurabilityPolicy::FlushEveryN(n) => FlushPolicy { every_n: n, interval: None },
+            DurabilityPolicy::FsyncEveryWrite | DurabilityPolicy::FsyncInterval(_) => self.flush_policy,
+        };
+    }
+
+    // Control how often buffered appends are flushed (default: every append).
+    pub fn set_flush_policy(&mut self, policy: FlushPolicy) {
+        self.flush_policy = policy;
+    }
+
+    // Flush and drop the append handle before the active file is rewritten or
+    // renamed; the next append reopens it.
+    fn close_writer(&mut self) -> Result<()> {
+        self.flush_writes()?;
+        if let Some(ref mut active) = self.active {
+            active.close()?;
+        }
+        Ok(())
+    }
+
+    // Store open rollups, flush sinks, then fsync and close the active file.
+    // The next append reopens it.
+    pub fn close(&mut self) -> Result<()> {
+        self.flush_rollups()?;
+        self.flush()?;
+        self.sync()?;
+        self.close_writer()
+    }
+
+    // Events accepted but not yet handed to the OS: unflushed appends plus
+    // records held in open rollup windows.
+    pub fn backlog(&self) -> usize {
+        self.unflushed as usize + self.rollups.len()
+    }
+
+    // Rotate once the active file has reached `rotate_bytes`.
+    fn rotate_if_due(&mut self) -> Result<()> {
+        self.rotate_if_new_day()?;
+        if let (Some(max), Some(active)) = (self.rotate_bytes, self.active.as_ref())
+            && active.size()? >= max
+        {
+            self.rotate()?;
+        }
+        Ok(())
+    }
+
+    // Also rotate the active file whenever the UTC date changes, into
+    // `<stem>-YYYY-MM-DD.ndjson` next to it (gzipped with the `compression`
+    // feature) named after the day it was written. Checked before each append
+    // and by `VaultlineService`'s tick, so a quiet log still rolls over.
+    pub fn set_rotate_daily(&mut self, on: bool) {
+        self.rotate_daily = on;
+    }
+
+    fn rotate_if_new_day(&mut self) -> Result<()> {
+        if !self.rotate_daily || self.read_only { return Ok(()) }
+        let Some(path) = self.file.clone() else { return Ok(()) };
+        let today = now_ms() / DAY_MS;
+        let day = match self.active_day {
+            Some(day) => day,
+            None => {
+                // Nothing written by this process yet: the file's last write says which day it holds.
+                let Ok(meta) = std::fs::metadata(&path) else { return Ok(()) };
+                if meta.len() == 0 { return Ok(()) }
+                let day = meta.modified()?.duration_since(UNIX_EPOCH)?.as_millis() / DAY_MS;
+                *self.active_day.insert(day)
+            }
+        };
+        if day < today {
+            self.rotate_into(Some(day))?;
+        }
+        Ok(())
+    }
+
+    // Receive a copy of every event stored from now on. Dropping the receiver unsubscribes.
+    pub fn subscribe(&mut self) -> Receiver<Event> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.push(tx);
+        rx
+    }
+
+    fn notify(&mut self, event: &Event) {
+        self.subscribers.retain(|tx| tx.send(event.clone()).is_ok());
+    }
+
+    // Retrieve the last n events in memory.
+    pub fn tail(&self, n: usize) -> Vec<&Event> {
+        let now = now_ms();
+        let mut tail: Vec<&Event> = self.mem.iter().rev().filter(|ev| !ev.is_expired(now)).take(n).collect();
+        tail.reverse();
+        tail
+    }
+
+    // Create an in-memory only vaultline (no file).
+    pub fn new_in_memory() -> Self {
+        Self::with_file(None)
+    }
+
+    // A vaultline persisting to another store (e.g. `SqliteStore`) in place of
+    // the `FileStore` that `new` writes through. Compaction goes through
+    // `EventStore::replace`; file-only features (rotation, archives, retention
+    // on disk) do not apply.
+    pub fn with_store<S: EventStore + 'static>(store: S) -> Self {
+        let mut vault = Self::with_file(None);
+        vault.store = Some(Box::new(store));
+        vault
+    }
+
+    // The store events are persisted to: the one given to `with_store`, or else
+    // the active file's `FileStore`. None for in-memory and read-only vaultlines.
+    // The active file's records are only readable through it while they are
+    // plain NDJSON (no encryption or binary format).
+    pub fn event_store(&mut self) -> Option<&mut dyn EventStore> {
+        if let Some(ref mut store) = self.store {
+            return Some(store.as_mut());
+        }
+        self.active.as_mut().map(|active| active as &mut dyn EventStore)
+    }
+
+    pub fn normalize_event(ev: &mut Event) {
+        if ev.level.as_str().is_empty() {
+            ev.level = Level::Info;
+        }
+        if ev.source.is_empty() {
+            ev.source = "unknown".to_string();
+        }
+        if ev.message.is_empty() {
+            ev.message = "(no message)".to_string();
+        }
+
+        if ev.ts_ms == 0 {
+            ev.ts_ms = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_millis();
+        }  
+
+        if ev.kv.is_null() {
+            ev.kv = serde_json::Value::Object(serde_json::Map::new());
+        }
+    }
+
+    // Keep only the `k` most recent archives; older ones are deleted after each rotation.
+    pub fn set_max_archives(&mut self, k: usize) {
+        self.max_archives = Some(k);
+    }
+
+    // Archived segments (`<file>.<ts_ms>`) of the backing file, oldest first.
+    pub fn archives(&self) -> Result<Vec<PathBuf>> {
+        let Some(ref path) = self.file else { return Ok(Vec::new()) };
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        let dir = match path.parent() {
+            Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let mut found: Vec<(u128, PathBuf)> = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if let Some(stamp) = segment_stamp(&name, &file_name) {
+                found.push((stamp, entry.path()));
+            }
+        }
+        found.sort();
+        Ok(found.into_iter().map(|(_, p)| p).collect())
+    }
+
+    // Move the active file aside to a timestamped archive and start a fresh one.
+    // Returns the archive path (None for in-memory vaultlines).
+    pub fn rotate(&mut self) -> Result<Option<PathBuf>> {
+        self.rotate_into(None)
+    }
+
+    // `rotate`, naming the archive after UTC `day` (see `set_rotate_daily`)
+    // instead of the rotation time when given.
+    fn rotate_into(&mut self, day: Option<u128>) -> Result<Option<PathBuf>> {
+        let Some(path) = self.file.clone() else { return Ok(None) };
+        self.require_writable("rotation")?;
+        self.close_writer()?;
+        let archive = match day {
+            Some(day) => {
+                let stem = file_stem(path.file_name().and_then(|n| n.to_str()).unwrap_or_default()).to_string();
+                let mut n = 0;
+                let mut archive = path.with_file_name(daily_archive_name(&stem, day, n));
+                // Checking for the `.gz` too keeps a compressed archive from being overwritten.
+                while archive.exists() || sibling_path(&archive, ".gz").exists() {
+                    n += 1;
+                    archive = path.with_file_name(daily_archive_name(&stem, day, n));
+                }
+                archive
+            }
+            None => {
+                let mut stamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+                let mut archive = sibling_path(&path, &format!(".{stamp}"));
+                while archive.exists() {
+                    stamp += 1;
+                    archive = sibling_path(&path, &format!(".{stamp}"));
+                }
+                archive
+            }
+        };
+        if path.exists() {
+            std::fs::rename(&path, &archive)?;
+        }
+        OpenOptions::new().create(true).append(true).open(&path)?;
+        self.active_day = None;
+        // Daily archives are always compressed when the feature is built.
+        #[cfg(feature = "compression")]
+        let archive = if (self.compress_archives || day.is_some()) && archive.exists() {
+            self.compress_segment(&archive)?
+        } else {
+            archive
+        };
+
+        if let Some(ref archiver) = self.archiver
+            && archive.exists()
+        {
+            archiver.upload(&archive)?;
+            std::fs::remove_file(&archive)?;
+            self.invalidate_summary()?;
+        }
+
+        if let Some(keep) = self.max_archives {
+            let archives = self.archives()?;
+            for old in &archives[..archives.len().saturating_sub(keep)] {
+                std::fs::remove_file(old)?;
+                self.invalidate_summary()?;
+            }
+        }
+        self.enforce_retention()?;
+        Ok(Some(archive))
+    }
+
+    // Upload segments to `archiver` as they are rotated out, keeping only the
+    // active file locally. `query_archived` fetches them back on demand.
+    pub fn set_archiver(&mut self, archiver: Archiver) {
+        self.archiver = Some(archiver);
+    }
+
+    // Events in archived segments (local and remote) matching `filter`, oldest
+    // first. Segments rotated before `filter.since_ms` cannot hold matching
+    // events and are never downloaded.
+    pub fn query_archived(&self, filter: &Filter) -> Result<Vec<Event>> {
+        let Some(ref path) = self.file else { return Ok(Vec::new()) };
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let mut segments: Vec<(u128, PathBuf)> = Vec::new();
+        for local in self.archives()? {
+            let name = local.file_name().unwrap_or_default().to_string_lossy().into_owned();
+            if let Some(stamp) = segment_stamp(&name, &file_name) {
+                segments.push((stamp, local));
+            }
+        }
+        let wanted = |stamp: u128| filter.since_ms.is_none_or(|since| stamp >= since);
+        if let Some(ref archiver) = self.archiver {
+            for name in archiver.remote_segments(file_stem(&file_name))? {
+                let Some(stamp) = segment_stamp(&name, &file_name) else { continue };
+                if wanted(stamp) && !segments.iter().any(|(local, _)| *local == stamp) {
+                    segments.push((stamp, archiver.fetch(&name)?));
+                }
+            }
+        }
+        segments.sort();
+
+        let mut events = Vec::new();
+        for (stamp, seg) in segments {
+            if !wanted(stamp) { continue; }
+            events.extend(self.segment_events(&seg)?.into_iter().filter(|ev| filter.matches(ev)));
+        }
+        Ok(events)
+    }
+
+    // Move every archived segment whose events all predate `ts_ms` into one tar
+    // file at `dest` (gzipped when it ends in `.gz`, which needs the
+    // `compression` feature) and delete them locally. The active file is never
+    // moved. Returns the segments moved; nothing is written when none qualify.
+    // `query_tar` reads them back.
+    #[cfg(feature = "tar")]
+    pub fn archive_before(&mut self, ts_ms: u128, dest: &Path) -> Result<Vec<PathBuf>> {
+        self.require_writable("archiving segments")?;
+        if dest.exists() {
+            return Err(format!("refusing to overwrite {}", dest.display()).into());
+        }
+        let mut old = Vec::new();
+        for seg in self.archives()? {
+            if self.segment_events(&seg)?.iter().all(|ev| ev.ts_ms < ts_ms) {
+                old.push(seg);
+            }
+        }
+        if old.is_empty() { return Ok(old) }
+        tarball::write(dest, &old)?;
+        for seg in &old {
+            std::fs::remove_file(seg)?;
+        }
+        self.invalidate_summary()?;
+        Ok(old)
+    }
+
+    // Events matching `filter` in a tar written by `archive_before`, oldest
+    // segment first. As with `query_archived`, segments rotated before
+    // `filter.since_ms` are skipped without being decoded.
+    #[cfg(feature = "tar")]
+    pub fn query_tar(&self, tar: &Path, filter: &Filter) -> Result<Vec<Event>> {
+        let Some(ref path) = self.file else { return Err("query_tar needs a file-backed vaultline".into()) };
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let mut segments = Vec::new();
+        tarball::read(tar, |name, contents| {
+            if let Some(stamp) = segment_stamp(name, &file_name)
+                && filter.since_ms.is_none_or(|since| stamp >= since)
+            {
+                let contents = if is_compressed(Path::new(name)) {
+                    let mut plain = Vec::new();
+                    open_segment_bytes(&contents)?.read_to_end(&mut plain)?;
+                    plain
+                } else {
+                    contents
+                };
+                segments.push((stamp, contents));
+            }
+            Ok(())
+        })?;
+        segments.sort_by_key(|(stamp, _)| *stamp);
+
+        let mut events = Vec::new();
+        for (_, contents) in segments {
+            let decoded = match self.format {
+                Format::Binary => binary::decode_records(&contents)?.0,
+                Format::Ndjson => {
+                    let mut decoded = Vec::new();
+                    for line in contents.lines() {
+                        if let Some(ev) = self.decode_line(&line?)? {
+                            decoded.push(ev);
+                        }
+                    }
+                    decoded
+                }
+            };
+            events.extend(decoded.into_iter().filter(|ev| filter.matches(ev)));
+        }
+        Ok(events)
+    }
+
+    // Every readable event of a segment in this vaultline's format.
+    fn segment_events(&self, path: &Path) -> Result<Vec<Event>> {
+        match self.format {
+            Format::Binary => Ok(binary::read_records(path)?.0),
+            Format::Ndjson => Ok(self.read_segment(path)?.into_iter().map(|(_, ev)| ev).collect()),
+        }
+    }
+
+    // Gzip archives as they are rotated out; readers decompress them transparently.
+    #[cfg(feature = "compression")]
+    pub fn set_compress_archives(&mut self, on: bool) {
+        self.compress_archives = on;
+    }
+
+    // Replace a plain archive with `<archive>.gz`, returning the new path.
+    #[cfg(feature = "compression")]
+    fn compress_segment(&self, archive: &Path) -> Result<PathBuf> {
+        self.require_ndjson("archive compression")?;
+        let lines = open_segment(archive)?.lines().collect::<std::io::Result<Vec<_>>>()?;
+        let gz = sibling_path(archive, ".gz");
+        self.rewrite_file(&gz, &lines)?;
+        std::fs::remove_file(archive)?;
+        Ok(gz)
+    }
+
+    // Load events from disk into memory (if file exists). A `.gz` path is
+    // decompressed on the fly, so archived segments can be opened directly.
+    // Lines that cannot be parsed are skipped and described in the report (and
+    // copied to `<file>.quarantine` with `set_quarantine_skipped`).
+    pub fn load_from_disk(&mut self) -> Result<LoadReport> {
+        let mut report = LoadReport::default();
+        self.load_lines(usize::MAX, None, &mut report)?;
+        Ok(report)
+    }
+
+    // Append lines skipped by `load_from_disk` to `<file>.quarantine`. The
+    // active file itself is left as is (see `load_with_recovery` to rewrite it).
+    pub fn set_quarantine_skipped(&mut self, on: bool) {
+        self.quarantine_skipped = on;
+    }
+
+    // Load the active file, moving corrupt lines (bad JSON, checksum mismatch,
+    // a torn final write) to `<file>.quarantine` and rewriting the file without
+    // them. Fails without touching the file if no line can be read at all.
+    pub fn load_with_recovery(&mut self) -> Result<RecoveryReport> {
+        let mut report = RecoveryReport::default();
+        let Some(path) = self.file.clone() else { return Ok(report) };
+        if !path.exists() { return Ok(report) }
+        self.require_ndjson("recovery")?;
+        self.require_writable("recovery")?;
+        self.close_writer()?;
+        let bytes = std::fs::read(&path)?;
+        let unterminated = !bytes.is_empty() && !bytes.ends_with(b"\n");
+        let chunks: Vec<&[u8]> = bytes.split(|b| *b == b'\n').collect();
+
+        let (mut good, mut bad) = (Vec::new(), Vec::new());
+        let mut first_err = None;
+        for (i, chunk) in chunks.iter().enumerate() {
+            if chunk.iter().all(u8::is_ascii_whitespace) { continue; }
+            let decoded = match std::str::from_utf8(chunk) {
+                Ok(line) => self.decode_line(line),
+                Err(_) => Ok(None),
+            };
+            match decoded {
+                Ok(Some(ev)) => good.push((String::from_utf8_lossy(chunk).into_owned(), ev)),
+                other => {
+                    if let Err(e) = other { first_err.get_or_insert(e); }
+                    report.torn_tail |= unterminated && i == chunks.len() - 1;
+                    bad.push(String::from_utf8_lossy(chunk).into_owned());
+                }
+            }
+        }
+        if let Some(e) = first_err.filter(|_| good.is_empty()) {
+            return Err(e);
+        }
+
+        if !bad.is_empty() || unterminated {
+            if !bad.is_empty() {
+                let mut quarantine = OpenOptions::new().create(true).append(true).open(sibling_path(&path, ".quarantine"))?;
+                for line in &bad {
+                    writeln!(quarantine, "{line}")?;
+                }
+                quarantine.sync_all()?;
+            }
+            let lines: Vec<String> = good.iter().map(|(line, _)| line.clone()).collect();
+            self.rewrite_file(&path, &lines)?;
+        }
+        report.quarantined = bad.len();
+        report.loaded = good.len();
+        for (_, mut ev) in good {
+            self.canonicalize_source(&mut ev);
+            self.push_mem(ev);
+        }
+        Ok(report)
+    }
+
+    // Like `load_from_disk`, but stop after `max_events` or once `deadline` passes.
+    // Returns how many events were loaded and whether the load was cut short.
+    pub fn load_from_disk_limited(&mut self, max_events: usize, deadline: Instant) -> Result<(usize, bool)> {
+        let mut report = LoadReport::default();
+        let truncated = self.load_lines(max_events, Some(deadline), &mut report)?;
+        Ok((report.loaded, truncated))
+    }
+
+    fn push_loaded(&mut self, events: Vec<Event>, max_events: usize, deadline: Option<Instant>) -> (usize, bool) {
+        let mut added = 0usize;
+        for mut ev in events {
+            if added >= max_events || deadline.is_some_and(|d| Instant::now() >= d) {
+                return (added, true);
+            }
+            self.canonicalize_source(&mut ev);
+            self.push_mem(ev);
+            added += 1;
+        }
+        (added, false)
+    }
+
+    // Load into memory, recording progress in `report`. Returns whether the
+    // load was cut short by `max_events` or `deadline`.
+    fn load_lines(&mut self, max_events: usize, deadline: Option<Instant>, report: &mut LoadReport) -> Result<bool> {
+        self.flush_writes()?;
+        if let Some(ref mut store) = self.store {
+            let events = store.scan()?;
+            let (added, truncated) = self.push_loaded(events, max_events, deadline);
+            report.loaded = added;
+            return Ok(truncated);
+        }
+        let Some(path) = self.file.clone() else { return Ok(false) };
+        if !path.exists() { return Ok(false) }
+        if self.format == Format::Binary {
+            let (events, _torn) = binary::read_records(&path)?;
+            let (added, truncated) = self.push_loaded(events, max_events, deadline);
+            report.loaded = added;
+            return Ok(truncated);
+        }
+        let reader = open_segment(&path)?;
+        let mut skipped_lines = Vec::new();
+        let mut truncated = false;
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() { continue; }
+            if report.loaded >= max_events || deadline.is_some_and(|d| Instant::now() >= d) {
+                truncated = true;
+                break;
+            }
+            match self.decode_line(&line) {
+                Ok(Some(mut ev)) => {
+                    self.canonicalize_source(&mut ev);
+                    self.push_mem(ev);
+                    report.loaded += 1;
+                    continue;
+                }
+                Ok(None) => report.skip(i + 1, &line, self.checksums),
+                // Lines from a newer schema or sealed with another key.
+                Err(e) => report.skip_because(i + 1, e.to_string()),
+            }
+            if self.quarantine_skipped {
+                skipped_lines.push(line);
+            }
+        }
+        if !skipped_lines.is_empty() {
+            self.require_writable("quarantining skipped lines")?;
+            // Skipped lines stay in the log, so a later load skips them again;
+            // only lines not quarantined already are added.
+            let quarantine_path = sibling_path(&path, ".quarantine");
+            let quarantined: HashSet<String> = match std::fs::File::open(&quarantine_path) {
+                Ok(file) => BufReader::new(file).lines().collect::<std::io::Result<_>>()?,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashSet::new(),
+                Err(e) => return Err(e.into()),
+            };
+            skipped_lines.retain(|line| !quarantined.contains(line));
+            if !skipped_lines.is_empty() {
+                let mut quarantine = OpenOptions::new().create(true).append(true).open(&quarantine_path)?;
+                for line in &skipped_lines {
+                    writeln!(quarantine, "{line}")?;
+                }
+                quarantine.sync_all()?;
+            }
+        }
+        Ok(truncated)
+    }
+
+    // Whether events persist anywhere (a backing file or a store).
+    pub fn is_persistent(&self) -> bool {
+        self.file.is_some() || self.store.is_some()
+    }
+
+    // Stream the persisted events, oldest first, without loading them into
+    // memory: local archives, then the active file. A read or decryption error
+    // is yielded as an `Err`. Store-backed vaultlines yield a scan of their store.
+    pub fn iter_disk(&mut self) -> Result<Box<dyn Iterator<Item = Result<Event>> + '_>> {
+        self.iter_disk_filtered(Filter::default())
+    }
+
+    // `iter_disk` yielding only events matching `filter`. NDJSON lines that
+    // cannot contain `filter.contains` are skipped without being parsed.
+    pub fn iter_disk_filtered(&mut self, filter: Filter) -> Result<Box<dyn Iterator<Item = Result<Event>> + '_>> {
+        self.flush_writes()?;
+        if let Some(ref mut store) = self.store {
+            let events = store.scan()?;
+            return Ok(Box::new(events.into_iter().filter(move |ev| filter.matches(ev)).map(Ok)));
+        }
+        let this = &*self;
+        let Some(active) = this.file.clone() else { return Ok(Box::new(std::iter::empty())) };
+        let mut segments = this.archives()?;
+        segments.push(active);
+        segments.retain(|seg| seg.exists());
+        Ok(Box::new(segments.into_iter().flat_map(move |seg| this.segment_matching(&seg, filter.clone()))))
+    }
+
+    // Events of one segment matching `filter`, streamed; failing to open the
+    // segment is yielded as an `Err`.
+    fn segment_matching<'a>(&'a self, path: &Path, filter: Filter) -> Box<dyn Iterator<Item = Result<Event>> + 'a> {
+        // serde_json escapes quotes, backslashes and control characters, so only
+        // needles free of them appear verbatim in a plaintext line.
+        let needle = filter.contains.clone().filter(|n| !n.contains(['"', '\\']) && !n.chars().any(char::is_control));
+        let canonical = move |mut ev: Event| {
+            self.canonicalize_source(&mut ev);
+            filter.matches(&ev).then_some(ev)
+        };
+        if self.format == Format::Binary {
+            return match std::fs::File::open(path) {
+                Ok(file) => Box::new(binary::Records::new(BufReader::new(file)).filter_map(move |ev| ev.map(&canonical).transpose())),
+                Err(e) => Box::new(std::iter::once(Err(e.into()))),
+            };
+        }
+        let lines = match open_segment(path) {
+            Ok(reader) => reader.lines(),
+            Err(e) => return Box::new(std::iter::once(Err(e))),
+        };
+        Box::new(lines.filter_map(move |line| {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e.into())),
+            };
+            if line.trim().is_empty() { return None; }
+            if let Some(ref n) = needle
+                && !line.starts_with("enc:")
+                && !line.contains(n.as_str())
+            {
+                return None;
+            }
+            self.decode_line(&line).map(|ev| ev.and_then(&canonical)).transpose()
+        }))
+    }
+
+    // The last `n` persisted events, oldest first. Plain NDJSON segments are
+    // read by seeking backwards from the end, newest segment first, so only the
+    // tail is ever parsed; binary and compressed segments are scanned forward.
+    // Vaultlines without a file read the store or memory.
+    pub fn tail_disk(&mut self, n: usize) -> Result<Vec<Event>> {
+        self.tail_disk_filtered(n, Filter::default())
+    }
+
+    // `tail_disk` counting only events matching `filter`.
+    pub fn tail_disk_filtered(&mut self, n: usize, filter: Filter) -> Result<Vec<Event>> {
+        let now = now_ms();
+        let Some(active) = self.file.clone().filter(|_| self.store.is_none()) else {
+            let mut tail = VecDeque::with_capacity(n.min(1024));
+            for event in self.events_matching(filter)? {
+                let event = event?;
+                if event.is_expired(now) { continue; }
+                tail.push_back(event);
+                if tail.len() > n {
+                    tail.pop_front();
+                }
+            }
+            return Ok(tail.into());
+        };
+        self.flush_writes()?;
+        let mut segments = self.archives()?;
+        segments.push(active);
+        // Newest first until `n` are found.
+        let mut tail = Vec::new();
+        for seg in segments.iter().rev().filter(|seg| seg.exists()) {
+            if tail.len() >= n { break; }
+            if self.format == Format::Binary || is_compressed(seg) {
+                let mut events = Vec::new();
+                for event in self.segment_matching(seg, filter.clone()) {
+                    let event = event?;
+                    if !event.is_expired(now) {
+                        events.push(event);
+                    }
+                }
+                let take = events.len().min(n - tail.len());
+                tail.extend(events.into_iter().rev().take(take));
+                continue;
+            }
+            let mut err = None;
+            read_lines_rev(seg, |line| {
+                match self.decode_line(line) {
+                    Ok(Some(mut ev)) => {
+                        self.canonicalize_source(&mut ev);
+                        if filter.matches(&ev) && !ev.is_expired(now) {
+                            tail.push(ev);
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(e) => err = Some(e),
+                }
+                err.is_none() && tail.len() < n
+            })?;
+            if let Some(e) = err {
+                return Err(e);
+            }
+        }
+        tail.reverse();
+        Ok(tail)
+    }
+
+    // Persisted events matching `filter` streamed from disk (see `iter_disk`), or
+    // the matching in-memory events when nothing is persisted.
+    pub fn events_matching(&mut self, filter: Filter) -> Result<Box<dyn Iterator<Item = Result<Event>> + '_>> {
+        if self.is_persistent() {
+            self.iter_disk_filtered(filter)
+        } else {
+            Ok(Box::new(self.query(filter).cloned().map(Ok)))
+        }
+    }
+
+    // Retrieve all events in memory.
+    pub fn all(&self) -> &[Event] {
+        &self.mem
+    }
+
+    // In-memory events matching `filter`, oldest first. Source, level and time
+    // filters use the index.
+    pub fn query(&self, filter: Filter) -> impl Iterator<Item = &Event> + '_ {
+        let candidates: Box<dyn Iterator<Item = &Event> + '_> = if let Some(source) = filter.source.as_deref() {
+            Box::new(self.by_source(source))
+        } else if let Some(level) = filter.level.as_deref() {
+            Box::new(self.by_level(level))
+        } else if filter.since_ms.is_some() || filter.until_ms.is_some() {
+            Box::new(self.range(filter.since_ms.unwrap_or(0), filter.until_ms.unwrap_or(u128::MAX)))
+        } else {
+            Box::new(self.mem.iter())
+        };
+        let now = now_ms();
+        candidates.filter(move |ev| filter.matches(ev) && !ev.is_expired(now))
+    }
+
+    // In-memory events with `since_ms <= ts_ms < until_ms`, in buffer order.
+    // In-order stretches are found by binary search; only records that arrived
+    // out of timestamp order are checked one by one.
+    pub fn range(&self, since_ms: u128, until_ms: u128) -> impl Iterator<Item = &Event> + '_ {
+        self.index
+            .time_candidates(since_ms, until_ms)
+            .map(|i| &self.mem[i])
+            .filter(move |ev| ev.ts_ms >= since_ms && ev.ts_ms < until_ms)
+    }
+
+    // In-memory events from `source`, oldest first (indexed).
+    pub fn by_source(&self, source: &str) -> impl DoubleEndedIterator<Item = &Event> + '_ {
+        self.index.source_offsets(source).map(|i| &self.mem[i])
+    }
+
+    // In-memory events at `level`, oldest first (indexed, case-insensitive).
+    pub fn by_level(&self, level: &str) -> impl DoubleEndedIterator<Item = &Event> + '_ {
+        self.index.level_offsets(level).map(|i| &self.mem[i])
+    }
+
+    // Drop in-memory events failing `pred` (the file is untouched). Returns how many were removed.
+    pub fn retain<F: Fn(&Event) -> bool>(&mut self, pred: F) -> usize {
+        let before = self.mem.len();
+        self.mem.retain(|ev| pred(ev));
+        self.reindex();
+        before - self.mem.len()
+    }
+
+    // Write in-memory events as NDJSON. Returns how many were written.
+    pub fn export_json<W: Write>(&self, out: W) -> Result<usize> {
+        self.export_json_projected(&EVENT_FIELDS, out)
+    }
+
+    // Write in-memory events as NDJSON objects holding only `fields`.
+    pub fn export_json_projected<W: Write>(&self, fields: &[&str], out: W) -> Result<usize> {
+        export::write_json(self.mem.iter().cloned().map(Ok), fields, out)
+    }
+
+    // Write in-memory events as CSV with a header row. `kv` is emitted as a JSON string.
+    pub fn export_csv<W: Write>(&self, out: W) -> Result<usize> {
+        self.export_csv_projected(&EVENT_FIELDS, out)
+    }
+
+    // Write in-memory events as CSV with only the `fields` columns.
+    pub fn export_csv_projected<W: Write>(&self, fields: &[&str], out: W) -> Result<usize> {
+        export::write_csv(self.mem.iter().cloned().map(Ok), fields, out)
+    }
+
+    // Write the events matching `filter` to a new file at `path`. Persisted events
+    // are streamed from disk; in-memory vaultlines export what they hold.
+    // Returns how many events were written.
+    pub fn export(&mut self, format: ExportFormat, path: &Path, filter: Filter) -> Result<usize> {
+        let file = create_new(path)?;
+        let events = self.events_matching(filter)?;
+        match format {
+            ExportFormat::Json => export::write_json(events, &EVENT_FIELDS, BufWriter::new(file)),
+            ExportFormat::Csv => export::write_csv(events, &EVENT_FIELDS, BufWriter::new(file)),
+            #[cfg(feature = "parquet")]
+            ExportFormat::Parquet => export::write_parquet(events, file),
+        }
+    }
+}
+
+// Records held in open rollup windows would otherwise vanish with the
+// vaultline, so they are stored (and buffered appends flushed) on drop.
+// Errors can only be logged here; call `close` to see them.
+impl Drop for Vaultline {
+    fn drop(&mut self) {
+        if self.read_only { return }
+        if let Err(e) = self.flush_rollups().and_then(|_| self.flush()) {
+            tracing::warn!(error = %e, "vaultline dropped without close; pending records may be lost");
+        }
+    }
+}
+
+// Fans appends out to per-level vaultlines, e.g. errors into their own file.
+pub struct RoutingVaultline {
+    routes: HashMap<Level, Vaultline>,
+    fallback: Vaultline,
+}
+
+impl RoutingVaultline {
+    // Events whose level has no route go to `fallback`.
+    pub fn new(fallback: Vaultline) -> Self {
+        Self { routes: HashMap::new(), fallback }
+    }
+
+    // Send events of `level` to `vault` (replacing any previous route for it).
+    pub fn route<L: Into<Level>>(&mut self, level: L, vault: Vaultline) {
+        self.routes.insert(level.into(), vault);
+    }
+
+    pub fn append(&mut self, event: Event) -> Result<()> {
+        match self.routes.get_mut(&event.level) {
+            Some(vault) => vault.append(event),
+            None => self.fallback.append(event),
+        }
+    }
+
+    // The vaultline that receives `level`, falling back to the default.
+    pub fn vault_for(&self, level: &str) -> &Vaultline {
+        self.routes.get(&Level::parse(level)).unwrap_or(&self.fallback)
+    }
+
+    pub fn fallback(&self) -> &Vaultline {
+        &self.fallback
+    }
+}
+
+// `[YYYY-MM-DD[ HH:MM:SS]] LEVEL source: message`
+static TEXT_LINE: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(
+        r"^(?:(\d{4})-(\d{2})-(\d{2})(?:[ T](\d{2}):(\d{2}):(\d{2}))?\s+)?([A-Za-z]+)\s+([^\s:]+):\s*(.*)$",
+    )
+    .expect("valid text line regex")
+});
+
+// Milliseconds since the Unix epoch for a UTC civil date/time.
+fn civil_to_ms(year: i64, month: i64, day: i64, secs_of_day: i64) -> Option<u128> {
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) { return None; }
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+    u128::try_from((days * 86_400 + secs_of_day) * 1000).ok()
+}
+
+// Default parser for legacy text logs shaped like `2024-01-01 ERROR auth: login failed`.
+// Lines without a date are stamped with the current time.
+pub fn parse_text_line(line: &str) -> Option<Event> {
+    let caps = TEXT_LINE.captures(line.trim())?;
+    let num = |i: usize| caps.get(i).map(|m| m.as_str().parse::<i64>().unwrap_or(0));
+    let mut ev = Event::now(&caps[8], &caps[7], &caps[9]);
+    if let (Some(y), Some(m), Some(d)) = (num(1), num(2), num(3)) {
+        let secs = num(4).unwrap_or(0) * 3600 + num(5).unwrap_or(0) * 60 + num(6).unwrap_or(0);
+        ev.ts_ms = civil_to_ms(y, m, d, secs)?;
+    }
+    Some(ev)
+}
+
+// Read a plain-text log, turning each line into an event with `parser`; lines it rejects are skipped.
+pub fn import_text<P: AsRef<Path>, F: Fn(&str) -> Option<Event>>(path: P, parser: F) -> Result<Vec<Event>> {
+    let f = std::fs::File::open(path)?;
+    let mut events = Vec::new();
+    for line in BufReader::new(f).lines() {
+        let line = line?;
+        if line.trim().is_empty() { continue; }
+        if let Some(ev) = parser(&line) {
+            events.push(ev);
+        }
+    }
+    Ok(events)
+}
+
+// Unit tests for Vaultline
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_creation() {
+        let ev = Event::now("test_source", "info", "This is a test");
+        assert_eq!(ev.source, "test_source");
+        assert_eq!(ev.level, "info");
+        assert_eq!(ev.message, "This is a test");
+        assert!(ev.ts_ms > 0);
+        assert!(ev.kv.is_null());
+    }
+
+    #[test]
+    fn test_kv_builder_and_getters() {
+        let ev = Event::now("epoch", "info", "job done").with("job_id", 42).with("user", "x").with("ok", true);
+        assert_eq!(ev.kv, serde_json::json!({ "job_id": 42, "user": "x", "ok": true }));
+        assert_eq!(ev.kv_u64("job_id"), Some(42));
+        assert_eq!(ev.kv_i64("job_id"), Some(42));
+        assert_eq!(ev.kv_str("user"), Some("x"));
+        assert_eq!(ev.kv_bool("ok"), Some(true));
+        assert_eq!(ev.kv_str("job_id"), None);
+        assert_eq!(ev.kv_f64("missing"), None);
+    }
+
+    #[test]
+    fn test_vaultline_in_memory() {
+        let mut vault = Vaultline::new_in_memory();
+        assert_eq!(vault.all().len(), 0);
+
+        let ev1 = Event::now("src1", "info", "First event");
+        vault.append(ev1.clone()).unwrap();
+        assert_eq!(vault.all().len(), 1);
+        assert_eq!(vault.all()[0], ev1);
+
+        let ev2 = Event::now("src2", "error", "Second event");
+        vault.append(ev2.clone()).unwrap();
+        assert_eq!(vault.all().len(), 2);
+        assert_eq!(vault.all()[1], ev2);
+
+        let tail = vault.tail(1);
+        assert_eq!(tail.len(), 1);
+        assert_eq!(tail[0], &ev2);
+
+        let tail_all = vault.tail(5);
+        assert_eq!(tail_all.len(), 2);
+    }
+
+    #[test]
+    fn test_vaultline_file_backed() {
+        let temp_dir = std::env::temp_dir();
+        let log_path = temp_dir.join(format!("vaultline_test_{}.log", std::process::id()));
+        if log_path.exists() {
+            let _ = std::fs::remove_file(&log_path);
+        }
+
+        let mut vault = Vaultline::new(&log_path).unwrap();
+        assert_eq!(vault.all().len(), 0);
+
+        let ev1 = Event::now("file_src1", "info", "File event 1");
+        vault.append(ev1.clone()).unwrap();
+        assert_eq!(vault.all().len(), 1);
+
+        let ev2 = Event::now("file_src2", "warn", "File event 2");
+        vault.append(ev2.clone()).unwrap();
+        assert_eq!(vault.all().len(), 2);
+
+        // Reload from disk
+        let mut vault_reload = Vaultline::new(&log_path).unwrap();
+        assert_eq!(vault_reload
+            .load_from_disk()
+            .unwrap().loaded, 2);
+        assert_eq!(vault_reload.all().len(), 2);
+        assert_eq!(vault_reload.all()[0], ev1);
         assert_eq!(vault_reload.all()[1], ev2);
         let _ = std::fs::remove_file
             (&log_path);
     }   
     #[test]
-    fn test_event_normalization() {
-        let mut ev = Event {
-            ts_ms: 0,
-            source: "".into(),
-            level: "".into(),
-            message: "".into(),
-            kv: serde_json::Value::Null,
+    fn test_event_normalization() {
+        let mut ev = Event {
+            ts_ms: 0,
+            source: "".into(),
+            level: "".into(),
+            message: "".into(),
+            kv: serde_json::Value::Null,
+            seq: 0,
+            correlation_id: None,
+            trace_id: None,
+            idempotency_key: None,
+            prev_hash: None,
+            signature: None,
+            expires_at: None,
+            schema_version: SCHEMA_VERSION,
+        };
+        Vaultline::normalize_event(&mut ev);
+        assert_eq!(ev.source, "unknown");
+        assert_eq!(ev.level, "info");
+        assert_eq!(ev.message, "(no message)");
+        assert!(ev.ts_ms > 0);
+        assert!(ev.kv.is_object() && ev.kv.as_object().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_source_min_level() {
+        let mut vault = Vaultline::new_in_memory();
+        vault.set_source_min_level("chatty", "warn");
+
+        vault.append(Event::now("chatty", "info", "noise")).unwrap();
+        let quiet = Event::now("quiet", "info", "signal");
+        vault.append(quiet.clone()).unwrap();
+
+        assert_eq!(vault.all(), &[quiet]);
+    }
+
+    #[test]
+    fn test_min_level_from_config() {
+        let mut vault = Vaultline::new_in_memory();
+        vault.apply_config(&VaultConfig { min_level: Some(Level::Warn), ..Default::default() }).unwrap();
+        vault.append(Event::now("epoch", "debug", "tick")).unwrap();
+        vault.append(Event::now("epoch", "info", "job done")).unwrap();
+        vault.append(Event::now("epoch", "error", "job failed")).unwrap();
+        vault.append(Event::now("epoch", "notice", "unranked")).unwrap();
+
+        let kept: Vec<&str> = vault.all().iter().map(|ev| ev.message.as_str()).collect();
+        assert_eq!(kept, ["job failed", "unranked"]);
+        assert_eq!(vault.dropped_counts()["epoch"], 2);
+    }
+
+    #[test]
+    fn test_sinks_receive_every_stored_batch() {
+        struct Failing;
+        impl EventSink for Failing {
+            fn write(&mut self, _: &[Event]) -> Result<()> {
+                Err("forwarder down".into())
+            }
+        }
+        let path = std::env::temp_dir().join(format!("vaultline_sinks_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut vault = Vaultline::new_in_memory();
+        vault.set_min_level(Some(Level::Info));
+        vault.add_sink(NdjsonSink::new(std::fs::File::create(&path).unwrap()));
+        vault.append_batch(vec![Event::now("epoch", "info", "a"), Event::now("epoch", "debug", "b")]).unwrap();
+        vault.add_sink(Failing);
+        vault.add_sink(NdjsonSink::new(OpenOptions::new().append(true).open(&path).unwrap()));
+        vault.append(Event::now("epoch", "warn", "c")).unwrap();
+        vault.flush().unwrap();
+
+        let mut mirror = Vaultline::new(&path).unwrap();
+        mirror.load_from_disk().unwrap();
+        let messages: Vec<&str> = mirror.all().iter().map(|ev| ev.message.as_str()).collect();
+        assert_eq!(messages, ["a", "c", "c"]);
+        assert_eq!(vault.all().len(), 2);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_sinks_flush_only_when_asked() {
+        struct Counting(std::sync::Arc<AtomicU64>);
+        impl EventSink for Counting {
+            fn write(&mut self, _: &[Event]) -> Result<()> {
+                Ok(())
+            }
+            fn flush(&mut self) -> Result<()> {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            }
+        }
+        let path = std::env::temp_dir().join(format!("vaultline_sink_flush_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let flushes = std::sync::Arc::new(AtomicU64::new(0));
+        let mut vault = Vaultline::new(&path).unwrap();
+        vault.add_sink(Counting(flushes.clone()));
+        // The default policy writes every append through to the file, but sinks keep batching.
+        for i in 0..3 {
+            vault.append(Event::now("epoch", "info", format!("tick {i}"))).unwrap();
+        }
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 3);
+        assert_eq!(flushes.load(Ordering::SeqCst), 0);
+        vault.flush().unwrap();
+        vault.close().unwrap();
+        assert_eq!(flushes.load(Ordering::SeqCst), 2);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_stats_cover_archives_and_active_file() {
+        let dir = std::env::temp_dir().join(format!("vaultline_stats_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let at = |ts_ms, source: &str, level: &str| Event { ts_ms, ..Event::now(source, level, "x") };
+        let mut vault = Vaultline::new(dir.join("event.log")).unwrap();
+        vault.append(at(500, "auth", "error")).unwrap();
+        vault.rotate().unwrap();
+        vault.append(at(100, "epoch", "info")).unwrap();
+        vault.append(at(900, "epoch", "info")).unwrap();
+
+        let stats = Vaultline::new(dir.join("event.log")).unwrap().stats().unwrap();
+        assert_eq!((stats.total, stats.segments, stats.in_memory), (3, 2, 0));
+        assert_eq!(stats.by_level, BTreeMap::from([("error".to_string(), 1), ("info".to_string(), 2)]));
+        assert_eq!(stats.by_source["epoch"], 2);
+        assert_eq!((stats.oldest_ms, stats.newest_ms), (Some(100), Some(900)));
+        let on_disk: u64 = std::fs::read_dir(&dir).unwrap().map(|e| e.unwrap().metadata().unwrap().len()).sum();
+        assert_eq!(stats.disk_bytes, on_disk);
+        assert!(vault.stats().unwrap().memory_bytes >= 3 * std::mem::size_of::<Event>());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_search_message_and_kv_across_segments() {
+        let dir = std::env::temp_dir().join(format!("vaultline_search_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut vault = Vaultline::new(dir.join("event.log")).unwrap();
+        vault.append(Event::now("epoch", "warn", "Lease expired for job 1")).unwrap();
+        vault.rotate().unwrap();
+        vault.append(Event::now("epoch", "info", "job 2 done").with("detail", serde_json::json!({ "note": "lease expired once" }))).unwrap();
+        vault.append(Event::now("epoch", "info", "job 3 done")).unwrap();
+
+        let messages = |hits: Vec<Event>| hits.into_iter().map(|ev| ev.message).collect::<Vec<_>>();
+        let exact = SearchOptions::default();
+        assert_eq!(messages(vault.search("lease expired", &exact).unwrap()), ["job 2 done"]);
+        let loose = SearchOptions { case_insensitive: true, ..Default::default() };
+        assert_eq!(messages(vault.search("LEASE EXPIRED", &loose).unwrap()), ["Lease expired for job 1", "job 2 done"]);
+        let limited = SearchOptions { limit: Some(1), ..loose };
+        assert_eq!(vault.search("lease", &limited).unwrap().len(), 1);
+        let re = SearchOptions { regex: true, ..Default::default() };
+        assert_eq!(messages(vault.search(r"job [23] done", &re).unwrap()), ["job 2 done", "job 3 done"]);
+        assert!(vault.search("(", &re).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_range_handles_out_of_order_records() {
+        let mut vault = Vaultline::new_in_memory();
+        vault.set_memory_cap(6);
+        for ts_ms in [10, 20, 30, 15, 40, 50, 25, 60, 5] {
+            vault.append(Event { ts_ms, ..Event::now("epoch", "info", "tick") }).unwrap();
+        }
+        // Memory now holds 15, 40, 50, 25, 60, 5.
+        let stamps = |events: Vec<&Event>| events.iter().map(|ev| ev.ts_ms).collect::<Vec<_>>();
+        assert_eq!(stamps(vault.range(20, 55).collect()), [40, 50, 25]);
+        assert_eq!(stamps(vault.range(0, 16).collect()), [15, 5]);
+        assert_eq!(stamps(vault.range(60, u128::MAX).collect()), [60]);
+        assert!(vault.range(100, 200).next().is_none());
+        assert_eq!(stamps(vault.query(Filter::new().since(45)).collect()), [50, 60]);
+        vault.retain(|ev| ev.ts_ms != 50);
+        assert_eq!(stamps(vault.range(20, 55).collect()), [40, 25]);
+    }
+
+    #[cfg(feature = "signing")]
+    #[test]
+    fn test_signed_events_verify_against_public_key() {
+        let path = std::env::temp_dir().join(format!("vaultline_signing_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let seed = [7u8; 32];
+        let mut vault = Vaultline::new(&path).unwrap();
+        vault.set_sequencing(true);
+        vault.set_signing_key(&seed);
+        vault.append(Event::now("auth", "info", "login").with("user", 7)).unwrap();
+        vault.append(Event::now("auth", "warn", "password reset")).unwrap();
+
+        let public = public_key(&seed);
+        let report = vault.verify_signatures(&public).unwrap();
+        assert_eq!((report.records, report.signed), (2, 2));
+        assert!(report.invalid.is_empty());
+        assert!(!verify_event(&vault.all()[0], &public_key(&[8u8; 32])).unwrap());
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, text.replace("\"seq\":2", "\"seq\":3")).unwrap();
+        assert_eq!(vault.verify_signatures(&public).unwrap().invalid, [1]);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_redaction_applies_before_disk() {
+        let path = std::env::temp_dir().join(format!("vaultline_redact_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut vault = Vaultline::new(&path).unwrap();
+        vault.set_redactor(Some(Redactor::new(&["password"], &[r"token=\S+"]).unwrap()));
+        vault.append(Event::now("auth", "info", "login token=abc123 ok").with("password", "hunter2")).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(!text.contains("hunter2") && !text.contains("abc123"), "{text}");
+        assert_eq!(vault.all()[0].message, "login [REDACTED] ok");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_hash_chain_detects_edits_and_deletions() {
+        let dir = std::env::temp_dir().join(format!("vaultline_chain_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let log_path = dir.join("event.log");
+        let mut vault = Vaultline::new(&log_path).unwrap();
+        vault.append(Event::now("auth", "info", "unchained")).unwrap();
+        vault.set_hash_chain(true);
+        vault.append(Event::now("auth", "info", "grant admin to 7")).unwrap();
+        vault.rotate().unwrap();
+        drop(vault);
+        let mut vault = Vaultline::new(&log_path).unwrap();
+        vault.set_hash_chain(true);
+        for i in 0..3 {
+            vault.append(Event::now("auth", "info", format!("login {i}"))).unwrap();
+        }
+        let report = vault.verify().unwrap();
+        assert_eq!((report.records, report.chained), (5, 4));
+        assert!(report.is_intact(), "{report:?}");
+
+        let text = std::fs::read_to_string(&log_path).unwrap();
+        std::fs::write(&log_path, text.replace("login 1", "login 9")).unwrap();
+        assert_eq!(vault.verify().unwrap().breaks, [4]);
+        let lines: Vec<&str> = text.lines().collect();
+        std::fs::write(&log_path, format!("{}\n{}\n", lines[0], lines[2])).unwrap();
+        assert_eq!(vault.verify().unwrap().breaks, [3]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_compact_atomic_rewrite() {
+        let log_path = std::env::temp_dir().join(format!("vaultline_compact_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&log_path);
+
+        let mut vault = Vaultline::new(&log_path).unwrap();
+        vault.set_atomic_rewrite(true);
+        for i in 0..3 {
+            vault.append(Event::now("compact", "info", format!("event {i}"))).unwrap();
+        }
+        OpenOptions::new().append(true).open(&log_path).unwrap().write_all(b"{not json\n").unwrap();
+
+        let stats = vault.compact().unwrap();
+        assert_eq!(stats, CompactStats { kept: 3, malformed: 1, ..Default::default() });
+        assert!(!sibling_path(&log_path, ".tmp").exists());
+
+        let mut reloaded = Vaultline::new(&log_path).unwrap();
+        assert_eq!(reloaded.load_from_disk().unwrap().loaded, 3);
+        assert_eq!(reloaded.all(), vault.all());
+        let _ = std::fs::remove_file(&log_path);
+    }
+
+    #[test]
+    fn test_load_upgrades_unversioned_records() {
+        let path = std::env::temp_dir().join(format!("vaultline_schema_{}.log", std::process::id()));
+        std::fs::write(&path, "{\"ts_ms\":1,\"source\":\"epoch\",\"level\":\"info\",\"message\":\"v0\"}\n").unwrap();
+        let mut vault = Vaultline::new(&path).unwrap();
+        vault.append(Event::now("epoch", "info", "v1")).unwrap();
+
+        let mut reloaded = Vaultline::new(&path).unwrap();
+        assert_eq!(reloaded.load_from_disk().unwrap().loaded, 2);
+        assert!(reloaded.all().iter().all(|ev| ev.schema_version == SCHEMA_VERSION));
+        assert_eq!(reloaded.all()[1], vault.all()[0]);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_sequence_numbers_resume_after_reopen() {
+        let path = std::env::temp_dir().join(format!("vaultline_seq_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut vault = Vaultline::new(&path).unwrap();
+        vault.append(Event::now("src", "info", "unnumbered")).unwrap();
+        vault.set_sequencing(true);
+        for i in 0..3 {
+            vault.append(Event::now("src", "info", format!("event {i}"))).unwrap();
+        }
+        let seqs: Vec<u64> = vault.all().iter().map(|ev| ev.seq).collect();
+        assert_eq!(seqs, [0, 1, 2, 3]);
+        assert_eq!(vault.since(1).len(), 2);
+        assert_eq!(vault.since(1)[0].message, "event 1");
+
+        let mut reopened = Vaultline::new(&path).unwrap();
+        reopened.set_sequencing(true);
+        reopened.append(Event::now("src", "info", "after reopen")).unwrap();
+        assert_eq!(reopened.all()[0].seq, 4);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_append_batch_writes_once_and_validates_first() {
+        let path = std::env::temp_dir().join(format!("vaultline_batch_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut vault = Vaultline::new(&path).unwrap();
+        vault.set_source_min_level("chatty", "warn");
+        vault.require_kv("auth", vec!["user_id".to_string()]);
+
+        let batch = vec![
+            Event::now("epoch", "info", "one"),
+            Event::now("chatty", "debug", "dropped"),
+            Event::now("epoch", "info", "two"),
+        ];
+        assert_eq!(vault.append_batch(batch).unwrap(), 2);
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 2);
+
+        let rejected = vec![Event::now("epoch", "info", "three"), Event::now("auth", "info", "no user")];
+        assert!(vault.append_batch(rejected).is_err());
+        assert_eq!(vault.all().len(), 2);
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 2);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_iter_disk_streams_without_loading() {
+        let path = std::env::temp_dir().join(format!("vaultline_iter_disk_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut vault = Vaultline::new(&path).unwrap();
+        for i in 0..5 {
+            vault.append(Event::now("epoch", "info", format!("job {i} done"))).unwrap();
+        }
+        OpenOptions::new().append(true).open(&path).unwrap().write_all(b"{not json\n").unwrap();
+
+        let mut reader = Vaultline::new(&path).unwrap();
+        assert_eq!(reader.iter_disk().unwrap().count(), 5);
+        let hits: Vec<Event> = reader.iter_disk_filtered(Filter::new().contains("job 3")).unwrap().map(Result::unwrap).collect();
+        assert_eq!(hits, [vault.all()[3].clone()]);
+        assert!(reader.all().is_empty());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_append_lock_times_out() {
+        let path = std::env::temp_dir().join(format!("vaultline_lock_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut vault = Vaultline::new(&path).unwrap();
+        vault.set_append_lock(Some(Duration::from_millis(30)));
+        vault.set_sequencing(true);
+        vault.append(Event::now("daemon", "info", "unlocked")).unwrap();
+
+        // A second open file description stands in for another process.
+        let other = OpenOptions::new().append(true).open(&path).unwrap();
+        other.lock().unwrap();
+        let err = vault.append(Event::now("daemon", "info", "blocked").with_idempotency_key("k1")).unwrap_err();
+        assert!(matches!(err.downcast_ref::<VaultlineError>(), Some(VaultlineError::LockTimeout { .. })));
+        other.unlock().unwrap();
+
+        // The failed append left nothing behind: no buffered copy, no seq
+        // consumed, and its idempotency key can be retried.
+        assert_eq!(vault.all().len(), 1);
+        vault.append(Event::now("daemon", "info", "blocked").with_idempotency_key("k1")).unwrap();
+        assert_eq!(vault.all().iter().map(|ev| ev.seq).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 2);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_hard_limit() {
+        let mut vault = Vaultline::new_in_memory();
+        vault.set_hard_limit(2);
+        vault.append(Event::now("src", "info", "one")).unwrap();
+        vault.append(Event::now("src", "info", "two")).unwrap();
+
+        let err = vault.append(Event::now("src", "info", "three")).unwrap_err();
+        assert!(err.to_string().contains("vaultline full"));
+        assert_eq!(vault.all().len(), 2);
+    }
+
+    #[test]
+    fn test_export_projected() {
+        let mut vault = Vaultline::new_in_memory();
+        vault.append(Event::now("src", "info", "hello, world")).unwrap();
+        vault.append(Event::now("src", "warn", "second")).unwrap();
+
+        let mut buf = Vec::new();
+        assert_eq!(vault.export_json_projected(&["ts_ms", "message"], &mut buf).unwrap(), 2);
+        let lines: Vec<serde_json::Value> = String::from_utf8(buf).unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        for (line, ev) in lines.iter().zip(vault.all()) {
+            let obj = line.as_object().unwrap();
+            assert_eq!(obj.len(), 2);
+            assert_eq!(obj["ts_ms"], serde_json::json!(ev.ts_ms));
+            assert_eq!(obj["message"], serde_json::json!(ev.message));
+        }
+
+        let mut csv = Vec::new();
+        vault.export_csv_projected(&["level", "message"], &mut csv).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap(), "level,message\ninfo,\"hello, world\"\nwarn,second\n");
+
+        assert!(vault.export_json_projected(&["nope"], Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_export_streams_filtered_events_to_file() {
+        let dir = std::env::temp_dir().join(format!("vaultline_export_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut vault = Vaultline::new(dir.join("event.log")).unwrap();
+        vault.append(Event::now("auth", "error", "denied, twice")).unwrap();
+        vault.append(Event::now("epoch", "info", "done")).unwrap();
+
+        let mut reader = Vaultline::new(dir.join("event.log")).unwrap();
+        let csv = dir.join("errors.csv");
+        assert_eq!(reader.export(ExportFormat::Csv, &csv, Filter::new().level("error")).unwrap(), 1);
+        let written = std::fs::read_to_string(&csv).unwrap();
+        assert_eq!(written.lines().nth(1).unwrap().split(',').nth(1), Some("auth"));
+        assert!(written.contains("\"denied, twice\""));
+        assert!(reader.export(ExportFormat::Json, &csv, Filter::new()).is_err());
+        assert_eq!(reader.export(ExportFormat::Json, &dir.join("all.json"), Filter::new()).unwrap(), 2);
+        #[cfg(feature = "parquet")]
+        {
+            let parquet = dir.join("all.parquet");
+            assert_eq!(reader.export(ExportFormat::Parquet, &parquet, Filter::new()).unwrap(), 2);
+            assert_eq!(&std::fs::read(&parquet).unwrap()[..4], b"PAR1");
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_export_parquet_reads_back() {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+        use parquet::record::RowAccessor;
+
+        let dir = std::env::temp_dir().join(format!("vaultline_parquet_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut vault = Vaultline::new(dir.join("event.log")).unwrap();
+        vault.append(Event::now("auth", "error", "denied").with("user", "ana")).unwrap();
+        vault.append(Event::now("epoch", "info", "done")).unwrap();
+        vault.append(Event::now("epoch", "warn", "slow")).unwrap();
+
+        let path = dir.join("all.parquet");
+        assert_eq!(vault.export(ExportFormat::Parquet, &path, Filter::new()).unwrap(), 3);
+        let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let meta = reader.metadata().file_metadata();
+        assert_eq!(meta.num_rows(), 3);
+        let columns: Vec<&str> = meta.schema_descr().columns().iter().map(|c| c.name()).collect();
+        assert_eq!(columns, ["ts_ms", "source", "level", "message", "kv"]);
+
+        let rows: Vec<_> = reader.get_row_iter(None).unwrap().map(|row| row.unwrap()).collect();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].get_long(0).unwrap() as u128, vault.all()[0].ts_ms);
+        assert_eq!((rows[0].get_string(1).unwrap().as_str(), rows[2].get_string(3).unwrap().as_str()), ("auth", "slow"));
+        assert_eq!(rows[0].get_string(4).unwrap(), r#"{"user":"ana"}"#);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_load_from_disk_limited() {
+        let log_path = std::env::temp_dir().join(format!("vaultline_limited_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&log_path);
+        let mut vault = Vaultline::new(&log_path).unwrap();
+        for i in 0..10 {
+            vault.append(Event::now("src", "info", format!("event {i}"))).unwrap();
+        }
+
+        let deadline = Instant::now() + std::time::Duration::from_secs(60);
+        let mut reloaded = Vaultline::new(&log_path).unwrap();
+        assert_eq!(reloaded.load_from_disk_limited(3, deadline).unwrap(), (3, true));
+        assert_eq!(reloaded.all(), &vault.all()[..3]);
+
+        let mut full = Vaultline::new(&log_path).unwrap();
+        assert_eq!(full.load_from_disk_limited(10, deadline).unwrap(), (10, false));
+        let _ = std::fs::remove_file(&log_path);
+    }
+
+    #[test]
+    fn test_import_text() {
+        let path = std::env::temp_dir().join(format!("vaultline_import_{}.txt", std::process::id()));
+        std::fs::write(&path, "2024-01-01 ERROR auth: login failed\n\
+            garbage line\n\
+            2024-01-01 12:30:00 WARN epoch: lease expired for job 7\n\
+            INFO halodeck: status ok\n").unwrap();
+
+        let events = import_text(&path, parse_text_line).unwrap();
+        assert_eq!(events.len(), 3);
+        assert_eq!((events[0].level.as_str(), events[0].source.as_str(), events[0].message.as_str()), ("error", "auth", "login failed"));
+        assert_eq!(events[0].ts_ms, 1_704_067_200_000);
+        assert_eq!((events[1].level.as_str(), events[1].source.as_str()), ("warn", "epoch"));
+        assert_eq!(events[1].message, "lease expired for job 7");
+        assert_eq!(events[1].ts_ms, 1_704_067_200_000 + 45_000_000);
+        assert_eq!((events[2].level.as_str(), events[2].source.as_str(), events[2].message.as_str()), ("info", "halodeck", "status ok"));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_routing_vaultline() {
+        let mut router = RoutingVaultline::new(Vaultline::new_in_memory());
+        router.route("error", Vaultline::new_in_memory());
+
+        let boom = Event::now("auth", "error", "login failed");
+        let hello = Event::now("auth", "info", "login ok");
+        let slow = Event::now("epoch", "warn", "slow job");
+        for ev in [boom.clone(), hello.clone(), slow.clone()] {
+            router.append(ev).unwrap();
+        }
+
+        assert_eq!(router.vault_for("error").all(), &[boom]);
+        assert_eq!(router.fallback().all(), &[hello, slow]);
+    }
+
+    #[test]
+    fn test_rate_limit_keeps_noisy_source_in_check() {
+        let mut vault = Vaultline::new_in_memory();
+        vault.set_rate_limit(Some(RateLimit { per_sec: 10, sample_every: 50 }));
+        let burst = |source: &'static str, second: u128| (0..100).map(move |i| {
+            let mut ev = Event::now(source, "error", "connection refused");
+            ev.ts_ms = second * 1000 + i;
+            ev
+        });
+        vault.append_batch(burst("net", 1).chain(burst("auth", 1).take(5)).collect()).unwrap();
+        assert_eq!(vault.query(Filter { source: Some("net".into()), ..Filter::default() }).count(), 12);
+        assert_eq!(vault.dropped_counts()["net"], 88);
+        vault.append_batch(burst("net", 2).take(1).collect()).unwrap();
+        let last = vault.all().iter().rev().nth(1).unwrap();
+        assert_eq!((last.message.as_str(), &last.kv["dropped"]), ("dropped 88 events", &serde_json::json!(88)));
+        assert_eq!(vault.all().len(), 19);
+    }
+
+    #[test]
+    fn test_rollup_collapses_retry_storm() {
+        let path = std::env::temp_dir().join(format!("vaultline_rollup_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut vault = Vaultline::new(&path).unwrap();
+        vault.set_rollup_window(Some(Duration::from_secs(5)));
+        let now = Event::now("epoch", "warn", "retry").ts_ms;
+        for i in 0..1000 {
+            let mut ev = Event::now("epoch", "warn", "retry");
+            ev.ts_ms = now + i;
+            vault.append(ev).unwrap();
+        }
+        assert!(vault.all().is_empty());
+        assert_eq!(vault.flush_rollups().unwrap(), 1);
+        vault.flush().unwrap();
+        let stored: Vec<Event> = std::fs::read_to_string(&path).unwrap().lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].kv["count"], 1000);
+
+        // Dropping the vaultline stores what is still rolled up.
+        let mut dropped = Vaultline::new(&path).unwrap();
+        dropped.set_rollup_window(Some(Duration::from_secs(5)));
+        dropped.append(Event::now("epoch", "warn", "retry")).unwrap();
+        dropped.append(Event::now("epoch", "warn", "retry")).unwrap();
+        drop(dropped);
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 2);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_hooks_enrich_and_filter_in_order() {
+        let mut vault = Vaultline::new_in_memory();
+        vault.add_hook(|ev| Some(ev.with("pid", std::process::id())));
+        vault.add_hook(|ev| (ev.message != "heartbeat").then_some(ev));
+        let mut seen = 0;
+        vault.add_hook(move |ev| {
+            seen += 1;
+            Some(ev.with("nth", seen))
+        });
+        vault.append_batch(vec![
+            Event::now("axiom", "info", "heartbeat"),
+            Event::now("axiom", "info", "started"),
+            Event::now("epoch", "info", "tick"),
+        ]).unwrap();
+        let stored = vault.all();
+        assert_eq!(stored.len(), 2);
+        assert_eq!(stored[0].kv["pid"], std::process::id());
+        assert_eq!((&stored[0].kv["nth"], &stored[1].kv["nth"]), (&1.into(), &2.into()));
+        assert_eq!(vault.dropped_counts()["axiom"], 1);
+    }
+
+    #[test]
+    fn test_open_read_only_never_writes() {
+        let dir = std::env::temp_dir().join(format!("vaultline_read_only_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("event.log");
+        assert!(Vaultline::open_read_only(&path).is_err());
+        assert!(!dir.exists());
+
+        let mut live = Vaultline::new(&path).unwrap();
+        live.append(Event::now("axiom", "info", "live")).unwrap();
+        let before = std::fs::read(&path).unwrap();
+
+        let mut viewer = Vaultline::open_read_only(&path).unwrap();
+        viewer.set_retention(RetentionPolicy { max_age: None, max_events: Some(0) });
+        viewer.set_summaries(true);
+        assert_eq!(viewer.stats().unwrap().total, 1);
+        assert!(viewer.restore(&dir.join("missing.snapshot")).unwrap_err().to_string().contains("read-only"));
+        assert_eq!(viewer.load_from_disk().unwrap().loaded, 1);
+        assert!(viewer.append(Event::now("axiom", "info", "sneaky")).is_err());
+        assert!(viewer.rotate().is_err());
+        assert!(viewer.enforce_retention().is_err());
+        assert!(viewer.compact().is_err());
+        assert_eq!(std::fs::read(&path).unwrap(), before);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_tail_disk_reads_backwards() {
+        let path = std::env::temp_dir().join(format!("vaultline_tail_disk_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut vault = Vaultline::new(&path).unwrap();
+        // Long messages so the tail spans several read chunks.
+        let padding = "x".repeat(30_000);
+        for i in 0..20 {
+            let level = if i % 2 == 0 { "info" } else { "error" };
+            vault.append(Event::now("net", level, format!("{i} {padding}"))).unwrap();
+        }
+        OpenOptions::new().append(true).open(&path).unwrap().write_all(b"{\"torn").unwrap();
+        let numbers = |events: Vec<Event>| events.iter().map(|ev| ev.message.split(' ').next().unwrap().to_string()).collect::<Vec<_>>();
+        assert_eq!(numbers(vault.tail_disk(3).unwrap()), ["17", "18", "19"]);
+        let errors = Filter { level: Some("error".into()), ..Filter::default() };
+        assert_eq!(numbers(vault.tail_disk_filtered(2, errors).unwrap()), ["17", "19"]);
+        assert_eq!(vault.tail_disk(100).unwrap().len(), 20);
+        assert!(vault.tail_disk(0).unwrap().is_empty());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_load_report_describes_skipped_lines() {
+        let path = std::env::temp_dir().join(format!("vaultline_load_report_{}.log", std::process::id()));
+        let quarantine = sibling_path(&path, ".quarantine");
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&quarantine);
+        let mut vault = Vaultline::new(&path).unwrap();
+        vault.set_checksums(true);
+        vault.append(Event::now("auth", "info", "ok")).unwrap();
+        vault.append(Event::now("auth", "info", "flipped")).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap().replace("flipped", "flopped");
+        std::fs::write(&path, format!("{text}{{not json\n")).unwrap();
+
+        let mut reader = Vaultline::new(&path).unwrap();
+        reader.set_quarantine_skipped(true);
+        let report = reader.load_from_disk().unwrap();
+        assert_eq!((report.loaded, report.skipped, report.first_error_line), (1, 2, Some(2)));
+        assert_eq!(report.errors[0], "line 2: checksum mismatch");
+        assert!(report.errors[1].starts_with("line 3: "), "{:?}", report.errors);
+        assert_eq!(std::fs::read_to_string(&quarantine).unwrap().lines().count(), 2);
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 3);
+        // Reloading skips the same lines without quarantining them twice.
+        let mut again = Vaultline::new(&path).unwrap();
+        again.set_quarantine_skipped(true);
+        assert_eq!(again.load_from_disk().unwrap().skipped, 2);
+        assert_eq!(std::fs::read_to_string(&quarantine).unwrap().lines().count(), 2);
+
+        // A record from a newer schema is reported, not fatal.
+        let future = serde_json::json!({ "schema_version": 99, "ts_ms": 5, "source": "s", "level": "info", "message": "m" });
+        std::fs::write(&path, format!("{future}\n")).unwrap();
+        let report = Vaultline::new(&path).unwrap().load_from_disk().unwrap();
+        assert_eq!((report.loaded, report.skipped), (0, 1));
+        assert!(report.errors[0].contains("schema"), "{:?}", report.errors);
+
+        // With checksums on, a line whose CRC was stripped fails closed.
+        let bare = serde_json::to_string(&Event::now("auth", "info", "no crc")).unwrap();
+        std::fs::write(&path, format!("{}\n{bare}\n", text.lines().next().unwrap())).unwrap();
+        let mut strict = Vaultline::new(&path).unwrap();
+        strict.set_checksums(true);
+        let report = strict.load_from_disk().unwrap();
+        assert_eq!((report.loaded, report.skipped), (1, 1));
+        assert_eq!(report.errors, ["line 2: missing checksum"]);
+        assert_eq!(strict.verify().unwrap().checksum_failures, 1);
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&quarantine);
+    }
+
+    #[test]
+    fn test_ttl_hides_and_compacts_expired_events() {
+        let path = std::env::temp_dir().join(format!("vaultline_ttl_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut vault = Vaultline::new(&path).unwrap();
+        vault.append(Event::now("audit", "info", "granted role")).unwrap();
+        vault.append(Event::now("net", "debug", "probe").with_ttl(Duration::ZERO)).unwrap();
+        vault.append(Event::now("net", "debug", "retry").with_ttl(Duration::from_secs(3600))).unwrap();
+        assert_eq!(vault.all().len(), 3);
+        let visible: Vec<&str> = vault.query(Filter::default()).map(|ev| ev.message.as_str()).collect();
+        assert_eq!(visible, ["granted role", "retry"]);
+        assert_eq!(vault.tail(2).iter().map(|ev| ev.message.as_str()).collect::<Vec<_>>(), ["granted role", "retry"]);
+        assert_eq!(vault.tail_disk(2).unwrap().len(), 2);
+
+        let stats = vault.compact().unwrap();
+        assert_eq!((stats.kept, stats.expired), (2, 1));
+        assert!(!std::fs::read_to_string(&path).unwrap().contains("probe"));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_merged_interleaves_nodes_and_drops_copies() {
+        let dir = std::env::temp_dir().join(format!("vaultline_merged_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let at = |node: &str, ts_ms: u128| {
+            let mut ev = Event::now(node, "info", format!("{node}@{ts_ms}"));
+            ev.ts_ms = ts_ms;
+            ev
         };
-        Vaultline::normalize_event(&mut ev);
-        assert_eq!(ev.source, "unknown");
-        assert_eq!(ev.level, "info");
-        assert_eq!(ev.message, "(no message)");
-        assert!(ev.ts_ms > 0);
-        assert!(ev.kv.is_object() && ev.kv.as_object().unwrap().is_empty());
+        let (a, b) = (dir.join("a.log"), dir.join("b.log"));
+        let mut node_a = Vaultline::new(&a).unwrap();
+        node_a.set_sequencing(true);
+        node_a.append_batch(vec![at("a", 10), at("a", 30)]).unwrap();
+        node_a.rotate().unwrap();
+        node_a.append(at("a", 50)).unwrap();
+        let mut node_b = Vaultline::new(&b).unwrap();
+        node_b.set_sequencing(true);
+        // b also collected a copy of one of a's records.
+        node_b.append_batch(vec![at("b", 20), at("b", 30), at("b", 40)]).unwrap();
+        std::fs::write(dir.join("a_copy.log"), std::fs::read(&a).unwrap()).unwrap();
+
+        let merged = Vaultline::merged(&[a, b, dir.join("a_copy.log")]).unwrap();
+        let messages: Vec<&str> = merged.all().iter().map(|ev| ev.message.as_str()).collect();
+        assert_eq!(messages, ["a@10", "b@20", "a@30", "b@30", "b@40", "a@50"]);
+        assert_eq!(merged.all().iter().map(|ev| ev.seq).collect::<Vec<_>>(), [1, 2, 3, 4, 5, 6]);
+        let after: Vec<&str> = merged.since(3).iter().map(|ev| ev.message.as_str()).collect();
+        assert_eq!(after, ["b@30", "b@40", "a@50"]);
+        assert_eq!(merged.query(Filter { source: Some("b".into()), ..Filter::default() }).count(), 3);
+        assert!(Vaultline::merged(&[dir.join("missing.log")]).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_erase_removes_subject_and_leaves_tombstone() {
+        let path = std::env::temp_dir().join(format!("vaultline_erase_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        for archive in Vaultline::new(&path).unwrap().archives().unwrap() {
+            let _ = std::fs::remove_file(archive);
+        }
+        let mut vault = Vaultline::new(&path).unwrap();
+        vault.append(Event::now("auth", "info", "login").with("user_id", 42)).unwrap();
+        vault.append(Event::now("auth", "info", "login").with("user_id", 7)).unwrap();
+        let archive = vault.rotate().unwrap().unwrap();
+        let untouched = std::fs::read(&archive).unwrap();
+        vault.append(Event::now("auth", "info", "logout").with("user_id", 7)).unwrap();
+        vault.append(Event::now("billing", "info", "invoice").with("user_id", 7)).unwrap();
+        vault.append(Event::now("auth", "info", "logout").with("user_id", 42)).unwrap();
+
+        assert!(vault.erase(&Filter::default()).is_err());
+        let report = vault.erase(&Filter::new().kv("user_id", 7)).unwrap();
+        assert_eq!(report, EraseReport { erased: 3, segments_rewritten: 2 });
+        assert!(vault.query(Filter::new().kv("user_id", 7)).next().is_none());
+
+        let mut reader = Vaultline::new(&path).unwrap();
+        let remaining: Vec<Event> = reader.iter_disk().unwrap().map(Result::unwrap).collect();
+        assert_eq!(remaining.len(), 3);
+        let tombstone = &remaining[2];
+        assert_eq!((tombstone.message.as_str(), &tombstone.kv["erased"]), ("erased 3 events", &serde_json::json!(3)));
+        assert_eq!(tombstone.kv["fields"], serde_json::json!(["kv.user_id"]));
+        assert!(tombstone.kv.get("filter").is_none());
+        assert_ne!(std::fs::read(&archive).unwrap(), untouched);
+        let _ = std::fs::remove_file(&archive);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_erase_scrubs_sidecars() {
+        let path = std::env::temp_dir().join(format!("vaultline_erase_sidecars_{}.log", std::process::id()));
+        let (quarantine, keys) = (sibling_path(&path, ".quarantine"), sibling_path(&path, ".keys"));
+        for p in [&path, &quarantine, &keys] {
+            let _ = std::fs::remove_file(p);
+        }
+        let mut vault = Vaultline::new(&path).unwrap();
+        vault.set_checksums(true);
+        vault.append(Event::now("auth", "info", "login").with("user_id", 7).with_idempotency_key("login-7")).unwrap();
+        vault.append(Event::now("auth", "info", "login").with("user_id", 42)).unwrap();
+        // A record with a bad checksum still parses, so it can be matched once quarantined.
+        let raw = std::fs::read_to_string(&path).unwrap().replacen("\"login\"", "\"logon\"", 1);
+        std::fs::write(&path, raw).unwrap();
+        let mut vault = Vaultline::new(&path).unwrap();
+        vault.set_checksums(true);
+        assert_eq!(vault.load_with_recovery().unwrap().quarantined, 1);
+        vault.append(Event::now("auth", "info", "logout").with("user_id", 7).with_idempotency_key("logout-7")).unwrap();
+        assert!(std::fs::read_to_string(&keys).unwrap().contains("logout-7"));
+
+        let report = vault.erase(&Filter::new().kv("user_id", 7)).unwrap();
+        assert_eq!(report.erased, 2);
+        assert!(std::fs::read_to_string(&quarantine).unwrap().is_empty());
+        assert!(!keys.exists());
+        // The erased key is free again once the index is rebuilt.
+        vault.append(Event::now("auth", "info", "logout").with_idempotency_key("logout-7")).unwrap();
+        assert_eq!(vault.query(Filter::new().contains("logout")).count(), 1);
+
+        let bin = path.with_extension("bin");
+        let _ = std::fs::remove_file(&bin);
+        let mut binary = Vaultline::new_with_format(&bin, Format::Binary).unwrap();
+        binary.append(Event::now("auth", "info", "login").with("user_id", 7)).unwrap();
+        assert!(binary.erase(&Filter::new().kv("user_id", 7)).is_err());
+        assert_eq!(binary.all().len(), 1);
+        for p in [&path, &quarantine, &keys, &bin] {
+            let _ = std::fs::remove_file(p);
+        }
+    }
+
+    #[test]
+    fn test_dropped_counts() {
+        let mut vault = Vaultline::new_in_memory();
+        vault.set_level_sampling("info", 3);
+        for i in 0..6 {
+            vault.append(Event::now("noisy", "info", format!("tick {i}"))).unwrap();
+        }
+        vault.append(Event::now("noisy", "error", "kept")).unwrap();
+
+        assert_eq!(vault.all().len(), 3);
+        assert_eq!(vault.dropped_counts().get("noisy"), Some(&4));
+    }
+
+    #[test]
+    fn test_require_kv() {
+        let mut vault = Vaultline::new_in_memory();
+        vault.require_kv("auth", vec!["user_id".to_string()]);
+
+        let err = vault.append(Event::now("auth", "info", "login")).unwrap_err();
+        assert!(err.to_string().contains("user_id"));
+
+        let mut ev = Event::now("auth", "info", "login");
+        ev.kv = serde_json::json!({ "user_id": 42 });
+        vault.append(ev).unwrap();
+        vault.append(Event::now("epoch", "info", "no kv needed")).unwrap();
+        assert_eq!(vault.all().len(), 2);
+    }
+
+    #[test]
+    fn test_rotation_keeps_max_archives() {
+        let dir = std::env::temp_dir().join(format!("vaultline_rotate_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let log_path = dir.join("event.log");
+        let mut vault = Vaultline::new(&log_path).unwrap();
+        vault.set_max_archives(2);
+
+        let mut rotated = Vec::new();
+        for i in 0..3 {
+            vault.append(Event::now("src", "info", format!("segment {i}"))).unwrap();
+            rotated.push(vault.rotate().unwrap().unwrap());
+        }
+
+        assert_eq!(vault.archives().unwrap(), rotated[1..]);
+        assert!(log_path.exists());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 3);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_buffered_appends_flush_by_policy() {
+        let path = std::env::temp_dir().join(format!("vaultline_flush_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut vault = Vaultline::new(&path).unwrap();
+        vault.set_flush_policy(FlushPolicy { every_n: 3, interval: None });
+        let on_disk = || std::fs::read_to_string(&path).unwrap().lines().count();
+
+        vault.append(Event::now("src", "info", "one")).unwrap();
+        vault.append(Event::now("src", "info", "two")).unwrap();
+        assert_eq!(on_disk(), 0);
+        vault.append(Event::now("src", "info", "three")).unwrap();
+        assert_eq!(on_disk(), 3);
+        vault.append(Event::now("src", "info", "four")).unwrap();
+        vault.flush().unwrap();
+        assert_eq!(on_disk(), 4);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_durability_policy_from_config() {
+        let cfg: VaultConfig = toml::from_str("durability = { fsync_interval_ms = 250 }").unwrap();
+        assert_eq!(cfg.durability, Some(DurabilityPolicy::FsyncInterval(Duration::from_millis(250))));
+        let cfg: VaultConfig = toml::from_str("durability = { flush_every_n = 8 }").unwrap();
+
+        let path = std::env::temp_dir().join(format!("vaultline_durability_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut vault = Vaultline::new(&path).unwrap();
+        vault.apply_config(&cfg).unwrap();
+        vault.append(Event::now("src", "info", "buffered")).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+
+        vault.set_durability(DurabilityPolicy::FsyncEveryWrite);
+        vault.append(Event::now("src", "info", "synced")).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 2);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_default_store_is_the_file_store() {
+        let path = std::env::temp_dir().join(format!("vaultline_default_store_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut vault = Vaultline::new(&path).unwrap();
+        vault.append(Event::now("src", "info", "stored")).unwrap();
+        assert_eq!(vault.event_store().unwrap().scan().unwrap(), vault.all());
+
+        let mut swapped = Vaultline::with_store(FileStore::open(&path).unwrap());
+        assert_eq!(swapped.load_from_disk().unwrap().loaded, 1);
+        assert_eq!(swapped.all(), vault.all());
+        assert!(Vaultline::new_in_memory().event_store().is_none());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_file_store_round_trip() {
+        let path = std::env::temp_dir().join(format!("vaultline_file_store_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut vault = Vaultline::with_store(FileStore::open(&path).unwrap());
+        vault.append(Event::now("src", "info", "stored")).unwrap();
+
+        let mut reloaded = Vaultline::with_store(FileStore::open(&path).unwrap());
+        assert_eq!(reloaded.load_from_disk().unwrap().loaded, 1);
+        assert_eq!(reloaded.all(), vault.all());
+
+        let mut store = FileStore::open(&path).unwrap();
+        store.replace(&[]).unwrap();
+        assert!(store.scan().unwrap().is_empty());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_with_store_persists_through_store() {
+        let path = std::env::temp_dir().join(format!("vaultline_store_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut vault = Vaultline::with_store(SqliteStore::open(&path).unwrap());
+        vault.append(Event::now("src", "info", "stored")).unwrap();
+
+        let mut reloaded = Vaultline::with_store(SqliteStore::open(&path).unwrap());
+        assert_eq!(reloaded.load_from_disk().unwrap().loaded, 1);
+        assert_eq!(reloaded.all(), vault.all());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_compact_through_store() {
+        let mut vault = Vaultline::with_store(MemoryStore::new());
+        let mut old = Event::now("epoch", "info", "old");
+        old.ts_ms = 1;
+        vault.append(old).unwrap();
+        let recent = Event::now("epoch", "info", "recent");
+        vault.append(recent.clone()).unwrap();
+
+        let stats = vault.compact_with(&CompactOptions { before_ms: Some(2), ..Default::default() }).unwrap();
+        assert_eq!(stats, CompactStats { kept: 1, expired: 1, ..Default::default() });
+        assert_eq!(vault.all(), std::slice::from_ref(&recent));
+        assert_eq!(vault.event_store().unwrap().scan().unwrap(), [recent]);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_store_is_queryable() {
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        store.append_batch(&[Event::now("auth", "error", "denied"), Event::now("epoch", "info", "done")]).unwrap();
+        let errors: i64 = store.connection()
+            .query_row("SELECT COUNT(*) FROM events WHERE level = 'error'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(errors, 1);
+
+        let mut vault = Vaultline::with_store(store);
+        assert_eq!(vault.load_from_disk().unwrap().loaded, 2);
+        assert_eq!(vault.all()[1].source, "epoch");
+
+        // A database from the first release gains the columns added since.
+        let path = std::env::temp_dir().join(format!("vaultline_sqlite_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        rusqlite::Connection::open(&path).unwrap().execute_batch(
+            "CREATE TABLE events (id INTEGER PRIMARY KEY AUTOINCREMENT, ts_ms INTEGER NOT NULL, source TEXT NOT NULL,
+                level TEXT NOT NULL, message TEXT NOT NULL, kv TEXT NOT NULL);
+             INSERT INTO events (ts_ms, source, level, message, kv) VALUES (1, 'auth', 'info', 'old', 'null');",
+        ).unwrap();
+        let mut store = SqliteStore::open(&path).unwrap();
+        store.append_batch(&[Event::now("auth", "info", "new").with_ttl(Duration::from_secs(60))]).unwrap();
+        assert_eq!(store.scan().unwrap().iter().map(|ev| ev.message.as_str()).collect::<Vec<_>>(), ["old", "new"]);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_binary_format_skips_torn_tail() {
+        let path = std::env::temp_dir().join(format!("vaultline_bin_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut vault = Vaultline::new_with_format(&path, Format::Binary).unwrap();
+        vault.append(Event::now("src", "info", "one")).unwrap();
+        vault.append(Event::now("src", "warn", "two")).unwrap();
+        let len = std::fs::metadata(&path).unwrap().len();
+        std::fs::OpenOptions::new().write(true).open(&path).unwrap().set_len(len - 3).unwrap();
+
+        let mut reloaded = Vaultline::new_with_format(&path, Format::Binary).unwrap();
+        assert_eq!(reloaded.iter_disk().unwrap().count(), 1);
+        assert_eq!(reloaded.load_from_disk().unwrap().loaded, 1);
+        assert_eq!(reloaded.all()[0].message, "one");
+        assert!(reloaded.compact().is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_recovery_quarantines_corrupt_and_torn_lines() {
+        let path = std::env::temp_dir().join(format!("vaultline_crc_{}.log", std::process::id()));
+        let quarantine = sibling_path(&path, ".quarantine");
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&quarantine);
+        let mut vault = Vaultline::new(&path).unwrap();
+        vault.set_checksums(true);
+        for msg in ["one", "two", "three"] {
+            vault.append(Event::now("src", "info", msg)).unwrap();
+        }
+        // Flip a byte inside the second record and tear off the end of the third.
+        let mut raw = std::fs::read_to_string(&path).unwrap().replacen("\"two\"", "\"twx\"", 1);
+        raw.truncate(raw.len() - 10);
+        std::fs::write(&path, &raw).unwrap();
+
+        let mut reloaded = Vaultline::new(&path).unwrap();
+        let report = reloaded.load_with_recovery().unwrap();
+        assert_eq!(report, RecoveryReport { loaded: 1, quarantined: 2, torn_tail: true });
+        assert_eq!(reloaded.all()[0].message, "one");
+        assert_eq!(std::fs::read_to_string(&quarantine).unwrap().lines().count(), 2);
+        assert_eq!(Vaultline::new(&path).unwrap().load_from_disk().unwrap().loaded, 1);
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&quarantine);
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_encrypted_records_and_key_rotation() {
+        let path = std::env::temp_dir().join(format!("vaultline_enc_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let (old_key, new_key) = ([7u8; 32], [9u8; 32]);
+        let mut vault = Vaultline::new(&path).unwrap();
+        vault.set_encryption_key(&old_key);
+        vault.append(Event::now("auth", "info", "password reset for alice")).unwrap();
+
+        let raw = std::fs::read_to_string(&path).unwrap();
+        assert!(raw.starts_with("enc:") && !raw.contains("alice"));
+        let report = Vaultline::new(&path).unwrap().load_from_disk().unwrap();
+        assert_eq!((report.loaded, report.skipped), (0, 1));
+
+        assert_eq!(vault.rotate_encryption_key(&new_key).unwrap(), 1);
+        let mut stale = Vaultline::new(&path).unwrap();
+        stale.set_encryption_key(&old_key);
+        assert_eq!(stale.load_from_disk().unwrap().skipped, 1);
+
+        let mut reader = Vaultline::new(&path).unwrap();
+        reader.set_encryption_key(&new_key);
+        assert_eq!(reader.load_from_disk().unwrap().loaded, 1);
+        assert_eq!(reader.all()[0].message, "password reset for alice");
+
+        // Verifying with the wrong key reports the line instead of giving up.
+        let report = stale.verify().unwrap();
+        assert_eq!((report.records, report.malformed), (0, 1));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_compressed_archives_load_transparently() {
+        let dir = std::env::temp_dir().join(format!("vaultline_gzip_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut vault = Vaultline::new(dir.join("event.log")).unwrap();
+        vault.set_compress_archives(true);
+        vault.append(Event::now("src", "info", "archived")).unwrap();
+
+        let archive = vault.rotate().unwrap().unwrap();
+        assert!(archive.to_string_lossy().ends_with(".gz"));
+        assert_eq!(vault.archives().unwrap(), vec![archive.clone()]);
+        assert_ne!(std::fs::read(&archive).unwrap()[..2], *b"{\"");
+
+        let mut reader = Vaultline::new(&archive).unwrap();
+        assert_eq!(reader.load_from_disk().unwrap().loaded, 1);
+        assert_eq!(reader.all()[0].message, "archived");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_archived_segments_are_fetched_for_queries() {
+        let dir = std::env::temp_dir().join(format!("vaultline_archive_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut vault = Vaultline::new(dir.join("local/event.log")).unwrap();
+        let bucket = DirObjectStore::new(dir.join("bucket")).unwrap();
+        vault.set_archiver(Archiver::new(bucket, "hyperion/", dir.join("cache")));
+
+        let mut old = Event::now("epoch", "error", "job 1 failed");
+        old.ts_ms -= 86_400_000;
+        vault.append(old.clone()).unwrap();
+        let archive = vault.rotate().unwrap().unwrap();
+        vault.append(Event::now("epoch", "info", "still hot")).unwrap();
+
+        assert!(!archive.exists());
+        assert!(vault.archives().unwrap().is_empty());
+        let name = archive.file_name().unwrap().to_string_lossy().into_owned();
+        assert!(dir.join("bucket/hyperion").join(&name).exists());
+
+        assert_eq!(vault.query_archived(&Filter::new().level("error")).unwrap(), [old]);
+        assert!(dir.join("cache").join(&name).exists());
+        let stamp: u128 = name.rsplit('.').next().unwrap().parse().unwrap();
+        assert!(vault.query_archived(&Filter::new().since(stamp + 1)).unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_metrics_survive_memory_cap() {
+        let mut vault = Vaultline::new_in_memory();
+        vault.set_memory_cap(2);
+        vault.enable_metrics();
+        for level in ["info", "info", "error", "info", "warn"] {
+            vault.append(Event::now("src", level, "event")).unwrap();
+        }
+
+        assert_eq!(vault.all().len(), 2);
+        let metrics = vault.metric_counts();
+        assert_eq!((metrics["info"], metrics["error"], metrics["warn"]), (3, 1, 1));
+        let buffered = vault.level_counts();
+        assert_eq!(buffered.get("info"), Some(&1));
+        assert_eq!(buffered.get("error"), None);
+    }
+
+    #[test]
+    fn test_memory_cap_from_config_keeps_newest() {
+        let path = std::env::temp_dir().join(format!("vaultline_memcap_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut vault = Vaultline::new(&path).unwrap();
+        vault.apply_config(&VaultConfig { memory_cap: Some(3), ..Default::default() }).unwrap();
+        for i in 0..10 {
+            vault.append(Event::now("src", "info", format!("event {i}"))).unwrap();
+        }
+
+        let kept: Vec<&str> = vault.all().iter().map(|ev| ev.message.as_str()).collect();
+        assert_eq!(kept, ["event 7", "event 8", "event 9"]);
+        assert_eq!(vault.by_source("src").count(), 3);
+        assert_eq!(Vaultline::new(&path).unwrap().load_from_disk().unwrap().loaded, 10);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_source_aliases() {
+        let mut vault = Vaultline::new_in_memory();
+        vault.set_source_aliases(HashMap::from([("old-auth".to_string(), "auth".to_string())]));
+        vault.append(Event::now("old-auth", "info", "login")).unwrap();
+        vault.append(Event::now("epoch", "info", "tick")).unwrap();
+
+        assert_eq!(vault.all()[0].source, "auth");
+        assert_eq!(vault.all()[1].source, "epoch");
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut vault = Vaultline::new_in_memory();
+        for level in ["info", "error", "warn", "error", "debug"] {
+            vault.append(Event::now("src", level, "event")).unwrap();
+        }
+
+        assert_eq!(vault.retain(|ev| ev.level == "error"), 3);
+        assert_eq!(vault.all().len(), 2);
+        assert!(vault.all().iter().all(|ev| ev.level == "error"));
+    }
+
+    #[test]
+    fn test_omit_null_kv() {
+        let log_path = std::env::temp_dir().join(format!("vaultline_nullkv_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&log_path);
+        let mut vault = Vaultline::new(&log_path).unwrap();
+        vault.set_omit_null_kv(true);
+        let ev = Event::now("src", "info", "no kv");
+        vault.append(ev.clone()).unwrap();
+
+        let written = std::fs::read_to_string(&log_path).unwrap();
+        let line: serde_json::Value = serde_json::from_str(written.trim()).unwrap();
+        assert!(line.get("kv").is_none());
+
+        let mut reloaded = Vaultline::new(&log_path).unwrap();
+        reloaded.load_from_disk().unwrap();
+        assert_eq!(reloaded.all(), &[ev]);
+        let _ = std::fs::remove_file(&log_path);
+    }
+
+    #[test]
+    fn test_size_rotation() {
+        let dir = std::env::temp_dir().join(format!("vaultline_size_rotate_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let log_path = dir.join("event.log");
+        let mut vault = Vaultline::new(&log_path).unwrap();
+        vault.apply_config(&VaultConfig { rotate_bytes: Some(300), max_segments: Some(2), ..Default::default() }).unwrap();
+
+        for i in 0..12 {
+            vault.append(Event::now("src", "info", format!("a reasonably long message number {i}"))).unwrap();
+        }
+
+        assert_eq!(vault.archives().unwrap().len(), 2);
+        assert!(std::fs::metadata(&log_path).unwrap().len() < 300);
+        assert_eq!(vault.all().len(), 12);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_daily_rotation() {
+        assert_eq!(daily_archive_name("event", 19_875, 0), "event-2024-06-01.ndjson");
+        assert_eq!(daily_archive("event-2024-06-01-2.ndjson.gz"), Some(("event", 19_875, 2)));
+        assert_eq!(segment_stamp("event-2024-06-01.ndjson", "event.log"), Some(19_876 * DAY_MS));
+        assert_eq!(segment_stamp("other-2024-06-01.ndjson", "event.log"), None);
+
+        let dir = std::env::temp_dir().join(format!("vaultline_daily_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let log_path = dir.join("event.log");
+        let today = now_ms() / DAY_MS;
+        let archive_for = |day: u128, n: u128| {
+            let plain = dir.join(daily_archive_name("event", day, n));
+            if cfg!(feature = "compression") { sibling_path(&plain, ".gz") } else { plain }
+        };
+
+        // A log last written two days ago rolls over on the first append after a restart.
+        let mut vault = Vaultline::new(&log_path).unwrap();
+        vault.append(Event::now("src", "info", "old")).unwrap();
+        vault.flush().unwrap();
+        let two_days_ago = SystemTime::now() - Duration::from_millis(2 * DAY_MS as u64);
+        std::fs::File::options().append(true).open(&log_path).unwrap().set_modified(two_days_ago).unwrap();
+        let mut vault = Vaultline::new(&log_path).unwrap();
+        vault.apply_config(&VaultConfig { rotate_daily: true, ..Default::default() }).unwrap();
+        vault.append(Event::now("src", "info", "yesterday")).unwrap();
+        assert!(archive_for(today - 2, 0).exists());
+
+        // Pretend the active file was last written yesterday; so was an archive already.
+        vault.active_day = Some(today - 1);
+        std::fs::write(dir.join(daily_archive_name("event", today - 1, 0)), "").unwrap();
+        vault.append(Event::now("src", "info", "today")).unwrap();
+        assert!(archive_for(today - 1, 1).exists());
+        assert_eq!(vault.archives().unwrap().len(), 3);
+        assert_eq!(vault.iter_disk().unwrap().count(), 3);
+        assert_eq!(vault.tail_disk(2).unwrap().iter().map(|ev| ev.message.as_str()).collect::<Vec<_>>(), ["yesterday", "today"]);
+        assert_eq!(vault.stats().unwrap().total, 3);
+        assert_eq!(vault.query_archived(&Filter::new().contains("yesterday")).unwrap().len(), 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(feature = "tar")]
+    #[test]
+    fn test_archive_before_moves_old_segments_to_tar() {
+        let dir = std::env::temp_dir().join(format!("vaultline_tar_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let log_path = dir.join("event.log");
+        let tar = dir.join(if cfg!(feature = "compression") { "old.tar.gz" } else { "old.tar" });
+        let mut vault = Vaultline::new(&log_path).unwrap();
+        let at = |message: &str, ts_ms: u128| {
+            let mut ev = Event::now("src", "info", message);
+            ev.ts_ms = ts_ms;
+            ev
+        };
+        vault.append_batch(vec![at("first", 1_000), at("second", 2_000)]).unwrap();
+        let first = vault.rotate().unwrap().unwrap();
+        vault.append_batch(vec![at("third", 3_000), at("fourth", 9_000)]).unwrap();
+        let straddling = vault.rotate().unwrap().unwrap();
+        vault.append(at("active", 1)).unwrap();
+
+        assert_eq!(vault.archive_before(5_000, &tar).unwrap(), std::slice::from_ref(&first));
+        assert!(!first.exists() && straddling.exists() && tar.exists());
+        assert_eq!(vault.archives().unwrap(), [straddling]);
+        assert!(vault.archive_before(5_000, &tar).is_err());
+
+        let archived = vault.query_tar(&tar, &Filter::new()).unwrap();
+        let messages: Vec<&str> = archived.iter().map(|ev| ev.message.as_str()).collect();
+        assert_eq!(messages, ["first", "second"]);
+        assert_eq!(vault.query_tar(&tar, &Filter::new().contains("second")).unwrap().len(), 1);
+        assert!(vault.query_tar(&tar, &Filter::new().since(u128::MAX)).unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_retention_across_segments() {
+        let dir = std::env::temp_dir().join(format!("vaultline_retention_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let log_path = dir.join("event.log");
+        let mut vault = Vaultline::new(&log_path).unwrap();
+
+        let mut ancient = Event::now("src", "info", "ancient");
+        ancient.ts_ms -= 30 * 86_400_000;
+        vault.append(ancient).unwrap();
+        vault.rotate().unwrap();
+        for i in 0..3 {
+            vault.append(Event::now("src", "info", format!("old segment {i}"))).unwrap();
+        }
+        vault.rotate().unwrap();
+        for i in 0..3 {
+            vault.append(Event::now("src", "info", format!("active {i}"))).unwrap();
+        }
+
+        vault.set_retention(RetentionPolicy { max_age: Some(Duration::from_secs(86_400)), max_events: Some(4) });
+        assert_eq!(vault.enforce_retention().unwrap(), 3);
+
+        // The ancient archive is gone, the next one is truncated to its newest event.
+        let archives = vault.archives().unwrap();
+        assert_eq!(archives.len(), 1);
+        let kept: Vec<String> = vault.read_segment(&archives[0]).unwrap().into_iter().map(|(_, ev)| ev.message).collect();
+        assert_eq!(kept, ["old segment 2"]);
+        assert_eq!(vault.read_segment(&log_path).unwrap().len(), 3);
+        assert_eq!(vault.all().len(), 4);
+        assert_eq!(vault.all()[0].message, "old segment 2");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_retention_drops_stale_archives_by_mtime() {
+        let dir = std::env::temp_dir().join(format!("vaultline_retention_mtime_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut vault = Vaultline::new(dir.join("event.log")).unwrap();
+        vault.append(Event::now("src", "info", "stale")).unwrap();
+        let stale = vault.rotate().unwrap().unwrap();
+        vault.append(Event::now("src", "info", "fresh")).unwrap();
+        let fresh = vault.rotate().unwrap().unwrap();
+        // Last written two days ago, so nothing in it can be inside a one-day window.
+        let two_days_ago = SystemTime::now() - Duration::from_secs(2 * 86_400);
+        OpenOptions::new().write(true).open(&stale).unwrap().set_modified(two_days_ago).unwrap();
+
+        vault.set_retention(RetentionPolicy { max_age: Some(Duration::from_secs(86_400)), max_events: None });
+        assert_eq!(vault.enforce_retention().unwrap(), 1);
+        assert_eq!(vault.archives().unwrap(), std::slice::from_ref(&fresh));
+
+        // The fresh archive is recorded as fitting, so the next run leaves it unread and untouched.
+        assert_eq!(vault.segment_ranges[&fresh].events, 1);
+        let modified = std::fs::metadata(&fresh).unwrap().modified().unwrap();
+        vault.append(Event::now("src", "info", "active")).unwrap();
+        vault.rotate().unwrap();
+        assert_eq!(vault.enforce_retention().unwrap(), 0);
+        assert_eq!(std::fs::metadata(&fresh).unwrap().modified().unwrap(), modified);
+        assert_eq!(vault.segment_ranges.len(), 3);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_compact_dedupe_and_level_filter() {
+        let log_path = std::env::temp_dir().join(format!("vaultline_compact_dedupe_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&log_path);
+        let mut vault = Vaultline::new(&log_path).unwrap();
+        let retry = Event::now("epoch", "warn", "retrying job 7");
+        for _ in 0..3 {
+            vault.append(retry.clone()).unwrap();
+        }
+        vault.append(Event::now("epoch", "debug", "tick")).unwrap();
+        let boom = Event::now("epoch", "error", "job 7 failed");
+        vault.append(boom.clone()).unwrap();
+
+        let opts = CompactOptions { dedupe: true, min_level: Some("info".into()), ..Default::default() };
+        let stats = vault.compact_with(&opts).unwrap();
+        assert_eq!(stats, CompactStats { kept: 2, duplicates: 2, filtered: 1, ..Default::default() });
+        assert_eq!(stats.removed(), 3);
+        assert_eq!(vault.all(), &[retry.clone(), boom.clone()]);
+
+        let mut reloaded = Vaultline::new(&log_path).unwrap();
+        reloaded.load_from_disk().unwrap();
+        assert_eq!(reloaded.all(), &[retry, boom]);
+        let _ = std::fs::remove_file(&log_path);
+    }
+
+    #[test]
+    fn test_import_merges_in_timestamp_order() {
+        let dir = std::env::temp_dir().join(format!("vaultline_import_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let at = |ts_ms, message: &str| Event { ts_ms, ..Event::now("epoch", "info", message) };
+        let mut vault = Vaultline::new(dir.join("event.log")).unwrap();
+        vault.set_sequencing(true);
+        vault.set_hash_chain(true);
+        vault.set_redactor(Some(Redactor::new(&["token"], &[] as &[&str]).unwrap()));
+        vault.append(at(10, "local first")).unwrap();
+        vault.append(at(30, "shared")).unwrap();
+
+        // The other host numbered its own log: seq 1 there is a different event.
+        let mut other = String::new();
+        for (seq, ev) in [(2, at(30, "shared")), (1, at(20, "remote").with("token", "s3cret")), (1, at(20, "remote"))] {
+            other.push_str(&serde_json::to_string(&Event { seq, ..ev }).unwrap());
+            other.push('\n');
+        }
+        other.push_str("{not json\n");
+        std::fs::write(dir.join("other.log"), other).unwrap();
+
+        let stats = vault.import(&dir.join("other.log")).unwrap();
+        assert_eq!(stats, ImportStats { imported: 1, duplicates: 2, malformed: 1 });
+        let messages = |events: &[Event]| events.iter().map(|ev| ev.message.clone()).collect::<Vec<_>>();
+        assert_eq!(messages(vault.all()), ["local first", "remote", "shared"]);
+        assert_eq!(messages(vault.since(1)), ["remote", "shared"]);
+
+        let mut reloaded = Vaultline::new(dir.join("event.log")).unwrap();
+        reloaded.load_from_disk().unwrap();
+        assert_eq!(messages(reloaded.all()), ["local first", "remote", "shared"]);
+        assert_eq!(reloaded.all().iter().map(|ev| ev.seq).collect::<Vec<_>>(), [1, 2, 3]);
+        assert_ne!(reloaded.all()[1].kv["token"], "s3cret");
+        assert!(reloaded.verify().unwrap().is_intact());
+        vault.append(at(40, "after")).unwrap();
+        assert!(reloaded.verify().unwrap().is_intact());
+        assert_eq!(vault.all().last().unwrap().seq, 4);
+        let _ = std::fs::remove_dir_all(&dir);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_snapshot_restore_round_trip() {
+        let dir = std::env::temp_dir().join(format!("vaultline_snapshot_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut vault = Vaultline::new(dir.join("event.log")).unwrap();
+        vault.append(Event::now("epoch", "info", "archived")).unwrap();
+        vault.rotate().unwrap();
+        vault.append(Event::now("epoch", "info", "active")).unwrap();
+        let expected = vault.all().to_vec();
+
+        let snap = dir.join("backup.snap");
+        let meta = vault.snapshot(&snap).unwrap();
+        assert_eq!((meta.segments, meta.events), (2, 2));
+        assert!(vault.snapshot(&snap).is_err());
+        assert!(vault.restore(&snap).is_err());
+
+        let mut restored = Vaultline::new_with_format(dir.join("moved.bin"), Format::Binary).unwrap();
+        assert_eq!(restored.restore(&snap).unwrap(), meta);
+        assert_eq!(restored.all(), expected.as_slice());
+        let mut reloaded = Vaultline::new_with_format(dir.join("moved.bin"), Format::Binary).unwrap();
+        reloaded.load_from_disk().unwrap();
+        assert_eq!(reloaded.all(), expected.as_slice());
+
+        let text = std::fs::read_to_string(&snap).unwrap();
+        std::fs::write(&snap, text.replace("active", "edited")).unwrap();
+        let err = Vaultline::new_in_memory().restore(&snap).unwrap_err();
+        assert!(err.to_string().contains("corrupt"), "{err}");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_idempotency_key_skips_retried_appends() {
+        let dir = std::env::temp_dir().join(format!("vaultline_idempotency_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let log_path = dir.join("event.log");
+        let charge = || Event::now("billing", "info", "charged card").with_idempotency_key("order-7");
+        let mut vault = Vaultline::new(&log_path).unwrap();
+        vault.set_flush_policy(FlushPolicy { every_n: 0, interval: None });
+        assert_eq!(vault.append_batch(vec![charge(), charge()]).unwrap(), 1);
+        // The indexed record is already on disk, not waiting in the write buffer.
+        assert!(std::fs::read_to_string(&log_path).unwrap().contains("order-7"));
+        vault.append(charge()).unwrap();
+        vault.append(Event::now("billing", "info", "unkeyed")).unwrap();
+        vault.append(Event::now("billing", "info", "unkeyed")).unwrap();
+        assert_eq!(vault.all().len(), 3);
+        drop(vault);
+
+        // A new process consults the keys index, or rebuilds it from the log.
+        for _ in 0..2 {
+            let mut retry = Vaultline::new(&log_path).unwrap();
+            assert_eq!(retry.append_batch(vec![charge()]).unwrap(), 0);
+            std::fs::remove_file(dir.join("event.log.keys")).unwrap();
+        }
+        let mut reloaded = Vaultline::new(&log_path).unwrap();
+        reloaded.load_from_disk().unwrap();
+        assert_eq!(reloaded.all().len(), 3);
+        assert_eq!(reloaded.all()[0].idempotency_key.as_deref(), Some("order-7"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_query_filters() {
+        let mut vault = Vaultline::new_in_memory();
+        let mut early = Event::now("epoch", "error", "lease expired for job 1");
+        early.ts_ms = 1_000;
+        vault.append(early.clone()).unwrap();
+        let mut late = Event::now("epoch", "error", "lease expired for job 2");
+        late.ts_ms = 2_000;
+        vault.append(late.clone()).unwrap();
+        vault.append(Event::now("auth", "error", "lease expired elsewhere")).unwrap();
+        vault.append(Event::now("epoch", "info", "lease renewed")).unwrap();
+
+        let hits: Vec<&Event> = vault.query(Filter::new().level("ERROR").source("epoch").contains("expired")).collect();
+        assert_eq!(hits, [&early, &late]);
+
+        let windowed: Vec<&Event> = vault.query(Filter::new().source("epoch").since(1_500).until(2_001)).collect();
+        assert_eq!(windowed, [&late]);
+        assert_eq!(vault.query(Filter::new()).count(), 4);
+    }
+
+    #[test]
+    fn test_subscribe() {
+        let mut vault = Vaultline::new_in_memory();
+        vault.set_source_min_level("chatty", "warn");
+        let rx = vault.subscribe();
+        let dropped_rx = vault.subscribe();
+        drop(dropped_rx);
+
+        let ev = Event::now("epoch", "info", "job done");
+        vault.append(ev.clone()).unwrap();
+        vault.append(Event::now("chatty", "debug", "filtered out")).unwrap();
+
+        assert_eq!(rx.try_recv().unwrap(), ev);
+        assert!(rx.try_recv().is_err());
+        assert_eq!(vault.subscribers.len(), 1);
+    }
+
+    #[test]
+    fn test_index_lookups() {
+        let mut vault = Vaultline::new_in_memory();
+        vault.set_memory_cap(4);
+        for (source, level) in [("epoch", "info"), ("auth", "error"), ("epoch", "error"), ("axiom", "info"), ("epoch", "warn"), ("auth", "info")] {
+            vault.append(Event::now(source, level, format!("{source} {level}"))).unwrap();
+        }
+
+        // The first two events were evicted by the memory cap.
+        let epoch: Vec<&str> = vault.by_source("epoch").map(|ev| ev.message.as_str()).collect();
+        assert_eq!(epoch, ["epoch error", "epoch warn"]);
+        let last_epoch = vault.by_source("epoch").next_back().unwrap();
+        assert_eq!(last_epoch.level, "warn");
+        assert_eq!(vault.by_level("INFO").count(), 2);
+        assert_eq!(vault.by_source("auth").count(), 1);
+
+        vault.retain(|ev| ev.level != "error");
+        assert_eq!(vault.by_source("epoch").count(), 1);
+        assert_eq!(vault.query(Filter::new().source("auth").level("info")).count(), 1);
+    }
+}
@@ -0,0 +1,255 @@
+use crate::module::Result;
+use std::path::{Path, PathBuf};
+
+// Minimal blob storage used to offload rotated segments.
+pub trait ObjectStore: Send {
+    fn put(&self, key: &str, bytes: &[u8]) -> Result<()>;
+    fn get(&self, key: &str) -> Result<Vec<u8>>;
+    // Keys starting with `prefix`, in any order.
+    fn list(&self, prefix: &str) -> Result<Vec<String>>;
+}
+
+// Objects as files under a directory (a mounted share, or tests).
+pub struct DirObjectStore {
+    root: PathBuf,
+}
+
+impl DirObjectStore {
+    pub fn new<P: AsRef<Path>>(root: P) -> Result<Self> {
+        std::fs::create_dir_all(root.as_ref())?;
+        Ok(Self { root: root.as_ref().to_path_buf() })
+    }
+}
+
+impl ObjectStore for DirObjectStore {
+    fn put(&self, key: &str, bytes: &[u8]) -> Result<()> {
+        let path = self.root.join(key);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, bytes)?;
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Result<Vec<u8>> {
+        Ok(std::fs::read(self.root.join(key))?)
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        let (dir, _) = prefix.rsplit_once('/').unwrap_or(("", prefix));
+        let Ok(entries) = std::fs::read_dir(self.root.join(dir)) else { return Ok(Vec::new()) };
+        let mut keys = Vec::new();
+        for entry in entries {
+            let name = entry?.file_name().to_string_lossy().into_owned();
+            let key = if dir.is_empty() { name } else { format!("{dir}/{name}") };
+            if key.starts_with(prefix) {
+                keys.push(key);
+            }
+        }
+        Ok(keys)
+    }
+}
+
+// Uploads rotated segments under `prefix` and fetches them back on demand,
+// caching downloads in `cache_dir`.
+pub struct Archiver {
+    store: Box<dyn ObjectStore>,
+    prefix: String,
+    cache_dir: PathBuf,
+}
+
+impl Archiver {
+    pub fn new<S: ObjectStore + 'static>(store: S, prefix: &str, cache_dir: PathBuf) -> Self {
+        let prefix = prefix.trim_end_matches('/');
+        let prefix = if prefix.is_empty() { String::new() } else { format!("{prefix}/") };
+        Self { store: Box::new(store), prefix, cache_dir }
+    }
+
+    pub(super) fn upload(&self, segment: &Path) -> Result<()> {
+        let name = segment.file_name().ok_or("segment has no file name")?.to_string_lossy();
+        self.store.put(&format!("{}{name}", self.prefix), &std::fs::read(segment)?)
+    }
+
+    // Remote names starting with `stem`, the part of the log's file name that
+    // both size and daily archives keep; callers filter with `segment_stamp`.
+    pub(super) fn remote_segments(&self, stem: &str) -> Result<Vec<String>> {
+        let keys = self.store.list(&format!("{}{stem}", self.prefix))?;
+        Ok(keys.into_iter().filter_map(|k| k.strip_prefix(&self.prefix).map(str::to_string)).collect())
+    }
+
+    // Local copy of a remote segment, downloaded on first use.
+    pub(super) fn fetch(&self, name: &str) -> Result<PathBuf> {
+        let cached = self.cache_dir.join(name);
+        if !cached.exists() {
+            std::fs::create_dir_all(&self.cache_dir)?;
+            let tmp = self.cache_dir.join(format!("{name}.part"));
+            std::fs::write(&tmp, self.store.get(&format!("{}{name}", self.prefix))?)?;
+            std::fs::rename(&tmp, &cached)?;
+        }
+        Ok(cached)
+    }
+}
+
+// S3-compatible bucket (AWS, MinIO, R2, ...) over path-style URLs with SigV4 auth.
+#[cfg(feature = "s3")]
+pub struct S3ObjectStore {
+    endpoint: String,
+    bucket: String,
+    region: String,
+    access_key: String,
+    secret_key: String,
+}
+
+#[cfg(feature = "s3")]
+impl S3ObjectStore {
+    // `endpoint` like `https://s3.us-east-1.amazonaws.com` or `http://localhost:9000`.
+    pub fn new(endpoint: &str, bucket: &str, region: &str, access_key: &str, secret_key: &str) -> Self {
+        Self {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            bucket: bucket.to_string(),
+            region: region.to_string(),
+            access_key: access_key.to_string(),
+            secret_key: secret_key.to_string(),
+        }
+    }
+
+    // Credentials from `AWS_ACCESS_KEY_ID` / `AWS_SECRET_ACCESS_KEY`.
+    pub fn from_env(endpoint: &str, bucket: &str, region: &str) -> Result<Self> {
+        let access = std::env::var("AWS_ACCESS_KEY_ID").map_err(|_| "AWS_ACCESS_KEY_ID is not set")?;
+        let secret = std::env::var("AWS_SECRET_ACCESS_KEY").map_err(|_| "AWS_SECRET_ACCESS_KEY is not set")?;
+        Ok(Self::new(endpoint, bucket, region, &access, &secret))
+    }
+
+    fn request(&self, method: &str, key: &str, query: &[(&str, &str)], body: &[u8]) -> Result<ureq::Response> {
+        use sha2::{Digest, Sha256};
+
+        let host = self.endpoint.split("://").nth(1).unwrap_or(&self.endpoint);
+        let path = format!("/{}/{}", self.bucket, sigv4::uri_encode(key, false));
+        let mut pairs: Vec<(String, String)> =
+            query.iter().map(|(k, v)| (sigv4::uri_encode(k, true), sigv4::uri_encode(v, true))).collect();
+        pairs.sort();
+        let query = pairs.iter().map(|(k, v)| format!("{k}={v}")).collect::<Vec<_>>().join("&");
+
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_secs();
+        let (date, amz_date) = sigv4::timestamps(now);
+        let payload_hash = hex::encode(Sha256::digest(body));
+        let canonical = format!(
+            "{method}\n{path}\n{query}\nhost:{host}\nx-amz-content-sha256:{payload_hash}\nx-amz-date:{amz_date}\n\nhost;x-amz-content-sha256;x-amz-date\n{payload_hash}"
+        );
+        let scope = format!("{date}/{}/s3/aws4_request", self.region);
+        let to_sign = format!("AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}", hex::encode(Sha256::digest(canonical)));
+        let signing_key = sigv4::signing_key(&self.secret_key, &date, &self.region, "s3");
+        let signature = hex::encode(sigv4::hmac(&signing_key, to_sign.as_bytes()));
+        let auth = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders=host;x-amz-content-sha256;x-amz-date, Signature={signature}",
+            self.access_key
+        );
+
+        let url = if query.is_empty() { format!("{}{path}", self.endpoint) } else { format!("{}{path}?{query}", self.endpoint) };
+        let response = ureq::request(method, &url)
+            .set("x-amz-content-sha256", &payload_hash)
+            .set("x-amz-date", &amz_date)
+            .set("authorization", &auth)
+            .send_bytes(body)
+            .map_err(|e| format!("s3 {method} {key}: {e}"))?;
+        Ok(response)
+    }
+}
+
+#[cfg(feature = "s3")]
+impl ObjectStore for S3ObjectStore {
+    fn put(&self, key: &str, bytes: &[u8]) -> Result<()> {
+        self.request("PUT", key, &[], bytes)?;
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        std::io::Read::read_to_end(&mut self.request("GET", key, &[], &[])?.into_reader(), &mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        let tag = |xml: &str, name: &str| -> Vec<String> {
+            let open = format!("<{name}>");
+            let close = format!("</{name}>");
+            xml.split(&open).skip(1).filter_map(|rest| rest.split_once(&close).map(|(v, _)| v.to_string())).collect()
+        };
+        let mut keys = Vec::new();
+        let mut token: Option<String> = None;
+        loop {
+            let mut query = vec![("list-type", "2"), ("prefix", prefix)];
+            if let Some(ref t) = token {
+                query.push(("continuation-token", t));
+            }
+            let xml = self.request("GET", "", &query, &[])?.into_string()?;
+            keys.extend(tag(&xml, "Key"));
+            token = tag(&xml, "NextContinuationToken").into_iter().next();
+            if token.is_none() {
+                return Ok(keys);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "s3")]
+mod sigv4 {
+    use hmac::{Hmac, Mac};
+
+    pub(super) fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut mac = Hmac::<sha2::Sha256>::new_from_slice(key).expect("hmac accepts any key length");
+        mac.update(data);
+        mac.finalize().into_bytes().to_vec()
+    }
+
+    pub(super) fn signing_key(secret: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+        let k_date = hmac(format!("AWS4{secret}").as_bytes(), date.as_bytes());
+        let k_region = hmac(&k_date, region.as_bytes());
+        let k_service = hmac(&k_region, service.as_bytes());
+        hmac(&k_service, b"aws4_request")
+    }
+
+    // Percent-encode everything but unreserved characters (and `/` in paths).
+    pub(super) fn uri_encode(s: &str, encode_slash: bool) -> String {
+        let mut out = String::new();
+        for b in s.bytes() {
+            match b {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => out.push(b as char),
+                b'/' if !encode_slash => out.push('/'),
+                _ => out.push_str(&format!("%{b:02X}")),
+            }
+        }
+        out
+    }
+
+    // (`YYYYMMDD`, `YYYYMMDDTHHMMSSZ`) for a unix time in seconds.
+    pub(super) fn timestamps(secs: u64) -> (String, String) {
+        let days = (secs / 86_400) as i64;
+        let rem = secs % 86_400;
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z - era * 146_097;
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + i64::from(month <= 2);
+        let date = format!("{year:04}{month:02}{day:02}");
+        let time = format!("{date}T{:02}{:02}{:02}Z", rem / 3600, rem % 3600 / 60, rem % 60);
+        (date, time)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_signing_key_matches_aws_example() {
+            let key = signing_key("wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY", "20120215", "us-east-1", "iam");
+            assert_eq!(hex::encode(key), "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d");
+            assert_eq!(timestamps(1_369_353_600), ("20130524".into(), "20130524T000000Z".into()));
+            assert_eq!(uri_encode("logs/event.log 1", false), "logs/event.log%201");
+        }
+    }
+}
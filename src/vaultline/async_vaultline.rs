@@ -0,0 +1,162 @@
+use super::{binary, chain, Event, Filter, Format, Vaultline};
+use crate::module::Result;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tokio::io::AsyncWriteExt;
+
+// Vaultline for async services. The in-memory side (filters, sampling, index,
+// queries) is a regular in-memory `Vaultline`; records are written with
+// `tokio::fs` so appends never block the executor. Rotation, compaction and
+// custom stores are not available here.
+pub struct AsyncVaultline {
+    inner: Vaultline,
+    path: PathBuf,
+    file: tokio::fs::File,
+}
+
+impl AsyncVaultline {
+    // Open or create an NDJSON vaultline at the given path.
+    pub async fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::open_with_format(path, Format::Ndjson).await
+    }
+
+    pub async fn open_with_format<P: AsRef<Path>>(path: P, format: Format) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let file = tokio::fs::OpenOptions::new().create(true).append(true).open(&path).await?;
+        let mut inner = Vaultline::new_in_memory();
+        inner.format = format;
+        Ok(Self { inner, path, file })
+    }
+
+    // The wrapped vaultline, for settings (filters, checksums, keys, durability, ...)
+    // and synchronous reads.
+    pub fn vault(&self) -> &Vaultline {
+        &self.inner
+    }
+
+    pub fn vault_mut(&mut self) -> &mut Vaultline {
+        &mut self.inner
+    }
+
+    // Load the file's events into memory. Returns how many were loaded.
+    pub async fn load(&mut self) -> Result<usize> {
+        let events = self.read_records().await?;
+        Ok(self.inner.push_loaded(events, usize::MAX, None).0)
+    }
+
+    async fn read_records(&self) -> Result<Vec<Event>> {
+        let bytes = tokio::fs::read(&self.path).await?;
+        Ok(match self.inner.format {
+            Format::Binary => binary::decode_records(&bytes)?.0,
+            Format::Ndjson => {
+                let mut events = Vec::new();
+                for line in String::from_utf8_lossy(&bytes).lines() {
+                    if line.trim().is_empty() { continue; }
+                    if let Some(ev) = self.inner.decode_line(line)? {
+                        events.push(ev);
+                    }
+                }
+                events
+            }
+        })
+    }
+
+    // The wrapped vaultline only sees memory, so continue `seq` and the hash
+    // chain from the records already in the file before they are first needed
+    // (whether or not `load` was called).
+    async fn resume(&mut self) -> Result<()> {
+        let resume_seq = self.inner.sequencing && !self.inner.seq_resumed;
+        let resume_chain = self.inner.hash_chain && self.inner.last_hash.is_none();
+        if !resume_seq && !resume_chain { return Ok(()) }
+        let records = self.read_records().await?;
+        if resume_seq {
+            let last = records.iter().map(|ev| ev.seq).max().unwrap_or(0);
+            self.inner.next_seq = self.inner.next_seq.max(last + 1);
+            self.inner.seq_resumed = true;
+        }
+        if resume_chain {
+            self.inner.last_hash = Some(match records.last() {
+                Some(ev) => chain::record_hash(ev)?,
+                None => chain::GENESIS.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    pub async fn append(&mut self, event: Event) -> Result<()> {
+        self.append_batch(vec![event]).await.map(|_| ())
+    }
+
+    // Like `Vaultline::append_batch`. Each batch is flushed to the OS; fsync
+    // follows the vaultline's durability policy.
+    pub async fn append_batch(&mut self, events: Vec<Event>) -> Result<usize> {
+        self.resume().await?;
+        let batch = self.inner.admit(events)?;
+        if batch.is_empty() { return Ok(0) }
+        let buf = self.inner.encode_batch(&batch)?;
+        self.file.write_all(&buf).await?;
+        self.file.flush().await?;
+        self.inner.commit(&batch)?;
+        if self.inner.sync_due() {
+            self.file.sync_data().await?;
+            self.inner.last_sync = Instant::now();
+        }
+        self.inner.fan_out(&batch);
+        Ok(batch.len())
+    }
+
+    // In-memory events matching `filter`, oldest first.
+    pub async fn query(&self, filter: Filter) -> Vec<Event> {
+        self.inner.query(filter).cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vaultline::DurabilityPolicy;
+
+    #[test]
+    fn test_async_append_query_and_reload() {
+        let path = std::env::temp_dir().join(format!("vaultline_async_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let rt = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        rt.block_on(async {
+            let mut vault = AsyncVaultline::open(&path).await.unwrap();
+            vault.vault_mut().set_durability(DurabilityPolicy::FsyncEveryWrite);
+            vault.append(Event::now("epoch", "error", "job 1 failed")).await.unwrap();
+            vault.append(Event::now("epoch", "info", "job 2 done")).await.unwrap();
+            let errors = vault.query(Filter::new().level("error")).await;
+            assert_eq!(errors.len(), 1);
+
+            let mut reopened = AsyncVaultline::open(&path).await.unwrap();
+            assert_eq!(reopened.load().await.unwrap(), 2);
+            assert_eq!(reopened.vault().all(), vault.vault().all());
+        });
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_reopen_continues_seq_and_chain() {
+        let path = std::env::temp_dir().join(format!("vaultline_async_resume_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let rt = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        rt.block_on(async {
+            for message in ["first", "second"] {
+                let mut vault = AsyncVaultline::open(&path).await.unwrap();
+                vault.vault_mut().set_sequencing(true);
+                vault.vault_mut().set_hash_chain(true);
+                vault.append(Event::now("epoch", "info", message)).await.unwrap();
+            }
+        });
+        let mut reader = Vaultline::new(&path).unwrap();
+        reader.load_from_disk().unwrap();
+        assert_eq!(reader.all().iter().map(|ev| ev.seq).collect::<Vec<_>>(), [1, 2]);
+        let report = reader.verify().unwrap();
+        assert!(report.is_intact() && report.chained == 2, "{report:?}");
+        let _ = std::fs::remove_file(&path);
+    }
+}
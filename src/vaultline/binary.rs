@@ -0,0 +1,95 @@
+use super::Event;
+use crate::module::Result;
+use std::io::{ErrorKind, Read, Write};
+use std::path::Path;
+
+// Frame header: payload length and CRC32 of the payload, both u32 little-endian.
+const HEADER_LEN: usize = 8;
+
+// Write one framed record: header followed by the MessagePack-encoded event.
+pub(super) fn write_record(out: &mut impl Write, event: &Event) -> Result<()> {
+    let payload = rmp_serde::to_vec_named(event)?;
+    let len = u32::try_from(payload.len()).map_err(|_| "event too large for a binary frame")?;
+    out.write_all(&len.to_le_bytes())?;
+    out.write_all(&crc32fast::hash(&payload).to_le_bytes())?;
+    out.write_all(&payload)?;
+    Ok(())
+}
+
+// Decode every intact record in a binary segment. Records failing their CRC are
+// skipped; a frame cut short by a torn write ends the read. Returns the events and
+// whether a torn tail was found.
+pub(super) fn read_records(path: &Path) -> Result<(Vec<Event>, bool)> {
+    decode_records(&std::fs::read(path)?)
+}
+
+// Streaming form of `read_records`: yields intact records one at a time, skips
+// frames failing their CRC and stops at a torn tail.
+pub(super) struct Records<R> {
+    reader: R,
+}
+
+impl<R: Read> Records<R> {
+    pub(super) fn new(reader: R) -> Self {
+        Self { reader }
+    }
+}
+
+impl<R: Read> Iterator for Records<R> {
+    type Item = Result<Event>;
+
+    fn next(&mut self) -> Option<Result<Event>> {
+        loop {
+            let mut header = [0u8; HEADER_LEN];
+            match read_full(&mut self.reader, &mut header) {
+                Ok(true) => {}
+                Ok(false) => return None,
+                Err(e) => return Some(Err(e.into())),
+            }
+            let len = u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize;
+            let crc = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+            let mut payload = vec![0u8; len];
+            match read_full(&mut self.reader, &mut payload) {
+                Ok(true) => {}
+                Ok(false) => return None,
+                Err(e) => return Some(Err(e.into())),
+            }
+            if crc32fast::hash(&payload) != crc { continue; }
+            let Ok(ev) = rmp_serde::from_slice::<Event>(&payload) else { continue };
+            match super::schema::upgrade_event(ev) {
+                Ok(Some(ev)) => return Some(Ok(ev)),
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+// Fill `buf` completely; false if the input ended first.
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> std::io::Result<bool> {
+    match reader.read_exact(buf) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+// `read_records` over bytes already in memory.
+pub(super) fn decode_records(bytes: &[u8]) -> Result<(Vec<Event>, bool)> {
+    let mut events = Vec::new();
+    let mut rest = bytes;
+    while !rest.is_empty() {
+        let Some((header, body)) = rest.split_at_checked(HEADER_LEN) else { return Ok((events, true)) };
+        let len = u32::from_le_bytes(header[..4].try_into()?) as usize;
+        let crc = u32::from_le_bytes(header[4..].try_into()?);
+        let Some((payload, next)) = body.split_at_checked(len) else { return Ok((events, true)) };
+        if crc32fast::hash(payload) == crc
+            && let Ok(ev) = rmp_serde::from_slice::<Event>(payload)
+            && let Some(ev) = super::schema::upgrade_event(ev)?
+        {
+            events.push(ev);
+        }
+        rest = next;
+    }
+    Ok((events, false))
+}
@@ -0,0 +1,176 @@
+use super::Event;
+use crate::module::Result;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// Content-addressed store for large payloads (stack traces, request bodies)
+// that would bloat log lines. Each blob lives at `<dir>/<aa>/<rest of hash>`
+// under the hex SHA-256 of its bytes, so identical payloads are stored once and
+// a blob can be checked against its name. Events reference blobs from `kv` as
+// `{"blob": "<sha256>", "bytes": N}`.
+#[derive(Debug, Clone)]
+pub struct BlobStore {
+    dir: PathBuf,
+}
+
+impl BlobStore {
+    // The directory is created on the first `put`.
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self { dir: dir.as_ref().to_path_buf() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    // Store `data` and return its hash. Writing goes through a temp file and a
+    // rename, so a blob is either absent or complete.
+    pub fn put(&self, data: &[u8]) -> Result<String> {
+        let hash = sha256_hex(data);
+        let path = self.path(&hash)?;
+        if !path.exists() {
+            let parent = path.parent().unwrap_or(&self.dir);
+            fs::create_dir_all(parent)?;
+            let tmp = parent.join(format!(".{}.{}.tmp", &hash[2..], std::process::id()));
+            let mut file = File::create(&tmp)?;
+            file.write_all(data)?;
+            file.sync_all()?;
+            fs::rename(&tmp, &path)?;
+        }
+        Ok(hash)
+    }
+
+    // Bytes of the blob named `hash`, failing if it is missing or no longer
+    // matches its hash.
+    pub fn get(&self, hash: &str) -> Result<Vec<u8>> {
+        let path = self.path(hash)?;
+        let data = fs::read(&path).map_err(|e| format!("blob {hash}: {e}"))?;
+        if sha256_hex(&data) != hash {
+            return Err(format!("blob {hash} is corrupt: content does not match its hash").into());
+        }
+        Ok(data)
+    }
+
+    pub fn contains(&self, hash: &str) -> bool {
+        self.path(hash).is_ok_and(|p| p.is_file())
+    }
+
+    // Where the blob named `hash` lives. Anything but 64 lowercase hex chars is
+    // rejected, so a hash from a log line can't point outside the store.
+    pub fn path(&self, hash: &str) -> Result<PathBuf> {
+        if hash.len() != 64 || !hash.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) {
+            return Err(format!("invalid blob hash {hash:?}").into());
+        }
+        Ok(self.dir.join(&hash[..2]).join(&hash[2..]))
+    }
+
+    // Store `data` and set `kv[key]` to a reference to it.
+    pub fn attach<K: Into<String>>(&self, event: Event, key: K, data: &[u8]) -> Result<Event> {
+        let hash = self.put(data)?;
+        Ok(event.with(key, blob_ref(&hash, data.len())))
+    }
+
+    // Move top-level kv strings of at least `min_bytes` into the store,
+    // leaving references behind. Returns how many were moved.
+    pub(super) fn offload(&self, event: &mut Event, min_bytes: usize) -> Result<usize> {
+        let Value::Object(kv) = &mut event.kv else { return Ok(0) };
+        let mut moved = 0;
+        for value in kv.values_mut() {
+            if let Value::String(s) = value
+                && s.len() >= min_bytes
+            {
+                let hash = self.put(s.as_bytes())?;
+                *value = blob_ref(&hash, s.len());
+                moved += 1;
+            }
+        }
+        Ok(moved)
+    }
+
+    // Bytes referenced by `kv[key]`, or None when that value is not a blob reference.
+    pub fn resolve(&self, event: &Event, key: &str) -> Result<Option<Vec<u8>>> {
+        event.kv.get(key).and_then(blob_hash).map(|hash| self.get(hash)).transpose()
+    }
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+fn blob_ref(hash: &str, bytes: usize) -> Value {
+    serde_json::json!({ "blob": hash, "bytes": bytes })
+}
+
+// The hash in a `{"blob": ..., "bytes": ...}` reference.
+pub fn blob_hash(value: &Value) -> Option<&str> {
+    let obj = value.as_object()?;
+    if obj.len() != 2 || !obj.get("bytes").is_some_and(Value::is_u64) {
+        return None;
+    }
+    obj.get("blob")?.as_str()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::VaultConfig;
+    use crate::vaultline::Vaultline;
+
+    #[test]
+    fn large_kv_values_move_to_blobs() {
+        let dir = std::env::temp_dir().join(format!("vaultline_blobs_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("event.log");
+        let trace = "at frame\n".repeat(100);
+
+        let mut vault = Vaultline::new(&path).unwrap();
+        vault.apply_config(&VaultConfig { blob_min_bytes: Some(256), ..Default::default() }).unwrap();
+        vault.append(Event::now("epoch", "error", "job panicked").with("trace", trace.clone()).with("job", "j1")).unwrap();
+        let body = vault.blob_store().unwrap().attach(Event::now("api", "warn", "bad request"), "body", b"\x00binary").unwrap();
+        vault.append(body).unwrap();
+        vault.flush().unwrap();
+
+        let line = fs::read_to_string(&path).unwrap();
+        assert!(!line.contains("at frame") && line.contains("\"job\":\"j1\""));
+        let mut reader = Vaultline::open_read_only(&path).unwrap();
+        reader.set_blob_store(Some(BlobStore::new(dir.join("blobs"))));
+        reader.load_from_disk().unwrap();
+        let events = reader.all().to_vec();
+        assert_eq!(reader.attachment(&events[0], "trace").unwrap().unwrap(), trace.as_bytes());
+        assert_eq!(reader.attachment(&events[0], "job").unwrap(), None);
+        assert_eq!(reader.attachment(&events[1], "body").unwrap().unwrap(), b"\x00binary");
+
+        // Identical payloads share one blob; tampering and bad hashes are caught.
+        let store = BlobStore::new(dir.join("blobs"));
+        let hash = blob_hash(&events[0].kv["trace"]).unwrap();
+        assert_eq!(store.put(trace.as_bytes()).unwrap(), hash);
+        fs::write(store.path(hash).unwrap(), "edited").unwrap();
+        assert!(store.get(hash).is_err());
+        assert!(store.get("../../etc/passwd").is_err());
+
+        // A rejected append stores no blob.
+        vault.require_kv("billing", vec!["invoice".to_string()]);
+        let blobs = || fs::read_dir(dir.join("blobs")).unwrap().count();
+        let before = blobs();
+        assert!(vault.append(Event::now("billing", "error", "charge failed").with("trace", "x".repeat(300))).is_err());
+        assert_eq!(blobs(), before);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn nothing_is_offloaded_while_encrypting() {
+        let dir = std::env::temp_dir().join(format!("vaultline_blobs_enc_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut vault = Vaultline::new(dir.join("event.log")).unwrap();
+        vault.apply_config(&VaultConfig { blob_min_bytes: Some(16), ..Default::default() }).unwrap();
+        vault.set_encryption_key(&[7u8; 32]);
+        vault.append(Event::now("auth", "info", "token").with("secret", "s".repeat(64))).unwrap();
+        assert!(!dir.join("blobs").exists());
+        assert_eq!(vault.all()[0].kv["secret"], "s".repeat(64));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
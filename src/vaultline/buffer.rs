@@ -0,0 +1,44 @@
+use super::Event;
+use std::ops::Deref;
+
+// Events held in memory, oldest first. Dropping from the front only advances a
+// start offset and the dead prefix is reclaimed once it is as large as the live
+// part, so evicting under a memory cap is amortized O(1) while the events stay
+// one contiguous slice.
+#[derive(Debug, Default)]
+pub(super) struct EventBuffer {
+    events: Vec<Event>,
+    start: usize,
+}
+
+impl EventBuffer {
+    pub(super) fn push(&mut self, event: Event) {
+        self.events.push(event);
+    }
+
+    // Forget the oldest `n` events.
+    pub(super) fn drop_front(&mut self, n: usize) {
+        self.start = (self.start + n).min(self.events.len());
+        if self.start >= self.events.len() - self.start {
+            self.compact();
+        }
+    }
+
+    pub(super) fn retain<F: FnMut(&Event) -> bool>(&mut self, keep: F) {
+        self.compact();
+        self.events.retain(keep);
+    }
+
+    fn compact(&mut self) {
+        self.events.drain(..self.start);
+        self.start = 0;
+    }
+}
+
+impl Deref for EventBuffer {
+    type Target = [Event];
+
+    fn deref(&self) -> &[Event] {
+        &self.events[self.start..]
+    }
+}
@@ -0,0 +1,39 @@
+use super::Event;
+use crate::module::Result;
+use sha2::{Digest, Sha256};
+
+// `prev_hash` of the first record in a chain.
+pub(super) const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+// SHA-256 (hex) of an event's canonical form, `prev_hash` included, so each
+// record commits to everything before it. Hashing the event rather than the
+// stored line keeps the chain valid across encryption, checksums and format
+// changes.
+pub(super) fn record_hash(event: &Event) -> Result<String> {
+    Ok(hex::encode(Sha256::digest(canonical_bytes(event)?)))
+}
+
+// An event's JSON form without `schema_version`, which loading rewrites when
+// it upgrades an old record, so hashes and signatures survive upgrades.
+pub(super) fn canonical_bytes(event: &Event) -> Result<Vec<u8>> {
+    let mut value = serde_json::to_value(event)?;
+    if let Some(record) = value.as_object_mut() {
+        record.remove("schema_version");
+    }
+    Ok(serde_json::to_vec(&value)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_ignores_schema_version() {
+        let mut ev = Event::now("epoch", "info", "job 1 done");
+        let hash = record_hash(&ev).unwrap();
+        ev.schema_version = 0;
+        assert_eq!(record_hash(&ev).unwrap(), hash);
+        ev.message.push('!');
+        assert_ne!(record_hash(&ev).unwrap(), hash);
+    }
+}
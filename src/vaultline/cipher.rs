@@ -0,0 +1,61 @@
+use crate::config::VaultConfig;
+use crate::module::Result;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+
+// Prefix marking an encrypted line: `enc:<base64(nonce || ciphertext)>`.
+pub(super) const PREFIX: &str = "enc:";
+const NONCE_LEN: usize = 12;
+
+// AES-256-GCM sealing of individual NDJSON lines, each with a fresh nonce.
+pub(super) struct LineCipher {
+    aead: Aes256Gcm,
+}
+
+impl LineCipher {
+    pub(super) fn new(key: &[u8; 32]) -> Self {
+        Self { aead: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key)) }
+    }
+
+    pub(super) fn seal(&self, line: &str) -> Result<String> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let sealed = self.aead.encrypt(&nonce, line.as_bytes())
+            .map_err(|_| "failed to encrypt vaultline record")?;
+        let mut raw = nonce.to_vec();
+        raw.extend_from_slice(&sealed);
+        Ok(format!("{PREFIX}{}", STANDARD.encode(raw)))
+    }
+
+    pub(super) fn open(&self, line: &str) -> Result<String> {
+        let body = line.strip_prefix(PREFIX).ok_or("record is not encrypted")?;
+        let raw = STANDARD.decode(body)?;
+        if raw.len() < NONCE_LEN {
+            return Err("encrypted record is truncated".into());
+        }
+        let (nonce, sealed) = raw.split_at(NONCE_LEN);
+        let plain = self.aead.decrypt(Nonce::from_slice(nonce), sealed)
+            .map_err(|_| "failed to decrypt vaultline record (wrong key or corrupt line)")?;
+        Ok(String::from_utf8(plain)?)
+    }
+}
+
+// Resolve the vault key: `HYPERION_VAULT_KEY` wins over `[vault] encryption_key`.
+// Keys are 64 hex characters (32 bytes).
+pub fn load_key(cfg: &VaultConfig) -> Result<Option<[u8; 32]>> {
+    let hex = match std::env::var("HYPERION_VAULT_KEY") {
+        Ok(v) => v,
+        Err(_) => match cfg.encryption_key {
+            Some(ref v) => v.clone(),
+            None => return Ok(None),
+        },
+    };
+    parse_key(hex.trim()).map(Some)
+}
+
+pub fn parse_key(hex: &str) -> Result<[u8; 32]> {
+    let mut key = [0u8; 32];
+    hex::decode_to_slice(hex, &mut key).map_err(|_| "vault key must be 64 hex characters")?;
+    Ok(key)
+}
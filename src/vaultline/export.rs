@@ -0,0 +1,94 @@
+use super::{csv_cell, project, Event};
+use crate::module::Result;
+use std::io::Write;
+
+// Output formats for `Vaultline::export`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExportFormat {
+    // One JSON object per line.
+    Json,
+    // Header row plus one row per event; `kv` is a JSON string.
+    Csv,
+    // Columnar file for analytics tools; `kv` is a JSON string column.
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+pub(super) fn write_json<W: Write>(events: impl Iterator<Item = Result<Event>>, fields: &[&str], mut out: W) -> Result<usize> {
+    let mut written = 0;
+    for event in events {
+        serde_json::to_writer(&mut out, &project(&event?, fields)?)?;
+        out.write_all(b"\n")?;
+        written += 1;
+    }
+    out.flush()?;
+    Ok(written)
+}
+
+pub(super) fn write_csv<W: Write>(events: impl Iterator<Item = Result<Event>>, fields: &[&str], mut out: W) -> Result<usize> {
+    writeln!(out, "{}", fields.join(","))?;
+    let mut written = 0;
+    for event in events {
+        let row = project(&event?, fields)?;
+        let cells: Vec<String> = fields.iter().map(|f| csv_cell(&row[*f])).collect();
+        writeln!(out, "{}", cells.join(","))?;
+        written += 1;
+    }
+    out.flush()?;
+    Ok(written)
+}
+
+// Rows per Parquet row group; bounds memory while exporting.
+#[cfg(feature = "parquet")]
+const ROW_GROUP_LEN: usize = 65_536;
+
+// Write the default event columns as an uncompressed Parquet file.
+#[cfg(feature = "parquet")]
+pub(super) fn write_parquet(events: impl Iterator<Item = Result<Event>>, out: std::fs::File) -> Result<usize> {
+    use parquet::data_type::{ByteArray, ByteArrayType, Int64Type};
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::SerializedFileWriter;
+    use std::sync::Arc;
+
+    let schema = parquet::schema::parser::parse_message_type(
+        "message event {
+            REQUIRED INT64 ts_ms;
+            REQUIRED BYTE_ARRAY source (UTF8);
+            REQUIRED BYTE_ARRAY level (UTF8);
+            REQUIRED BYTE_ARRAY message (UTF8);
+            REQUIRED BYTE_ARRAY kv (UTF8);
+        }",
+    )?;
+    let mut writer = SerializedFileWriter::new(out, Arc::new(schema), Arc::new(WriterProperties::builder().build()))?;
+
+    let mut written = 0;
+    let mut events = events.peekable();
+    while events.peek().is_some() {
+        let mut ts = Vec::new();
+        let mut text: [Vec<ByteArray>; 4] = Default::default();
+        for event in events.by_ref().take(ROW_GROUP_LEN) {
+            let event = event?;
+            ts.push(i64::try_from(event.ts_ms).map_err(|_| "event timestamp out of range for parquet")?);
+            let kv = event.kv.to_string();
+            for (column, value) in text.iter_mut().zip([event.source, event.level.to_string(), event.message, kv]) {
+                column.push(ByteArray::from(value.into_bytes()));
+            }
+        }
+        written += ts.len();
+
+        let mut row_group = writer.next_row_group()?;
+        let mut index = 0;
+        while let Some(mut column) = row_group.next_column()? {
+            if index == 0 {
+                column.typed::<Int64Type>().write_batch(&ts, None, None)?;
+            } else {
+                column.typed::<ByteArrayType>().write_batch(&text[index - 1], None, None)?;
+            }
+            column.close()?;
+            index += 1;
+        }
+        row_group.close()?;
+    }
+    writer.close()?;
+    Ok(written)
+}
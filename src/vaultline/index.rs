@@ -0,0 +1,89 @@
+use super::{Event, Level};
+use std::collections::{HashMap, VecDeque};
+
+// Positions of buffered events by source and by level, plus what time-range
+// lookups need: the running maximum `ts_ms` per event (non-decreasing, so it can
+// be binary searched) and the positions of late events, whose `ts_ms` is below
+// that maximum. Positions are absolute (`base` + offset into the buffer) so
+// evicting from the front only pops entries.
+#[derive(Debug, Default)]
+pub(super) struct EventIndex {
+    base: usize,
+    by_source: HashMap<String, VecDeque<usize>>,
+    by_level: HashMap<String, VecDeque<usize>>,
+    max_ts: VecDeque<u128>,
+    late: VecDeque<usize>,
+}
+
+impl EventIndex {
+    pub(super) fn rebuild(events: &[Event]) -> Self {
+        let mut index = Self::default();
+        for (offset, ev) in events.iter().enumerate() {
+            index.insert(offset, ev);
+        }
+        index
+    }
+
+    // Record `ev`, stored at `offset` in the buffer.
+    pub(super) fn insert(&mut self, offset: usize, ev: &Event) {
+        let pos = self.base + offset;
+        self.by_source.entry(ev.source.clone()).or_default().push_back(pos);
+        self.by_level.entry(level_key(&ev.level)).or_default().push_back(pos);
+        let max = self.max_ts.back().copied().unwrap_or(0);
+        if ev.ts_ms < max {
+            self.late.push_back(pos);
+        }
+        self.max_ts.push_back(max.max(ev.ts_ms));
+    }
+
+    // Forget the events about to be drained from the front of the buffer.
+    pub(super) fn evict_front(&mut self, evicted: &[Event]) {
+        for ev in evicted {
+            pop_front(&mut self.by_source, &ev.source);
+            pop_front(&mut self.by_level, &level_key(&ev.level));
+            self.max_ts.pop_front();
+        }
+        self.base += evicted.len();
+        while self.late.front().is_some_and(|&pos| pos < self.base) {
+            self.late.pop_front();
+        }
+    }
+
+    // Buffer offsets of events from `source`, oldest first.
+    pub(super) fn source_offsets(&self, source: &str) -> impl DoubleEndedIterator<Item = usize> + '_ {
+        self.offsets(self.by_source.get(source))
+    }
+
+    // Buffer offsets of events at `level` (parsed leniently, case-insensitive), oldest first.
+    pub(super) fn level_offsets(&self, level: &str) -> impl DoubleEndedIterator<Item = usize> + '_ {
+        self.offsets(self.by_level.get(&level_key(&Level::parse(level))))
+    }
+
+    // Buffer offsets that may hold events with `since <= ts_ms < until`, in order.
+    // Events before the first running maximum reaching `since` are all older; from
+    // the first running maximum reaching `until` on, only late events can match.
+    pub(super) fn time_candidates(&self, since: u128, until: u128) -> impl Iterator<Item = usize> + '_ {
+        let lo = self.max_ts.partition_point(|&max| max < since);
+        let hi = self.max_ts.partition_point(|&max| max < until).max(lo);
+        let late_from = self.late.partition_point(|&pos| pos - self.base < hi);
+        (lo..hi).chain(self.late.range(late_from..).map(move |pos| pos - self.base))
+    }
+
+    fn offsets<'a>(&'a self, positions: Option<&'a VecDeque<usize>>) -> impl DoubleEndedIterator<Item = usize> + 'a {
+        positions.into_iter().flatten().map(move |pos| pos - self.base)
+    }
+}
+
+// Levels are indexed case-insensitively, matching `Filter` and `Level == str`.
+fn level_key(level: &Level) -> String {
+    level.as_str().to_ascii_lowercase()
+}
+
+fn pop_front(map: &mut HashMap<String, VecDeque<usize>>, key: &str) {
+    if let Some(positions) = map.get_mut(key) {
+        positions.pop_front();
+        if positions.is_empty() {
+            map.remove(key);
+        }
+    }
+}
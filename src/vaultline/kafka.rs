@@ -0,0 +1,107 @@
+use super::{Event, EventSink};
+use crate::module::Result;
+use rdkafka::config::ClientConfig;
+use rdkafka::error::{KafkaError, RDKafkaErrorCode};
+use rdkafka::producer::{BaseProducer, BaseRecord, DeliveryResult, Producer, ProducerContext};
+use rdkafka::ClientContext;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// Keeps the first delivery failure reported by librdkafka until the sink reports it.
+#[derive(Clone, Default)]
+struct Deliveries {
+    failed: Arc<Mutex<Option<String>>>,
+}
+
+impl ClientContext for Deliveries {}
+
+impl ProducerContext for Deliveries {
+    type DeliveryOpaque = ();
+
+    fn delivery(&self, result: &DeliveryResult<'_>, _: ()) {
+        if let Err((e, _)) = result
+            && let Ok(mut failed) = self.failed.lock()
+        {
+            failed.get_or_insert_with(|| e.to_string());
+        }
+    }
+}
+
+// Publishes each stored event as JSON to a Kafka topic, keyed by source so one
+// source's events stay ordered within a partition. Sends are asynchronous;
+// delivery failures surface on a later append or on `flush`, which waits up
+// to `flush_timeout` for outstanding messages.
+pub struct KafkaSink {
+    producer: BaseProducer<Deliveries>,
+    deliveries: Deliveries,
+    topic: String,
+    flush_timeout: Duration,
+}
+
+impl KafkaSink {
+    // Producer for `brokers` (comma-separated host:port list).
+    pub fn new<T: Into<String>>(brokers: &str, topic: T) -> Result<Self> {
+        Self::from_config(ClientConfig::new().set("bootstrap.servers", brokers), topic)
+    }
+
+    // Producer built from a full client config (acks, TLS, SASL, ...).
+    pub fn from_config<T: Into<String>>(config: &ClientConfig, topic: T) -> Result<Self> {
+        let deliveries = Deliveries::default();
+        let producer = config.create_with_context(deliveries.clone())?;
+        Ok(Self { producer, deliveries, topic: topic.into(), flush_timeout: Duration::from_secs(10) })
+    }
+
+    pub fn flush_timeout(mut self, timeout: Duration) -> Self {
+        self.flush_timeout = timeout;
+        self
+    }
+
+    fn take_failure(&self) -> Result<()> {
+        match self.deliveries.failed.lock().map_err(|_| "kafka delivery state poisoned")?.take() {
+            Some(e) => Err(format!("kafka delivery to {} failed: {e}", self.topic).into()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl EventSink for KafkaSink {
+    fn write(&mut self, events: &[Event]) -> Result<()> {
+        for event in events {
+            let payload = serde_json::to_vec(event)?;
+            let mut record = BaseRecord::to(&self.topic).key(&event.source).payload(&payload);
+            // A full local queue drains as the producer is polled.
+            loop {
+                match self.producer.send(record) {
+                    Ok(()) => break,
+                    Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), back)) => {
+                        record = back;
+                        self.producer.poll(Duration::from_millis(100));
+                    }
+                    Err((e, _)) => return Err(format!("kafka send to {}: {e}", self.topic).into()),
+                }
+            }
+        }
+        self.producer.poll(Duration::ZERO);
+        self.take_failure()
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.producer.flush(self.flush_timeout)?;
+        self.take_failure()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kafka_flush_reports_undeliverable_events() {
+        let mut config = ClientConfig::new();
+        config.set("bootstrap.servers", "127.0.0.1:1").set("message.timeout.ms", "200");
+        let mut sink = KafkaSink::from_config(&config, "audit").unwrap().flush_timeout(Duration::from_secs(5));
+        sink.write(&[Event::now("auth", "error", "login failed")]).unwrap();
+        let err = sink.flush().unwrap_err();
+        assert!(err.to_string().contains("audit"), "{err}");
+    }
+}
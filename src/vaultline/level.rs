@@ -0,0 +1,133 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::convert::Infallible;
+use std::fmt;
+use std::str::FromStr;
+
+// Severity of an event. Known levels serialize as their lowercase names;
+// anything else is kept verbatim as `Other` so no record is lost on load.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Level {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Other(String),
+}
+
+impl Level {
+    // Lenient parse: known names match case-insensitively ("warning" is `Warn`).
+    pub fn parse(s: &str) -> Self {
+        match s.to_ascii_lowercase().as_str() {
+            "trace" => Level::Trace,
+            "debug" => Level::Debug,
+            "info" => Level::Info,
+            "warn" | "warning" => Level::Warn,
+            "error" => Level::Error,
+            _ => Level::Other(s.to_string()),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Level::Trace => "trace",
+            Level::Debug => "debug",
+            Level::Info => "info",
+            Level::Warn => "warn",
+            Level::Error => "error",
+            Level::Other(s) => s,
+        }
+    }
+
+    // Position in the severity order; `Other` levels have none.
+    pub fn rank(&self) -> Option<u8> {
+        match self {
+            Level::Trace => Some(0),
+            Level::Debug => Some(1),
+            Level::Info => Some(2),
+            Level::Warn => Some(3),
+            Level::Error => Some(4),
+            Level::Other(_) => None,
+        }
+    }
+}
+
+// Known levels are ordered by severity; `Other` levels only compare equal to themselves.
+impl PartialOrd for Level {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self.rank(), other.rank()) {
+            (Some(a), Some(b)) => Some(a.cmp(&b)),
+            _ => (self == other).then_some(Ordering::Equal),
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Level {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Infallible> {
+        Ok(Level::parse(s))
+    }
+}
+
+impl From<&str> for Level {
+    fn from(s: &str) -> Self {
+        Level::parse(s)
+    }
+}
+
+impl From<String> for Level {
+    fn from(s: String) -> Self {
+        Level::parse(&s)
+    }
+}
+
+// Compares the way `parse` reads and ignores case, so `level == "WARNING"`
+// holds for `Warn`.
+impl PartialEq<str> for Level {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str().eq_ignore_ascii_case(Level::parse(other).as_str())
+    }
+}
+
+impl PartialEq<&str> for Level {
+    fn eq(&self, other: &&str) -> bool {
+        *self == **other
+    }
+}
+
+impl Serialize for Level {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Level {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        Ok(Level::parse(&String::deserialize(d)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level_parse_order_and_serde() {
+        assert_eq!(Level::parse("WARNING"), Level::Warn);
+        assert_eq!(Level::parse("notice"), Level::Other("notice".into()));
+        assert!(Level::Warn == "WARNING" && Level::parse("Notice") == "NOTICE");
+        assert!(Level::Error > Level::Warn && Level::Debug < Level::Info);
+        assert_eq!(Level::Other("notice".into()).partial_cmp(&Level::Info), None);
+        assert_eq!(serde_json::to_string(&Level::Warn).unwrap(), "\"warn\"");
+        assert_eq!(serde_json::from_str::<Level>("\"Error\"").unwrap(), Level::Error);
+        assert_eq!(serde_json::to_string(&Level::parse("Notice")).unwrap(), "\"Notice\"");
+    }
+}
@@ -0,0 +1,136 @@
+use super::{Event, EventSink, Level, WebhookSink};
+use crate::epoch::Backoff;
+use crate::module::Result;
+use serde_json::{json, Value};
+
+// Ships stored events as OpenTelemetry LogRecords over OTLP/HTTP (JSON encoding)
+// to `<endpoint>/v1/logs`, e.g. a collector on `http://localhost:4318`. The
+// resource carries `telemetry::resource_attributes`; batching and retries work
+// as in `WebhookSink`.
+pub struct OtlpSink {
+    inner: WebhookSink,
+}
+
+impl OtlpSink {
+    pub fn new(endpoint: &str) -> Self {
+        let url = format!("{}/v1/logs", endpoint.trim_end_matches('/'));
+        Self { inner: WebhookSink::new(url).encoder(encode) }
+    }
+
+    pub fn batch_size(self, n: usize) -> Self {
+        Self { inner: self.inner.batch_size(n) }
+    }
+
+    pub fn retries(self, retries: u32, backoff: Backoff) -> Self {
+        Self { inner: self.inner.retries(retries, backoff) }
+    }
+
+    // Extra request header, e.g. a collector API key.
+    pub fn header<K: Into<String>, V: Into<String>>(self, name: K, value: V) -> Self {
+        Self { inner: self.inner.header(name, value) }
+    }
+}
+
+impl EventSink for OtlpSink {
+    fn write(&mut self, events: &[Event]) -> Result<()> {
+        self.inner.write(events)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+}
+
+// OTLP SeverityNumber for the base value of each level's range.
+fn severity_number(level: &Level) -> u8 {
+    match level {
+        Level::Trace => 1,
+        Level::Debug => 5,
+        Level::Info => 9,
+        Level::Warn => 13,
+        Level::Error => 17,
+        Level::Other(_) => 0,
+    }
+}
+
+// An OTLP AnyValue. Integers are strings, as the JSON encoding of int64 requires.
+fn any_value(value: &Value) -> Value {
+    match value {
+        Value::String(s) => json!({ "stringValue": s }),
+        Value::Bool(b) => json!({ "boolValue": b }),
+        Value::Number(n) if n.is_i64() || n.is_u64() => json!({ "intValue": n.to_string() }),
+        Value::Number(n) => json!({ "doubleValue": n.as_f64() }),
+        other => json!({ "stringValue": other.to_string() }),
+    }
+}
+
+fn attribute(key: &str, value: &Value) -> Value {
+    json!({ "key": key, "value": any_value(value) })
+}
+
+fn log_record(event: &Event) -> Value {
+    let mut attributes = vec![attribute("source", &Value::from(event.source.as_str()))];
+    if let Some(ref id) = event.correlation_id {
+        attributes.push(attribute("correlation_id", &Value::from(id.as_str())));
+    }
+    if let Some(kv) = event.kv.as_object() {
+        attributes.extend(kv.iter().map(|(k, v)| attribute(k, v)));
+    }
+    let nanos = (event.ts_ms * 1_000_000).to_string();
+    let mut record = json!({
+        "timeUnixNano": nanos,
+        "observedTimeUnixNano": nanos,
+        "severityNumber": severity_number(&event.level),
+        "severityText": event.level.as_str(),
+        "body": { "stringValue": event.message },
+        "attributes": attributes,
+    });
+    // OTLP trace ids are 16 bytes of hex; anything else stays an attribute.
+    match event.trace_id {
+        Some(ref id) if id.len() == 32 && id.bytes().all(|b| b.is_ascii_hexdigit()) => record["traceId"] = id.as_str().into(),
+        Some(ref id) => record["attributes"].as_array_mut().expect("attributes is an array").push(attribute("trace_id", &Value::from(id.as_str()))),
+        None => {}
+    }
+    record
+}
+
+// ExportLogsServiceRequest body for a batch.
+fn encode(events: &[Event]) -> Result<Vec<u8>> {
+    let resource: Vec<Value> =
+        crate::telemetry::resource_attributes().iter().map(|(k, v)| attribute(k, &Value::from(v.as_str()))).collect();
+    let request = json!({
+        "resourceLogs": [{
+            "resource": { "attributes": resource },
+            "scopeLogs": [{
+                "scope": { "name": "hyperion.vaultline", "version": env!("CARGO_PKG_VERSION") },
+                "logRecords": events.iter().map(log_record).collect::<Vec<_>>(),
+            }],
+        }],
+    });
+    Ok(serde_json::to_vec(&request)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_otlp_encoding() {
+        let mut ev = Event::now("epoch", "warn", "lease expired")
+            .with("job_id", 7)
+            .with("kind", "email")
+            .with_trace_id("0123456789abcdef0123456789abcdef");
+        ev.ts_ms = 1_700_000_000_123;
+        let body: Value = serde_json::from_slice(&encode(&[ev]).unwrap()).unwrap();
+        let logs = &body["resourceLogs"][0];
+        assert!(logs["resource"]["attributes"].as_array().unwrap().iter().any(|a| a["key"] == "service.name"));
+        let record = &logs["scopeLogs"][0]["logRecords"][0];
+        assert_eq!(record["timeUnixNano"], "1700000000123000000");
+        assert_eq!((record["severityNumber"].as_u64(), record["severityText"].as_str()), (Some(13), Some("warn")));
+        assert_eq!(record["body"]["stringValue"], "lease expired");
+        assert_eq!(record["traceId"], "0123456789abcdef0123456789abcdef");
+        let attrs = record["attributes"].as_array().unwrap();
+        assert!(attrs.contains(&json!({ "key": "job_id", "value": { "intValue": "7" } })));
+        assert!(attrs.contains(&json!({ "key": "source", "value": { "stringValue": "epoch" } })));
+    }
+}
@@ -0,0 +1,226 @@
+use super::{daily_archive, merge_by_ts, Event, EventSink, Filter, RetentionPolicy, Vaultline};
+use crate::config::VaultConfig;
+use crate::module::Result;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+// Writes each source to its own file, `<dir>/<source>.ndjson`, so hot files
+// stay small and retention can differ per component. Reads merge every
+// partition back into one timestamp-ordered view. Also usable as a sink of
+// the main vaultline (`[vault] partition_by_source`).
+pub struct PartitionedVaultline {
+    dir: PathBuf,
+    config: VaultConfig,
+    source_aliases: HashMap<String, String>,
+    retention: BTreeMap<String, RetentionPolicy>,
+    partitions: BTreeMap<String, Vaultline>,
+}
+
+impl PartitionedVaultline {
+    // Open `dir`, picking up the partitions already in it.
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir)?;
+        let mut partitions = BTreeMap::new();
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            // Daily archives of a partition share its extension.
+            let name = path.file_name().and_then(|s| s.to_str()).unwrap_or_default();
+            if path.extension().is_some_and(|ext| ext == "ndjson")
+                && daily_archive(name).is_none()
+                && let Some(stem) = path.file_stem().and_then(|s| s.to_str())
+            {
+                partitions.insert(stem.to_string(), Vaultline::new(&path)?);
+            }
+        }
+        Ok(Self { dir, config: VaultConfig::default(), source_aliases: HashMap::new(), retention: BTreeMap::new(), partitions })
+    }
+
+    // Rewrite old source names to canonical ones (key -> value) before the
+    // partition is picked, so an alias and its target share one file.
+    pub fn set_source_aliases(&mut self, map: HashMap<String, String>) {
+        for vault in self.partitions.values_mut() {
+            vault.set_source_aliases(map.clone());
+        }
+        self.source_aliases = map;
+    }
+
+    fn canonical<'a>(&'a self, source: &'a str) -> &'a str {
+        self.source_aliases.get(source).map_or(source, String::as_str)
+    }
+
+    // Apply the `[vault]` settings to every partition, present and future.
+    pub fn apply_config(&mut self, cfg: &VaultConfig) -> Result<()> {
+        self.config = cfg.clone();
+        for (stem, vault) in &mut self.partitions {
+            vault.apply_config(cfg)?;
+            if let Some(policy) = self.retention.get(stem) {
+                vault.set_retention(*policy);
+            }
+        }
+        Ok(())
+    }
+
+    // Retention for one source's partition, overriding the config's.
+    pub fn set_retention(&mut self, source: &str, policy: RetentionPolicy) {
+        let stem = partition_stem(self.canonical(source));
+        if let Some(vault) = self.partitions.get_mut(&stem) {
+            vault.set_retention(policy);
+        }
+        self.retention.insert(stem, policy);
+    }
+
+    pub fn append(&mut self, mut event: Event) -> Result<()> {
+        event.source = self.canonical(&event.source).to_string();
+        self.partition_mut(&event.source)?.append(event)
+    }
+
+    // The partition holding `source`, created on first use.
+    pub fn partition_mut(&mut self, source: &str) -> Result<&mut Vaultline> {
+        let stem = partition_stem(self.canonical(source));
+        if !self.partitions.contains_key(&stem) {
+            let mut vault = Vaultline::new(self.dir.join(format!("{stem}.ndjson")))?;
+            vault.apply_config(&self.config)?;
+            vault.set_source_aliases(self.source_aliases.clone());
+            if let Some(policy) = self.retention.get(&stem) {
+                vault.set_retention(*policy);
+            }
+            self.partitions.insert(stem.clone(), vault);
+        }
+        Ok(self.partitions.get_mut(&stem).expect("partition just inserted"))
+    }
+
+    // Partition file stems, sorted.
+    pub fn partitions(&self) -> impl Iterator<Item = &str> {
+        self.partitions.keys().map(String::as_str)
+    }
+
+    // Events matching `filter` from every partition on disk, oldest first.
+    // A source filter only reads that source's partition.
+    pub fn query(&mut self, mut filter: Filter) -> Result<Vec<Event>> {
+        if let Some(source) = filter.source.take() {
+            filter.source = Some(self.canonical(&source).to_string());
+        }
+        let only = filter.source.as_deref().map(partition_stem);
+        let mut merged = Vec::new();
+        for (stem, vault) in &mut self.partitions {
+            if only.as_ref().is_some_and(|only| only != stem) {
+                continue;
+            }
+            let events = vault.events_matching(filter.clone())?.collect::<Result<Vec<_>>>()?;
+            merged = merge_by_ts(merged, events, |ev| ev.ts_ms);
+        }
+        Ok(merged)
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.partitions.values_mut().try_for_each(Vaultline::flush)
+    }
+
+    // Enforce each partition's retention policy. Returns on-disk events removed.
+    pub fn enforce_retention(&mut self) -> Result<usize> {
+        let mut removed = 0;
+        for vault in self.partitions.values_mut() {
+            removed += vault.enforce_retention()?;
+        }
+        Ok(removed)
+    }
+}
+
+impl EventSink for PartitionedVaultline {
+    fn write(&mut self, events: &[Event]) -> Result<()> {
+        events.iter().try_for_each(|event| self.append(event.clone()))
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        PartitionedVaultline::flush(self)
+    }
+}
+
+// File stem for a source. Lowercase ASCII letters, digits and `-` are kept;
+// every other byte becomes `_` plus two hex digits, so distinct sources never
+// share a file (`net/http` is `net_2fhttp`, `net_http` is `net_5fhttp`), even
+// on case-insensitive file systems. The empty source is `_`.
+fn partition_stem(source: &str) -> String {
+    if source.is_empty() {
+        return "_".to_string();
+    }
+    let mut stem = String::with_capacity(source.len());
+    for byte in source.bytes() {
+        if byte.is_ascii_lowercase() || byte.is_ascii_digit() || byte == b'-' {
+            stem.push(char::from(byte));
+        } else {
+            stem.push_str(&format!("_{byte:02x}"));
+        }
+    }
+    stem
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_per_source_files_and_merges_reads() {
+        let dir = std::env::temp_dir().join(format!("vaultline_partitions_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut vault = PartitionedVaultline::open(&dir).unwrap();
+        vault.set_retention("axiom", RetentionPolicy { max_age: None, max_events: Some(1) });
+        let now = Event::now("epoch", "info", "").ts_ms;
+        for (i, source) in ["epoch", "axiom", "epoch", "axiom", "net/http", "net.http", "net_http"].into_iter().enumerate() {
+            let mut ev = Event::now(source, "info", format!("event {i}"));
+            ev.ts_ms = now + i as u128;
+            vault.append(ev).unwrap();
+        }
+        vault.flush().unwrap();
+        assert!(dir.join("epoch.ndjson").exists() && dir.join("net_2fhttp.ndjson").exists());
+
+        let mut reopened = PartitionedVaultline::open(&dir).unwrap();
+        assert_eq!(reopened.partitions().collect::<Vec<_>>(), ["axiom", "epoch", "net_2ehttp", "net_2fhttp", "net_5fhttp"]);
+        let messages = |events: Vec<Event>| events.into_iter().map(|ev| ev.message).collect::<Vec<_>>();
+        let all = ["event 0", "event 1", "event 2", "event 3", "event 4", "event 5", "event 6"];
+        assert_eq!(messages(reopened.query(Filter::default()).unwrap()), all);
+        let http = Filter { source: Some("net.http".into()), ..Filter::default() };
+        assert_eq!(messages(reopened.query(http).unwrap()), ["event 5"]);
+
+        assert_eq!(vault.enforce_retention().unwrap(), 1);
+        let axiom = Filter { source: Some("axiom".into()), ..Filter::default() };
+        assert_eq!(messages(vault.query(axiom).unwrap()), ["event 3"]);
+        assert_eq!(vault.partition_mut("epoch").unwrap().stats().unwrap().total, 2);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn aliases_pick_the_canonical_partition() {
+        let dir = std::env::temp_dir().join(format!("vaultline_partitions_alias_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut vault = PartitionedVaultline::open(&dir).unwrap();
+        vault.set_source_aliases(HashMap::from([("old-auth".to_string(), "auth".to_string())]));
+        vault.write(&[Event::now("old-auth", "info", "legacy"), Event::now("auth", "info", "current"), Event::now("Auth", "info", "upper")]).unwrap();
+        EventSink::flush(&mut vault).unwrap();
+
+        assert_eq!(vault.partitions().collect::<Vec<_>>(), ["_41uth", "auth"]);
+        let auth = Filter { source: Some("old-auth".into()), ..Filter::default() };
+        let stored = vault.query(auth).unwrap();
+        assert_eq!(stored.iter().map(|ev| (ev.source.as_str(), ev.message.as_str())).collect::<Vec<_>>(), [("auth", "legacy"), ("auth", "current")]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn config_redaction_reaches_every_partition() {
+        let dir = std::env::temp_dir().join(format!("vaultline_partitions_redact_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut vault = PartitionedVaultline::open(&dir).unwrap();
+        vault.append(Event::now("auth", "info", "login").with("password", "hunter2")).unwrap();
+        vault.apply_config(&VaultConfig { redact_keys: vec!["password".into()], ..VaultConfig::default() }).unwrap();
+        vault.append(Event::now("auth", "info", "login").with("password", "hunter2")).unwrap();
+        vault.append(Event::now("epoch", "info", "job").with("password", "hunter2")).unwrap();
+        vault.flush().unwrap();
+
+        let stored = vault.query(Filter::default()).unwrap();
+        let passwords: Vec<&str> = stored[1..].iter().map(|ev| ev.kv["password"].as_str().unwrap()).collect();
+        assert!(passwords.iter().all(|p| *p != "hunter2"));
+        assert_eq!(passwords.len(), 2);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
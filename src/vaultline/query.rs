@@ -0,0 +1,158 @@
+use super::{Filter, Level, Result};
+use std::str::FromStr;
+
+// Textual form of a `Filter`: clauses joined by `AND`, e.g.
+//
+//   level>=warn AND source=epoch AND message~"lease" AND kv.attempt=3
+//
+// Fields: `level` (`=` exact, `>=` at or above), `source` and
+// `correlation_id` (`=`), `message` (`~` substring), `ts` (`>=`, `>`, `<`,
+// `<=` on ts_ms) and `kv.<key>` (`=`; bare values are read as JSON when they
+// parse, so `kv.attempt=3` matches the number). Values with spaces or
+// operator characters go in double quotes, with `\"` and `\\` escapes. Each
+// field may appear once (`kv.` once in total).
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Op(&'static str),
+}
+
+const OPS: [&str; 6] = [">=", "<=", "=", "~", ">", "<"];
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = input.trim_start();
+    while !rest.is_empty() {
+        if let Some(op) = OPS.iter().find(|op| rest.starts_with(**op)) {
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        } else if let Some(quoted) = rest.strip_prefix('"') {
+            let mut value = String::new();
+            let mut chars = quoted.char_indices();
+            let end = loop {
+                match chars.next() {
+                    Some((i, '"')) => break i,
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, c)) => value.push(c),
+                        None => return Err("query: unterminated string".into()),
+                    },
+                    Some((_, c)) => value.push(c),
+                    None => return Err("query: unterminated string".into()),
+                }
+            };
+            tokens.push(Token::Quoted(value));
+            rest = &quoted[end + 1..];
+        } else {
+            let end = rest.find(|c: char| c.is_whitespace() || c == '"' || "<>=~".contains(c)).unwrap_or(rest.len());
+            tokens.push(Token::Word(rest[..end].to_string()));
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+impl Filter {
+    // Parse the query syntax described at the top of this file. An empty
+    // string is the empty filter.
+    pub fn parse(input: &str) -> Result<Filter> {
+        let mut filter = Filter::new();
+        let mut tokens = tokenize(input)?.into_iter();
+        loop {
+            let Some(field) = tokens.next() else { return Ok(filter) };
+            let Token::Word(field) = field else {
+                return Err(format!("query: expected a field name, got {field:?}").into());
+            };
+            let Some(Token::Op(op)) = tokens.next() else {
+                return Err(format!("query: expected an operator after `{field}`").into());
+            };
+            let value = match tokens.next() {
+                Some(Token::Word(v)) | Some(Token::Quoted(v)) if !v.is_empty() => v,
+                _ => return Err(format!("query: missing value for `{field}{op}`").into()),
+            };
+            filter = apply(filter, &field, op, value)?;
+            match tokens.next() {
+                None => return Ok(filter),
+                Some(Token::Word(and)) if and.eq_ignore_ascii_case("and") => {}
+                Some(other) => return Err(format!("query: expected AND, got {other:?}").into()),
+            }
+            if tokens.len() == 0 {
+                return Err("query: dangling AND".into());
+            }
+        }
+    }
+}
+
+fn apply(filter: Filter, field: &str, op: &str, value: String) -> Result<Filter> {
+    // A filter holds one value per field, so a second clause for the same one
+    // would silently replace the first.
+    let taken = match (field, op) {
+        ("level", "=") => filter.level.is_some(),
+        ("level", ">=") => filter.min_level.is_some(),
+        ("source", _) => filter.source.is_some(),
+        ("correlation_id", _) => filter.correlation_id.is_some(),
+        ("message", _) => filter.contains.is_some(),
+        ("ts", ">=" | ">") => filter.since_ms.is_some(),
+        ("ts", "<" | "<=") => filter.until_ms.is_some(),
+        (kv, _) if kv.starts_with("kv.") => filter.kv.is_some(),
+        _ => false,
+    };
+    if taken {
+        return Err(format!("query: more than one `{field}{op}` clause").into());
+    }
+    let timestamp = || value.parse::<u128>().map_err(|_| format!("query: `ts` needs a millisecond timestamp, got {value:?}"));
+    let after = || timestamp()?.checked_add(1).ok_or_else(|| format!("query: `ts` value {value} is out of range"));
+    Ok(match (field, op) {
+        ("level", "=") => filter.level(value),
+        ("level", ">=") => filter.min_level(Level::parse(&value)),
+        ("source", "=") => filter.source(value),
+        ("correlation_id", "=") => filter.correlation_id(value),
+        ("message", "~") => filter.contains(value),
+        ("ts", ">=") => filter.since(timestamp()?),
+        ("ts", ">") => filter.since(after()?),
+        ("ts", "<") => filter.until(timestamp()?),
+        ("ts", "<=") => filter.until(after()?),
+        (kv, "=") if kv.starts_with("kv.") && kv.len() > 3 => {
+            let parsed = serde_json::from_str(&value).unwrap_or(serde_json::Value::String(value));
+            filter.kv(&kv[3..], parsed)
+        }
+        ("level" | "source" | "correlation_id" | "message" | "ts", _) => {
+            return Err(format!("query: `{field}` does not support `{op}`").into());
+        }
+        _ => return Err(format!("query: unknown field `{field}`").into()),
+    })
+}
+
+impl FromStr for Filter {
+    type Err = crate::module::Error;
+
+    fn from_str(s: &str) -> Result<Filter> {
+        Filter::parse(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vaultline::Event;
+
+    #[test]
+    fn parses_clauses_into_filter() {
+        let filter = Filter::parse(r#"level>=warn AND source=epoch and message~"lease \"7\" lost" AND kv.attempt=3 AND ts<2000"#).unwrap();
+        assert_eq!(filter, Filter::new().min_level(Level::Warn).source("epoch").contains("lease \"7\" lost").kv("attempt", 3).until(2000));
+
+        let mut ev = Event::now("epoch", "error", "lease \"7\" lost").with("attempt", 3);
+        ev.ts_ms = 1000;
+        assert!(filter.matches(&ev));
+        ev.level = Level::Info;
+        assert!(!filter.matches(&ev));
+        assert_eq!(Filter::parse("kv.user=alice").unwrap().kv, Some(("user".into(), "alice".into())));
+        assert_eq!("".parse::<Filter>().unwrap(), Filter::new());
+
+        for bad in ["level", "level>=", "colour=red", "message=lease", "source=a source=b", "source=a AND source=b", "kv.a=1 AND kv.b=2", "source=a AND", "ts>=soon", r#"message~"open"#, "ts>340282366920938463463374607431768211455"] {
+            assert!(Filter::parse(bad).is_err(), "{bad} should not parse");
+        }
+    }
+}
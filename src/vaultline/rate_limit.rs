@@ -0,0 +1,97 @@
+use super::{Event, Level};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// Per-source cap on appends: up to `per_sec` events per source in each
+// one-second window (by event timestamp), then only 1 in `sample_every` of the
+// rest. What is dropped is reported by a "dropped N events" marker from the
+// same source once its next window starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimit {
+    pub per_sec: u64,
+    #[serde(default = "default_sample_every")]
+    pub sample_every: u64,
+}
+
+fn default_sample_every() -> u64 { 100 }
+
+#[derive(Default)]
+struct Window {
+    second: u128,
+    seen: u64,
+    dropped: u64,
+}
+
+pub(super) struct RateLimiter {
+    limit: RateLimit,
+    windows: HashMap<String, Window>,
+}
+
+pub(super) enum Verdict {
+    Keep,
+    // Keep the event, storing this marker for the source's previous window first.
+    KeepAfter(Box<Event>),
+    Drop,
+}
+
+impl RateLimiter {
+    pub(super) fn new(limit: RateLimit) -> Self {
+        Self { limit, windows: HashMap::new() }
+    }
+
+    pub(super) fn check(&mut self, event: &Event) -> Verdict {
+        let second = event.ts_ms / 1000;
+        let window = self.windows.entry(event.source.clone()).or_default();
+        // Out-of-order timestamps count towards the current window.
+        if second > window.second {
+            let ended = std::mem::replace(window, Window { second, seen: 1, dropped: 0 });
+            return match ended.dropped {
+                0 => Verdict::Keep,
+                n => Verdict::KeepAfter(Box::new(dropped_marker(event, ended.second, n))),
+            };
+        }
+        window.seen += 1;
+        let over = window.seen.saturating_sub(self.limit.per_sec);
+        if over == 0 || (over - 1).is_multiple_of(self.limit.sample_every.max(1)) {
+            Verdict::Keep
+        } else {
+            window.dropped += 1;
+            Verdict::Drop
+        }
+    }
+}
+
+fn dropped_marker(next: &Event, second: u128, dropped: u64) -> Event {
+    let mut marker = Event::now(next.source.clone(), Level::Warn, format!("dropped {dropped} events"))
+        .with("dropped", dropped)
+        .with("window_start_ms", (second * 1000) as u64);
+    marker.ts_ms = next.ts_ms;
+    marker
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_each_source_and_reports_drops() {
+        let mut limiter = RateLimiter::new(RateLimit { per_sec: 2, sample_every: 3 });
+        let at = |source: &str, ts_ms| {
+            let mut ev = Event::now(source, "info", "retry");
+            ev.ts_ms = ts_ms;
+            ev
+        };
+        let kept: Vec<bool> = (0..8).map(|i| !matches!(limiter.check(&at("noisy", 5_000 + i)), Verdict::Drop)).collect();
+        // Two free, then 1 in 3 of the rest.
+        assert_eq!(kept, [true, true, true, false, false, true, false, false]);
+        assert!(matches!(limiter.check(&at("quiet", 5_001)), Verdict::Keep));
+        match limiter.check(&at("noisy", 6_200)) {
+            Verdict::KeepAfter(marker) => {
+                assert_eq!(marker.message, "dropped 4 events");
+                assert_eq!(marker.kv["window_start_ms"], 5_000);
+                assert_eq!(marker.level, Level::Warn);
+            }
+            _ => panic!("expected a dropped marker"),
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use super::Event;
+use crate::config::VaultConfig;
+use crate::module::Result;
+use regex::Regex;
+use serde_json::Value;
+use std::collections::HashSet;
+
+pub const MASK: &str = "[REDACTED]";
+
+// Masks sensitive data before an event is stored: the whole value of any kv
+// key named in `keys` (case-insensitive, at any depth), and every match of
+// `patterns` in the message and in kv string values.
+#[derive(Debug, Clone, Default)]
+pub struct Redactor {
+    keys: HashSet<String>,
+    patterns: Vec<Regex>,
+}
+
+impl Redactor {
+    pub fn new<K: AsRef<str>, P: AsRef<str>>(keys: &[K], patterns: &[P]) -> Result<Self> {
+        let patterns = patterns.iter()
+            .map(|p| Regex::new(p.as_ref()).map_err(|e| format!("invalid redaction pattern {:?}: {e}", p.as_ref())))
+            .collect::<std::result::Result<_, _>>()?;
+        Ok(Self { keys: keys.iter().map(|k| k.as_ref().to_lowercase()).collect(), patterns })
+    }
+
+    // `[vault] redact_keys` / `redact_patterns`; None when both are empty.
+    pub fn from_config(cfg: &VaultConfig) -> Result<Option<Self>> {
+        if cfg.redact_keys.is_empty() && cfg.redact_patterns.is_empty() {
+            return Ok(None);
+        }
+        Self::new(&cfg.redact_keys, &cfg.redact_patterns).map(Some)
+    }
+
+    pub fn redact(&self, event: &mut Event) {
+        if let Some(masked) = self.mask_text(&event.message) {
+            event.message = masked;
+        }
+        self.redact_value(&mut event.kv);
+    }
+
+    fn redact_value(&self, value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                for (key, v) in map.iter_mut() {
+                    if self.keys.contains(&key.to_lowercase()) {
+                        *v = Value::String(MASK.to_string());
+                    } else {
+                        self.redact_value(v);
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|v| self.redact_value(v)),
+            Value::String(s) => {
+                if let Some(masked) = self.mask_text(s) {
+                    *s = masked;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn mask_text(&self, text: &str) -> Option<String> {
+        let mut masked: Option<String> = None;
+        for re in &self.patterns {
+            let current = masked.as_deref().unwrap_or(text);
+            if re.is_match(current) {
+                masked = Some(re.replace_all(current, MASK).into_owned());
+            }
+        }
+        masked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn masks_keys_at_any_depth_and_pattern_matches() {
+        let redactor = Redactor::new(&["password", "Token"], &[r"[\w.+-]+@[\w-]+\.[\w.]+"]).unwrap();
+        let mut ev = Event::now("auth", "info", "reset sent to ada@example.com")
+            .with("TOKEN", "abc")
+            .with("user", json!({"email": "ada@example.com", "password": "hunter2", "id": 7}));
+        redactor.redact(&mut ev);
+        assert_eq!(ev.message, "reset sent to [REDACTED]");
+        assert_eq!(ev.kv, json!({"TOKEN": MASK, "user": {"email": MASK, "password": MASK, "id": 7}}));
+        assert!(Redactor::new::<&str, _>(&[], &["("]).is_err());
+    }
+}
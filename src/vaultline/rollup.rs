@@ -0,0 +1,107 @@
+use super::{Event, Level};
+use std::collections::HashMap;
+
+struct Open {
+    first: Event,
+    count: u64,
+    last_ts_ms: u128,
+}
+
+// Collapses events with the same (source, level, message) into the first of
+// them, held back until `window_ms` after its timestamp. A collapsed record
+// gets `count` and `last_ts_ms` kv fields; lone events are stored unchanged.
+#[derive(Default)]
+pub(super) struct Rollup {
+    open: HashMap<(String, Level, String), Open>,
+}
+
+impl Rollup {
+    pub(super) fn len(&self) -> usize {
+        self.open.len()
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.open.is_empty()
+    }
+
+    // Fold `batch` into the open windows and return the records ready to store:
+    // windows that ended before each event's timestamp, oldest first. With no
+    // window every open record is released and the batch passes straight through.
+    pub(super) fn absorb(&mut self, batch: Vec<Event>, window_ms: Option<u128>) -> Vec<Event> {
+        let Some(window_ms) = window_ms else {
+            let mut ready = self.drain();
+            ready.extend(batch);
+            return ready;
+        };
+        let mut ready = Vec::new();
+        for event in batch {
+            self.close_before(event.ts_ms, window_ms, &mut ready);
+            let key = (event.source.clone(), event.level.clone(), event.message.clone());
+            match self.open.get_mut(&key) {
+                Some(open) => {
+                    open.count += 1;
+                    open.last_ts_ms = open.last_ts_ms.max(event.ts_ms);
+                }
+                None => {
+                    let last_ts_ms = event.ts_ms;
+                    self.open.insert(key, Open { first: event, count: 1, last_ts_ms });
+                }
+            }
+        }
+        ready
+    }
+
+    // Every open record, oldest first.
+    pub(super) fn drain(&mut self) -> Vec<Event> {
+        let mut ready = Vec::new();
+        self.close_before(u128::MAX, 0, &mut ready);
+        ready
+    }
+
+    fn close_before(&mut self, ts_ms: u128, window_ms: u128, ready: &mut Vec<Event>) {
+        let start = ready.len();
+        let closed: Vec<_> = self.open.iter()
+            .filter(|(_, open)| open.first.ts_ms.saturating_add(window_ms) <= ts_ms)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in closed {
+            if let Some(open) = self.open.remove(&key) {
+                ready.push(collapse(open));
+            }
+        }
+        ready[start..].sort_by_key(|ev| ev.ts_ms);
+    }
+}
+
+fn collapse(open: Open) -> Event {
+    if open.count == 1 {
+        return open.first;
+    }
+    open.first.with("count", open.count).with("last_ts_ms", open.last_ts_ms as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(message: &str, ts_ms: u128) -> Event {
+        let mut ev = Event::now("epoch", "error", message);
+        ev.ts_ms = ts_ms;
+        ev
+    }
+
+    #[test]
+    fn collapses_repeats_within_the_window() {
+        let mut rollup = Rollup::default();
+        let storm: Vec<Event> = (0..50).map(|i| at("retry failed", 1_000 + i * 10)).collect();
+        assert!(rollup.absorb(storm, Some(1_000)).is_empty());
+        let ready = rollup.absorb(vec![at("other", 1_500), at("retry failed", 2_000)], Some(1_000));
+        assert_eq!(ready.len(), 1);
+        assert_eq!((ready[0].ts_ms, &ready[0].kv["count"], &ready[0].kv["last_ts_ms"]), (1_000, &50.into(), &1_490.into()));
+
+        let rest = rollup.drain();
+        assert_eq!(rest.iter().map(|ev| ev.message.as_str()).collect::<Vec<_>>(), ["other", "retry failed"]);
+        assert!(rest[0].kv.get("count").is_none());
+        assert!(rollup.is_empty());
+    }
+}
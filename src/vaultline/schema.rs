@@ -0,0 +1,56 @@
+use super::Event;
+use crate::module::Result;
+use serde_json::{Map, Value};
+
+// Version stamped on every new event. Bump it together with a new entry in
+// `MIGRATIONS` whenever the stored shape of `Event` changes.
+pub const SCHEMA_VERSION: u32 = 1;
+
+// `MIGRATIONS[n]` upgrades a record from version `n` to `n + 1`.
+const MIGRATIONS: [fn(&mut Map<String, Value>); SCHEMA_VERSION as usize] = [v0_to_v1];
+
+// Records written before versioning may have no `kv` at all.
+fn v0_to_v1(record: &mut Map<String, Value>) {
+    record.entry("kv").or_insert(Value::Null);
+}
+
+// Parse a stored record of any known version into the current `Event` shape.
+// Ok(None) means the record is not an event; a version newer than this build
+// understands is an error so the record is never mistaken for garbage.
+pub(super) fn upgrade(value: Value) -> Result<Option<Event>> {
+    let Value::Object(mut record) = value else { return Ok(None) };
+    let version = record.get("schema_version").and_then(Value::as_u64).unwrap_or(0);
+    if version > u64::from(SCHEMA_VERSION) {
+        return Err(format!("record has schema version {version}; this build reads up to {SCHEMA_VERSION}").into());
+    }
+    for migrate in &MIGRATIONS[version as usize..] {
+        migrate(&mut record);
+    }
+    record.insert("schema_version".into(), SCHEMA_VERSION.into());
+    Ok(serde_json::from_value(Value::Object(record)).ok())
+}
+
+// `upgrade` for an already-decoded event (binary records).
+pub(super) fn upgrade_event(event: Event) -> Result<Option<Event>> {
+    if event.schema_version == SCHEMA_VERSION {
+        return Ok(Some(event));
+    }
+    upgrade(serde_json::to_value(event)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upgrades_unversioned_records() {
+        let ev = upgrade(serde_json::json!({ "ts_ms": 5, "source": "epoch", "level": "info", "message": "old" }))
+            .unwrap()
+            .unwrap();
+        assert_eq!((ev.schema_version, ev.kv.clone()), (SCHEMA_VERSION, Value::Null));
+        assert_eq!(upgrade(Value::from("not an event")).unwrap(), None);
+
+        let future = serde_json::json!({ "schema_version": SCHEMA_VERSION + 1, "ts_ms": 5, "source": "s", "level": "info", "message": "m" });
+        assert!(upgrade(future).is_err());
+    }
+}
@@ -0,0 +1,97 @@
+use super::Vaultline;
+use crate::module::{Health, Module, Periodic, Result};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// Runs a shared vaultline as a `Module`: `start` spawns a thread that flushes
+// buffered appends and checks rotation every `interval`, `stop` joins it and
+// closes the file, and `health` reports failed background writes and the
+// backlog of events not yet written.
+pub struct VaultlineService {
+    shared: Arc<Mutex<Vaultline>>,
+    max_backlog: usize,
+    task: Periodic,
+}
+
+impl VaultlineService {
+    pub fn new(shared: Arc<Mutex<Vaultline>>, interval: Duration) -> Self {
+        Self { shared, max_backlog: 10_000, task: Periodic::new("vaultline service", interval) }
+    }
+
+    // Report Degraded once more than this many events are waiting (default 10,000).
+    pub fn max_backlog(mut self, max: usize) -> Self {
+        self.max_backlog = max;
+        self
+    }
+
+    pub fn vaultline(&self) -> Arc<Mutex<Vaultline>> {
+        self.shared.clone()
+    }
+
+    fn tick(vault: &Mutex<Vaultline>) -> Result<()> {
+        let mut vault = vault.lock().map_err(|_| "vaultline mutex poisoned")?;
+        vault.flush_writes()?;
+        vault.rotate_if_due()
+    }
+}
+
+impl Module for VaultlineService {
+    fn name(&self) -> &str { "vaultline" }
+
+    fn start(&mut self) -> Result<()> {
+        let shared = self.shared.clone();
+        self.task.start(move || Self::tick(&shared))
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.task.stop()?;
+        self.shared.lock().map_err(|_| "vaultline mutex poisoned")?.close()
+    }
+
+    fn health(&self) -> Health {
+        let health = self.task.health("write failed");
+        if health != Health::Healthy {
+            return health;
+        }
+        // Don't block on a busy vaultline; the backlog is only a hint.
+        let backlog = self.shared.try_lock().map(|vault| vault.backlog()).unwrap_or(0);
+        if backlog > self.max_backlog {
+            return Health::Degraded { reason: format!("{backlog} events waiting to be written") };
+        }
+        Health::Healthy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vaultline::{DurabilityPolicy, Event};
+    use std::thread;
+    use std::time::Instant;
+
+    #[test]
+    fn flushes_in_background_and_closes_on_stop() {
+        let path = std::env::temp_dir().join(format!("vaultline_service_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut vault = Vaultline::new(&path).unwrap();
+        vault.set_durability(DurabilityPolicy::None);
+        let mut service = VaultlineService::new(Arc::new(Mutex::new(vault)), Duration::from_millis(20)).max_backlog(0);
+        service.start().unwrap();
+
+        let shared = service.vaultline();
+        shared.lock().unwrap().append(Event::now("axiom", "info", "buffered")).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while std::fs::read_to_string(&path).unwrap_or_default().is_empty() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(std::fs::read_to_string(&path).unwrap().contains("buffered"));
+        assert_eq!(service.health(), Health::Healthy);
+
+        shared.lock().unwrap().set_rollup_window(Some(Duration::from_secs(60)));
+        shared.lock().unwrap().append(Event::now("axiom", "info", "held")).unwrap();
+        assert_eq!(shared.lock().unwrap().backlog(), 1);
+        service.stop().unwrap();
+        assert!(std::fs::read_to_string(&path).unwrap().contains("held"));
+        let _ = std::fs::remove_file(&path);
+    }
+}
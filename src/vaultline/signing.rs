@@ -0,0 +1,78 @@
+use super::{chain, Event};
+use crate::config::VaultConfig;
+use crate::module::Result;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::Serialize;
+
+// Resolve the signing key: `HYPERION_SIGNING_KEY` wins over `[vault] signing_key`.
+// Keys are 64 hex characters (the 32-byte ed25519 seed).
+pub fn load_signing_key(cfg: &VaultConfig) -> Result<Option<[u8; 32]>> {
+    let hex = match std::env::var("HYPERION_SIGNING_KEY") {
+        Ok(v) => v,
+        Err(_) => match cfg.signing_key {
+            Some(ref v) => v.clone(),
+            None => return Ok(None),
+        },
+    };
+    decode_hex(hex.trim(), "signing key").map(Some)
+}
+
+// Public half of a signing seed, for handing to downstream verifiers.
+pub fn public_key(seed: &[u8; 32]) -> [u8; 32] {
+    SigningKey::from_bytes(seed).verifying_key().to_bytes()
+}
+
+// Check `event.signature` against `public_key`. Ok(false) means unsigned or a
+// signature that does not match; malformed keys and signatures are errors.
+pub fn verify_event(event: &Event, public_key: &[u8; 32]) -> Result<bool> {
+    let Some(ref hex) = event.signature else {
+        return Ok(false);
+    };
+    let key = VerifyingKey::from_bytes(public_key)?;
+    let signature = Signature::from_bytes(&decode_hex(hex, "signature")?);
+    Ok(key.verify(&signed_bytes(event)?, &signature).is_ok())
+}
+
+// Outcome of `Vaultline::verify_signatures`. Positions count records in log
+// order, oldest archive first.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SignatureReport {
+    pub records: usize,
+    pub signed: usize,
+    // Signed records whose signature does not match the key.
+    pub invalid: Vec<usize>,
+}
+
+pub(super) struct EventSigner {
+    key: SigningKey,
+}
+
+impl EventSigner {
+    pub(super) fn new(seed: &[u8; 32]) -> Self {
+        Self { key: SigningKey::from_bytes(seed) }
+    }
+
+    // Signs everything but the signature itself and `schema_version`, `seq` and
+    // `prev_hash` included.
+    pub(super) fn sign(&self, event: &mut Event) -> Result<()> {
+        event.signature = None;
+        let signature = self.key.sign(&signed_bytes(event)?);
+        event.signature = Some(hex::encode(signature.to_bytes()));
+        Ok(())
+    }
+}
+
+fn signed_bytes(event: &Event) -> Result<Vec<u8>> {
+    if event.signature.is_none() {
+        return chain::canonical_bytes(event);
+    }
+    let mut unsigned = event.clone();
+    unsigned.signature = None;
+    chain::canonical_bytes(&unsigned)
+}
+
+fn decode_hex<const N: usize>(hex: &str, what: &str) -> Result<[u8; N]> {
+    let mut out = [0u8; N];
+    hex::decode_to_slice(hex, &mut out).map_err(|_| format!("{what} must be {} hex characters", N * 2))?;
+    Ok(out)
+}
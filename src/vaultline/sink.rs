@@ -0,0 +1,45 @@
+use super::Event;
+use crate::module::Result;
+use std::io::Write;
+
+// Extra destination for stored events, fed alongside the vaultline's own file
+// or store. Register sinks at runtime with `Vaultline::add_sink`.
+pub trait EventSink: Send {
+    // Receive a batch of stored events, oldest first.
+    fn write(&mut self, events: &[Event]) -> Result<()>;
+
+    // Push anything buffered to its destination (called by `Vaultline::flush`).
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+// Writes each event as an NDJSON line to any writer: a second file, stderr, a socket.
+pub struct NdjsonSink<W> {
+    out: W,
+}
+
+impl<W: Write + Send> NdjsonSink<W> {
+    pub fn new(out: W) -> Self {
+        Self { out }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl<W: Write + Send> EventSink for NdjsonSink<W> {
+    fn write(&mut self, events: &[Event]) -> Result<()> {
+        for event in events {
+            serde_json::to_writer(&mut self.out, event)?;
+            self.out.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,96 @@
+use super::{parse_record, with_crc, Event, SCHEMA_VERSION};
+use crate::module::Result;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+// Layout version of the snapshot file itself.
+const SNAPSHOT_VERSION: u32 = 1;
+
+// First line of a snapshot. `crc` covers every event line that follows
+// (newlines included), so a truncated or edited snapshot is rejected as a whole.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotMeta {
+    pub snapshot_version: u32,
+    pub schema_version: u32,
+    pub created_ms: u128,
+    // Segments (archives plus the active file) the events were read from.
+    pub segments: usize,
+    pub events: usize,
+    pub crc: String,
+}
+
+// Write `events` to a new snapshot file: the metadata line followed by one
+// checksummed NDJSON line per event. `path` is claimed up front (an existing
+// file is never overwritten), then the file is written beside it and renamed
+// into place once synced.
+pub(super) fn write(path: &Path, events: &[Event], segments: usize, created_ms: u128) -> Result<SnapshotMeta> {
+    let mut lines = Vec::with_capacity(events.len());
+    let mut digest = crc32fast::Hasher::new();
+    for event in events {
+        let line = with_crc(&serde_json::to_string(event)?);
+        digest.update(line.as_bytes());
+        digest.update(b"\n");
+        lines.push(line);
+    }
+    let meta = SnapshotMeta {
+        snapshot_version: SNAPSHOT_VERSION,
+        schema_version: SCHEMA_VERSION,
+        created_ms,
+        segments,
+        events: events.len(),
+        crc: format!("{:08x}", digest.finalize()),
+    };
+
+    drop(super::create_new(path)?);
+    write_claimed(path, &meta, &lines).inspect_err(|_| {
+        let _ = std::fs::remove_file(path);
+    })?;
+    Ok(meta)
+}
+
+fn write_claimed(path: &Path, meta: &SnapshotMeta, lines: &[String]) -> Result<()> {
+    let tmp = super::sibling_path(path, ".tmp");
+    let file = std::fs::File::create(&tmp)?;
+    let mut out = BufWriter::new(&file);
+    serde_json::to_writer(&mut out, meta)?;
+    out.write_all(b"\n")?;
+    for line in lines {
+        out.write_all(line.as_bytes())?;
+        out.write_all(b"\n")?;
+    }
+    out.flush()?;
+    drop(out);
+    file.sync_all()?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+// Read and verify a snapshot, returning its metadata and events.
+pub(super) fn read(path: &Path) -> Result<(SnapshotMeta, Vec<Event>)> {
+    let corrupt = |why: &str| format!("snapshot {} is corrupt: {why}", path.display());
+    let mut lines = BufReader::new(std::fs::File::open(path)?).lines();
+    let header = lines.next().ok_or_else(|| corrupt("empty file"))??;
+    let meta: SnapshotMeta = serde_json::from_str(&header).map_err(|_| corrupt("missing metadata line"))?;
+    if meta.snapshot_version > SNAPSHOT_VERSION {
+        return Err(format!("snapshot version {} is newer than this build reads ({SNAPSHOT_VERSION})", meta.snapshot_version).into());
+    }
+
+    let mut digest = crc32fast::Hasher::new();
+    // `meta.events` is only checked once every line is read, so it can't size
+    // the buffer.
+    let mut events = Vec::new();
+    for line in lines {
+        let line = line?;
+        digest.update(line.as_bytes());
+        digest.update(b"\n");
+        events.push(parse_record(&line, true)?.ok_or_else(|| corrupt(&format!("bad record {}", events.len() + 1)))?);
+    }
+    if events.len() != meta.events {
+        return Err(corrupt(&format!("expected {} events, found {}", meta.events, events.len())).into());
+    }
+    if format!("{:08x}", digest.finalize()) != meta.crc {
+        return Err(corrupt("checksum mismatch").into());
+    }
+    Ok((meta, events))
+}
@@ -0,0 +1,361 @@
+use super::Event;
+use crate::module::Result;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+// Where a vaultline persists its events. `Vaultline::new` writes through a
+// `FileStore` on its path; plug in another (e.g. `SqliteStore`) with
+// `Vaultline::with_store`.
+pub trait EventStore: Send {
+    fn append(&mut self, event: &Event) -> Result<()>;
+
+    // Every stored event, oldest first.
+    fn scan(&mut self) -> Result<Vec<Event>>;
+
+    // Remove every stored event.
+    fn truncate(&mut self) -> Result<()>;
+
+    // Store several events at once; stores that can should make this atomic.
+    fn append_batch(&mut self, events: &[Event]) -> Result<()> {
+        for event in events {
+            self.append(event)?;
+        }
+        Ok(())
+    }
+
+    // Replace the stored events with `events` (used by compaction).
+    fn replace(&mut self, events: &[Event]) -> Result<()> {
+        self.truncate()?;
+        self.append_batch(events)
+    }
+}
+
+// Events kept in a plain vector; nothing survives the process.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    events: Vec<Event>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+}
+
+impl EventStore for MemoryStore {
+    fn append(&mut self, event: &Event) -> Result<()> {
+        self.events.push(event.clone());
+        Ok(())
+    }
+
+    fn scan(&mut self) -> Result<Vec<Event>> {
+        Ok(self.events.clone())
+    }
+
+    fn truncate(&mut self) -> Result<()> {
+        self.events.clear();
+        Ok(())
+    }
+}
+
+// NDJSON file, one event per line; the default store behind `Vaultline::new`.
+// Appends go through a buffered handle kept open until `close`, so callers
+// decide when to `flush` and `sync`. A vaultline also writes its own framed
+// records (checksummed, encrypted or binary) with `append_encoded`.
+pub struct FileStore {
+    path: PathBuf,
+    writer: Option<BufWriter<File>>,
+}
+
+impl FileStore {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self { path, writer: None })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn encode(events: &[Event]) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        for event in events {
+            serde_json::to_writer(&mut buf, event)?;
+            buf.push(b'\n');
+        }
+        Ok(buf)
+    }
+
+    // The append handle, reopened after `close`.
+    fn writer(&mut self) -> Result<&mut BufWriter<File>> {
+        if self.writer.is_none() {
+            let file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+            self.writer = Some(BufWriter::new(file));
+        }
+        Ok(self.writer.as_mut().expect("writer was just opened"))
+    }
+
+    // Buffer already-encoded records for the end of the file.
+    pub fn append_encoded(&mut self, buf: &[u8]) -> Result<()> {
+        self.writer()?.write_all(buf)?;
+        Ok(())
+    }
+
+    // The underlying file with nothing left buffered, for writes that must
+    // bypass the buffer (e.g. under an advisory lock).
+    pub fn file_mut(&mut self) -> Result<&mut File> {
+        let writer = self.writer()?;
+        writer.flush()?;
+        Ok(writer.get_mut())
+    }
+
+    // Hand buffered appends to the OS.
+    pub fn flush(&mut self) -> Result<()> {
+        if let Some(ref mut writer) = self.writer {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    // Flush, then fsync the file.
+    pub fn sync(&mut self) -> Result<()> {
+        self.flush()?;
+        if let Some(ref writer) = self.writer {
+            writer.get_ref().sync_all()?;
+        }
+        Ok(())
+    }
+
+    // Flush and drop the handle before the file is rewritten or renamed; the
+    // next append reopens it.
+    pub fn close(&mut self) -> Result<()> {
+        self.flush()?;
+        self.writer = None;
+        Ok(())
+    }
+
+    // Size of the file once buffered appends land; 0 while no handle is open.
+    pub fn size(&self) -> Result<u64> {
+        let Some(ref writer) = self.writer else { return Ok(0) };
+        Ok(writer.get_ref().metadata()?.len() + writer.buffer().len() as u64)
+    }
+}
+
+impl EventStore for FileStore {
+    fn append(&mut self, event: &Event) -> Result<()> {
+        self.append_batch(std::slice::from_ref(event))
+    }
+
+    // Lines that do not parse (or fail their checksum) are skipped.
+    fn scan(&mut self) -> Result<Vec<Event>> {
+        self.flush()?;
+        let mut events = Vec::new();
+        for line in BufReader::new(File::open(&self.path)?).lines() {
+            if let Some(ev) = super::parse_record(&line?, false)? {
+                events.push(ev);
+            }
+        }
+        Ok(events)
+    }
+
+    fn truncate(&mut self) -> Result<()> {
+        self.close()?;
+        File::create(&self.path)?;
+        Ok(())
+    }
+
+    // Handed to the OS in one write per batch, so a batch lands contiguously.
+    fn append_batch(&mut self, events: &[Event]) -> Result<()> {
+        self.append_encoded(&Self::encode(events)?)?;
+        self.flush()
+    }
+
+    // Written to `<file>.tmp` and renamed over the original.
+    fn replace(&mut self, events: &[Event]) -> Result<()> {
+        self.close()?;
+        let tmp = super::sibling_path(&self.path, ".tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(&Self::encode(events)?)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+// Layout of the `events` table, kept in SQLite's `user_version`.
+#[cfg(feature = "sqlite")]
+const SQLITE_SCHEMA: i64 = 1;
+
+// Columns added to `events` after its first release, in order.
+#[cfg(feature = "sqlite")]
+const ADDED_COLUMNS: [(&str, &str); 7] = [
+    ("seq", "INTEGER NOT NULL DEFAULT 0"),
+    ("correlation_id", "TEXT"),
+    ("trace_id", "TEXT"),
+    ("idempotency_key", "TEXT"),
+    ("prev_hash", "TEXT"),
+    ("signature", "TEXT"),
+    ("expires_at", "INTEGER"),
+];
+
+// SQLite table `events`, indexed by timestamp and source. Use `connection()`
+// for ad-hoc SQL over the stored events.
+#[cfg(feature = "sqlite")]
+pub struct SqliteStore {
+    conn: rusqlite::Connection,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteStore {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::init(rusqlite::Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        Self::init(rusqlite::Connection::open_in_memory()?)
+    }
+
+    fn init(conn: rusqlite::Connection) -> Result<Self> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS events (
+                id      INTEGER PRIMARY KEY AUTOINCREMENT,
+                ts_ms   INTEGER NOT NULL,
+                source  TEXT NOT NULL,
+                level   TEXT NOT NULL,
+                message TEXT NOT NULL,
+                kv      TEXT NOT NULL,
+                seq     INTEGER NOT NULL DEFAULT 0,
+                correlation_id TEXT,
+                trace_id       TEXT,
+                idempotency_key TEXT,
+                prev_hash      TEXT,
+                signature      TEXT,
+                expires_at     INTEGER
+            );
+            CREATE INDEX IF NOT EXISTS events_ts ON events (ts_ms);
+            CREATE INDEX IF NOT EXISTS events_source ON events (source, ts_ms);",
+        )?;
+        Self::migrate(&conn)?;
+        Ok(Self { conn })
+    }
+
+    // Bring a table created by an older build up to `SQLITE_SCHEMA`. Tables from
+    // before the version was tracked (user_version 0) may lack any of the
+    // columns added since the first one, so each missing column is added.
+    fn migrate(conn: &rusqlite::Connection) -> Result<()> {
+        let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version >= SQLITE_SCHEMA {
+            return Ok(());
+        }
+        let mut stmt = conn.prepare("SELECT name FROM pragma_table_info('events')")?;
+        let existing = stmt.query_map([], |row| row.get::<_, String>(0))?.collect::<rusqlite::Result<Vec<_>>>()?;
+        for (column, decl) in ADDED_COLUMNS {
+            if !existing.iter().any(|name| name == column) {
+                conn.execute_batch(&format!("ALTER TABLE events ADD COLUMN {column} {decl}"))?;
+            }
+        }
+        conn.execute_batch(&format!("PRAGMA user_version = {SQLITE_SCHEMA}"))?;
+        Ok(())
+    }
+
+    pub fn connection(&self) -> &rusqlite::Connection {
+        &self.conn
+    }
+
+    fn insert(conn: &rusqlite::Connection, event: &Event) -> Result<()> {
+        let ts = i64::try_from(event.ts_ms).map_err(|_| "event timestamp out of range for sqlite")?;
+        let expires_at = event.expires_at.map(i64::try_from).transpose().map_err(|_| "event expiry out of range for sqlite")?;
+        conn.execute(
+            "INSERT INTO events (ts_ms, source, level, message, kv, seq, correlation_id, trace_id, idempotency_key, prev_hash, signature, expires_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            rusqlite::params![
+                ts,
+                event.source,
+                event.level.as_str(),
+                event.message,
+                event.kv.to_string(),
+                event.seq as i64,
+                event.correlation_id,
+                event.trace_id,
+                event.idempotency_key,
+                event.prev_hash,
+                event.signature,
+                expires_at,
+            ],
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl EventStore for SqliteStore {
+    fn append(&mut self, event: &Event) -> Result<()> {
+        Self::insert(&self.conn, event)
+    }
+
+    fn scan(&mut self) -> Result<Vec<Event>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT ts_ms, source, level, message, kv, seq, correlation_id, trace_id, idempotency_key, prev_hash, signature, expires_at FROM events ORDER BY id",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            let kv: String = row.get(4)?;
+            let event = Event {
+                ts_ms: row.get::<_, i64>(0)? as u128,
+                source: row.get(1)?,
+                level: super::Level::parse(&row.get::<_, String>(2)?),
+                message: row.get(3)?,
+                kv: serde_json::Value::Null,
+                seq: row.get::<_, i64>(5)? as u64,
+                correlation_id: row.get(6)?,
+                trace_id: row.get(7)?,
+                idempotency_key: row.get(8)?,
+                prev_hash: row.get(9)?,
+                signature: row.get(10)?,
+                expires_at: row.get::<_, Option<i64>>(11)?.map(|t| t as u128),
+                schema_version: super::SCHEMA_VERSION,
+            };
+            Ok((event, kv))
+        })?;
+        let mut events = Vec::new();
+        for row in rows {
+            let (mut event, kv) = row?;
+            event.kv = serde_json::from_str(&kv)?;
+            events.push(event);
+        }
+        Ok(events)
+    }
+
+    fn truncate(&mut self) -> Result<()> {
+        self.conn.execute("DELETE FROM events", [])?;
+        Ok(())
+    }
+
+    // All rows commit together or not at all.
+    fn append_batch(&mut self, events: &[Event]) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        for event in events {
+            Self::insert(&tx, event)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn replace(&mut self, events: &[Event]) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute("DELETE FROM events", [])?;
+        for event in events {
+            Self::insert(&tx, event)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
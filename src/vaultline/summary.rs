@@ -0,0 +1,177 @@
+use super::{Event, VaultlineStats};
+use crate::module::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+const HOUR_MS: u128 = 3_600_000;
+
+// Events of one source and level within one hour (`hour` counts hours since
+// the epoch, by `ts_ms`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HourlyCount {
+    pub hour: u64,
+    pub source: String,
+    pub level: String,
+    pub count: u64,
+    pub first_ms: u128,
+    pub last_ms: u128,
+}
+
+impl HourlyCount {
+    pub fn start_ms(&self) -> u128 {
+        u128::from(self.hour) * HOUR_MS
+    }
+}
+
+// Rolling counts kept in the `<file>.summary` sidecar: one row per bucket and
+// batch, appended as batches are stored and summed on read.
+#[derive(Debug, Default)]
+pub(super) struct Summary {
+    buckets: BTreeMap<(u64, String, String), HourlyCount>,
+}
+
+impl Summary {
+    pub(super) fn add(&mut self, ev: &Event) {
+        self.merge(HourlyCount {
+            hour: (ev.ts_ms / HOUR_MS) as u64,
+            source: ev.source.clone(),
+            level: ev.level.to_string(),
+            count: 1,
+            first_ms: ev.ts_ms,
+            last_ms: ev.ts_ms,
+        });
+    }
+
+    fn merge(&mut self, row: HourlyCount) {
+        let key = (row.hour, row.source.clone(), row.level.clone());
+        match self.buckets.get_mut(&key) {
+            Some(b) => {
+                b.count += row.count;
+                b.first_ms = b.first_ms.min(row.first_ms);
+                b.last_ms = b.last_ms.max(row.last_ms);
+            }
+            None => {
+                self.buckets.insert(key, row);
+            }
+        }
+    }
+
+    pub(super) fn of(batch: &[Event]) -> Self {
+        let mut summary = Self::default();
+        for ev in batch {
+            summary.add(ev);
+        }
+        summary
+    }
+
+    // Sum the sidecar's rows. Once it holds more than twice as many rows as
+    // buckets it is rewritten with one row per bucket, unless `read_only`. None
+    // when a row can't be read (e.g. torn by a crash mid-append): the sidecar
+    // must then be rebuilt.
+    pub(super) fn read(path: &Path, read_only: bool) -> Result<Option<Self>> {
+        let mut summary = Self::default();
+        let mut rows = 0;
+        for line in BufReader::new(File::open(path)?).lines() {
+            let Ok(row) = serde_json::from_str(&line?) else { return Ok(None) };
+            summary.merge(row);
+            rows += 1;
+        }
+        if !read_only && rows > 2 * summary.buckets.len() {
+            summary.write(path)?;
+        }
+        Ok(Some(summary))
+    }
+
+    pub(super) fn append(&self, path: &Path) -> Result<()> {
+        self.write_rows(OpenOptions::new().create(true).append(true).open(path)?)
+    }
+
+    // Replace the sidecar with this summary, via a temp file and a rename.
+    pub(super) fn write(&self, path: &Path) -> Result<()> {
+        let tmp = super::sibling_path(path, ".tmp");
+        self.write_rows(File::create(&tmp)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    fn write_rows(&self, file: File) -> Result<()> {
+        let mut out = BufWriter::new(file);
+        for row in self.buckets.values() {
+            writeln!(out, "{}", serde_json::to_string(row)?)?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    pub(super) fn fill(&self, stats: &mut VaultlineStats) {
+        for b in self.buckets.values() {
+            let n = b.count as usize;
+            stats.total += n;
+            *stats.by_level.entry(b.level.clone()).or_default() += n;
+            *stats.by_source.entry(b.source.clone()).or_default() += n;
+            stats.oldest_ms = Some(stats.oldest_ms.map_or(b.first_ms, |t| t.min(b.first_ms)));
+            stats.newest_ms = Some(stats.newest_ms.map_or(b.last_ms, |t| t.max(b.last_ms)));
+        }
+    }
+
+    // Buckets ordered by hour, then source and level.
+    pub(super) fn into_rows(self) -> Vec<HourlyCount> {
+        self.buckets.into_values().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vaultline::{Event, Filter, Vaultline};
+
+    #[test]
+    fn stats_come_from_the_sidecar() {
+        let path = std::env::temp_dir().join(format!("vaultline_summary_{}.log", std::process::id()));
+        let sidecar = super::super::sibling_path(&path, ".summary");
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&sidecar);
+        let at = |source: &str, level: &str, ts_ms: u128| {
+            let mut ev = Event::now(source, level, "tick");
+            ev.ts_ms = ts_ms;
+            ev
+        };
+
+        // Events written before summaries were on are counted by the first rebuild.
+        let mut vault = Vaultline::new(&path).unwrap();
+        vault.append_batch(vec![at("epoch", "info", 1_000), at("epoch", "error", 2_000)]).unwrap();
+        vault.set_summaries(true);
+        let first = vault.stats().unwrap();
+        assert!(sidecar.exists());
+        vault.append_batch(vec![at("axiom", "info", 3_600_000), at("epoch", "info", 7_300_000)]).unwrap();
+
+        // Counts match a reader that scans every segment.
+        let mut scanned = Vaultline::open_read_only(&path).unwrap();
+        let counts = |v: &mut Vaultline| {
+            let s = v.stats().unwrap();
+            (s.total, s.by_level, s.by_source, s.oldest_ms, s.newest_ms)
+        };
+        assert_eq!(first.total, 2);
+        assert_eq!(counts(&mut vault), counts(&mut scanned));
+        let hours = vault.hourly_counts().unwrap();
+        let hours: Vec<_> = hours.iter().map(|c| (c.hour, c.source.as_str(), c.level.as_str(), c.count)).collect();
+        assert_eq!(hours, [(0, "epoch", "error", 1), (0, "epoch", "info", 1), (1, "axiom", "info", 1), (2, "epoch", "info", 1)]);
+
+        // Erasure drops the sidecar; the next read rebuilds it from the log.
+        vault.erase(&Filter::new().source("axiom")).unwrap();
+        assert!(!sidecar.exists());
+        assert_eq!(counts(&mut vault), counts(&mut scanned));
+        assert!(sidecar.exists());
+        assert_eq!(vault.stats().unwrap().by_source.get("axiom"), None);
+
+        // A row torn by a crash mid-append makes the sidecar rebuild, not fail.
+        let text = std::fs::read_to_string(&sidecar).unwrap();
+        std::fs::write(&sidecar, format!("{text}{{\"hour\":3,\"sou")).unwrap();
+        assert_eq!(counts(&mut vault), counts(&mut scanned));
+        assert_eq!(vault.hourly_counts().unwrap(), scanned.hourly_counts().unwrap());
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&sidecar);
+    }
+}
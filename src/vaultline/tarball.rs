@@ -0,0 +1,67 @@
+use super::{is_compressed, sibling_path};
+use crate::module::Result;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+// Pack `segments` into a new tar file at `dest`, each under its file name,
+// gzipping the whole archive when `dest` ends in `.gz`. Written to a temp file
+// and renamed, so `dest` is complete or absent.
+pub(super) fn write(dest: &Path, segments: &[PathBuf]) -> Result<()> {
+    let tmp = sibling_path(dest, ".tmp");
+    let file = File::create(&tmp)?;
+    let file = if is_compressed(dest) {
+        #[cfg(feature = "compression")]
+        {
+            let gz = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+            pack(gz, segments)?.finish()?
+        }
+        #[cfg(not(feature = "compression"))]
+        {
+            drop(file);
+            let _ = std::fs::remove_file(&tmp);
+            return Err("tar.gz archives require the `compression` feature".into());
+        }
+    } else {
+        pack(file, segments)?
+    };
+    file.sync_all()?;
+    std::fs::rename(&tmp, dest)?;
+    Ok(())
+}
+
+fn pack<W: Write>(out: W, segments: &[PathBuf]) -> Result<W> {
+    let mut builder = tar::Builder::new(out);
+    for seg in segments {
+        let name = seg.file_name().ok_or_else(|| format!("{} has no file name", seg.display()))?;
+        builder.append_path_with_name(seg, name)?;
+    }
+    Ok(builder.into_inner()?)
+}
+
+// Visit each segment in a tar written by `write` as (file name, contents), in
+// archive order. Gzipped segments are handed over still compressed.
+pub(super) fn read(path: &Path, mut visit: impl FnMut(&str, Vec<u8>) -> Result<()>) -> Result<()> {
+    let file = File::open(path)?;
+    let input: Box<dyn Read> = if is_compressed(path) {
+        #[cfg(feature = "compression")]
+        {
+            Box::new(flate2::read::GzDecoder::new(file))
+        }
+        #[cfg(not(feature = "compression"))]
+        {
+            return Err(format!("{} is compressed; rebuild with the `compression` feature", path.display()).into());
+        }
+    } else {
+        Box::new(file)
+    };
+    let mut archive = tar::Archive::new(input);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.to_string_lossy().into_owned();
+        let mut contents = Vec::new();
+        entry.read_to_end(&mut contents)?;
+        visit(&name, contents)?;
+    }
+    Ok(())
+}
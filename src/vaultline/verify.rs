@@ -0,0 +1,111 @@
+use super::{chain, Event};
+use crate::module::Result;
+use serde::Serialize;
+
+// Problems described in full in `VerifyReport::issues`; the counts cover all.
+pub(super) const VERIFY_ISSUES_KEPT: usize = 20;
+
+// Outcome of `Vaultline::verify`. Positions count records in log order, oldest
+// archive first.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct VerifyReport {
+    pub segments: usize,
+    // Well-formed records.
+    pub records: usize,
+    // Lines that are not a readable event record (bad JSON, unknown schema).
+    pub malformed: usize,
+    // Records carrying a CRC, and lines whose CRC does not match.
+    pub checksummed: usize,
+    pub checksum_failures: usize,
+    // Records older than the record before them.
+    pub out_of_order: usize,
+    // Records carrying a `seq`, and places where `seq` does not follow on from
+    // the previous numbered record (deleted, duplicated or reordered records).
+    pub sequenced: usize,
+    pub seq_gaps: usize,
+    // Records carrying a `prev_hash` (written with the hash chain on).
+    pub chained: usize,
+    // Records whose `prev_hash` does not match the record before them: that
+    // record or its predecessor was modified, or records between them deleted.
+    pub breaks: Vec<usize>,
+    // `<segment>:<line>: problem` for the first `VERIFY_ISSUES_KEPT` problems.
+    pub issues: Vec<String>,
+}
+
+impl VerifyReport {
+    // No problem of any kind was found.
+    pub fn is_intact(&self) -> bool {
+        self.malformed == 0 && self.checksum_failures == 0 && self.out_of_order == 0 && self.seq_gaps == 0 && self.breaks.is_empty()
+    }
+
+    fn issue(&mut self, at: &str, line: usize, problem: String) {
+        if self.issues.len() < VERIFY_ISSUES_KEPT {
+            self.issues.push(format!("{at}:{line}: {problem}"));
+        }
+    }
+}
+
+// Walks records in log order, carrying what each check needs from the record
+// before.
+#[derive(Default)]
+pub(super) struct Verifier {
+    pub(super) report: VerifyReport,
+    last_ts: Option<u128>,
+    last_seq: Option<u64>,
+    last_hash: Option<String>,
+}
+
+impl Verifier {
+    pub(super) fn segment(&mut self) {
+        self.report.segments += 1;
+    }
+
+    pub(super) fn checksum(&mut self, ok: bool, at: &str, line: usize) {
+        self.report.checksummed += 1;
+        if !ok {
+            self.report.checksum_failures += 1;
+            self.report.issue(at, line, "checksum mismatch".to_string());
+        }
+    }
+
+    // A record without a CRC in a log written with checksums on.
+    pub(super) fn missing_checksum(&mut self, at: &str, line: usize) {
+        self.report.checksummed += 1;
+        self.report.checksum_failures += 1;
+        self.report.issue(at, line, "missing checksum".to_string());
+    }
+
+    pub(super) fn malformed(&mut self, at: &str, line: usize, reason: String) {
+        self.report.malformed += 1;
+        self.report.issue(at, line, reason);
+    }
+
+    pub(super) fn record(&mut self, ev: &Event, at: &str, line: usize) -> Result<()> {
+        let report = &mut self.report;
+        if self.last_ts.is_some_and(|last| ev.ts_ms < last) {
+            report.out_of_order += 1;
+            report.issue(at, line, format!("ts_ms {} is before the previous record's {}", ev.ts_ms, self.last_ts.unwrap_or_default()));
+        }
+        self.last_ts = Some(ev.ts_ms);
+        if ev.seq > 0 {
+            report.sequenced += 1;
+            if let Some(last) = self.last_seq
+                && ev.seq != last + 1
+            {
+                report.seq_gaps += 1;
+                report.issue(at, line, format!("seq {} follows seq {last}", ev.seq));
+            }
+            self.last_seq = Some(ev.seq);
+        }
+        if let Some(ref link) = ev.prev_hash {
+            report.chained += 1;
+            if self.last_hash.as_ref().is_some_and(|hash| hash != link) {
+                report.breaks.push(report.records);
+                report.issue(at, line, "prev_hash does not match the previous record".to_string());
+            }
+        }
+        report.records += 1;
+        self.last_hash = Some(chain::record_hash(ev)?);
+        Ok(())
+    }
+}
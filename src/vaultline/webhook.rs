@@ -0,0 +1,377 @@
+use super::{Event, EventSink};
+use crate::epoch::Backoff;
+use crate::module::Result;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+// POSTs stored events as a JSON array to an HTTP endpoint (a Slack relay, a SIEM
+// collector). Writes only add events to a buffer shared with a sender thread,
+// so appends never wait on the network. The thread posts once `batch_size`
+// events are buffered, once the oldest has waited `flush_interval`, and on
+// `flush`. At most `queue_size` batches' worth of events are buffered; events
+// that find the buffer full are dropped and reported. The thread retries
+// transport errors, 429 and 5xx responses with `backoff`; when retries run out
+// the events are kept and tried again after `flush_interval`, up to
+// `max_pending` (oldest dropped first). Other 4xx responses drop the batch,
+// since resending cannot succeed. Send errors are returned by the next `write`
+// or `flush`. Dropping the sink waits for the buffer to be posted.
+pub struct WebhookSink {
+    target: Target,
+    batch_size: usize,
+    queue_size: usize,
+    flush_interval: Duration,
+    sender: Option<Sender>,
+}
+
+// Where and how the sender thread posts.
+#[derive(Clone)]
+struct Target {
+    url: String,
+    headers: Vec<(String, String)>,
+    retries: u32,
+    backoff: Backoff,
+    timeout: Duration,
+    max_pending: usize,
+    encode: fn(&[Event]) -> Result<Vec<u8>>,
+}
+
+struct Sender {
+    shared: Arc<(Mutex<Buffer>, Condvar)>,
+    handle: Option<JoinHandle<()>>,
+}
+
+// State shared between the sink and its sender thread.
+#[derive(Default)]
+struct Buffer {
+    events: Vec<Event>,
+    // When the oldest buffered event arrived.
+    since: Option<Instant>,
+    flush: bool,
+    closed: bool,
+    error: Option<String>,
+}
+
+fn json_array(events: &[Event]) -> Result<Vec<u8>> {
+    Ok(serde_json::to_vec(events)?)
+}
+
+impl WebhookSink {
+    pub fn new<S: Into<String>>(url: S) -> Self {
+        Self {
+            target: Target {
+                url: url.into(),
+                headers: Vec::new(),
+                retries: 3,
+                backoff: Backoff::Exponential { base: Duration::from_millis(200), max: Duration::from_secs(5) },
+                timeout: Duration::from_secs(10),
+                max_pending: 10_000,
+                encode: json_array,
+            },
+            batch_size: 100,
+            queue_size: 64,
+            flush_interval: Duration::from_secs(1),
+            sender: None,
+        }
+    }
+
+    // Send once this many events are buffered (at least 1).
+    pub fn batch_size(mut self, n: usize) -> Self {
+        self.batch_size = n.max(1);
+        self
+    }
+
+    // Send a smaller batch once its oldest event has waited this long (default 1s).
+    pub fn flush_interval(mut self, every: Duration) -> Self {
+        self.flush_interval = every;
+        self
+    }
+
+    // Retry a failed request up to `retries` times, waiting `backoff` in between.
+    pub fn retries(mut self, retries: u32, backoff: Backoff) -> Self {
+        self.target.retries = retries;
+        self.target.backoff = backoff;
+        self
+    }
+
+    // Extra request header, e.g. an authorization token.
+    pub fn header<K: Into<String>, V: Into<String>>(mut self, name: K, value: V) -> Self {
+        self.target.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.target.timeout = timeout;
+        self
+    }
+
+    pub fn max_pending(mut self, n: usize) -> Self {
+        self.target.max_pending = n;
+        self
+    }
+
+    // Batches' worth of events the buffer holds while the thread is busy (at least 1).
+    pub fn queue_size(mut self, n: usize) -> Self {
+        self.queue_size = n.max(1);
+        self
+    }
+
+    // Request body for a batch, in place of the plain JSON array.
+    #[cfg(feature = "otel")]
+    pub(super) fn encoder(mut self, encode: fn(&[Event]) -> Result<Vec<u8>>) -> Self {
+        self.target.encode = encode;
+        self
+    }
+
+    // Update the shared buffer (starting the sender thread on first use), wake
+    // the thread, and report any error it hit since the last call.
+    fn with_buffer(&mut self, f: impl FnOnce(&mut Buffer)) -> Result<()> {
+        let (target, batch_size, flush_interval) = (&self.target, self.batch_size, self.flush_interval);
+        let sender = self.sender.get_or_insert_with(|| Sender::spawn(target.clone(), batch_size, flush_interval));
+        let (lock, wake) = &*sender.shared;
+        let mut buf = lock.lock().map_err(|_| "webhook buffer mutex poisoned")?;
+        f(&mut buf);
+        wake.notify_one();
+        match buf.error.take() {
+            Some(e) => Err(e.into()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl EventSink for WebhookSink {
+    fn write(&mut self, events: &[Event]) -> Result<()> {
+        let capacity = self.batch_size.saturating_mul(self.queue_size);
+        let url = self.target.url.clone();
+        let mut refused = None;
+        self.with_buffer(|buf| {
+            if buf.events.len() + events.len() > capacity {
+                refused = Some(format!("webhook {url} buffer is full; dropped {} events", events.len()));
+                return;
+            }
+            buf.since.get_or_insert_with(Instant::now);
+            buf.events.extend_from_slice(events);
+        })?;
+        match refused {
+            Some(e) => Err(e.into()),
+            None => Ok(()),
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.with_buffer(|buf| buf.flush = true)
+    }
+}
+
+impl Sender {
+    fn spawn(target: Target, batch_size: usize, flush_interval: Duration) -> Self {
+        let shared = Arc::new((Mutex::new(Buffer::default()), Condvar::new()));
+        let thread_shared = shared.clone();
+        let handle = thread::spawn(move || run(target, batch_size, flush_interval, &thread_shared));
+        Self { shared, handle: Some(handle) }
+    }
+}
+
+impl Drop for Sender {
+    fn drop(&mut self) {
+        let (lock, wake) = &*self.shared;
+        if let Ok(mut buf) = lock.lock() {
+            buf.closed = true;
+        }
+        wake.notify_one();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+// Wait until a batch is due, then post it along with anything kept from
+// earlier failures. After the sink is dropped, post what is left and stop.
+fn run(target: Target, batch_size: usize, flush_interval: Duration, shared: &(Mutex<Buffer>, Condvar)) {
+    let (lock, wake) = shared;
+    let mut kept: Vec<Event> = Vec::new();
+    let mut kept_since = Instant::now();
+    loop {
+        let Ok(mut buf) = lock.lock() else { return };
+        loop {
+            // A flush with nothing new buffered leaves kept events to the retry interval.
+            buf.flush &= !buf.events.is_empty();
+            let oldest = match (buf.since, kept.is_empty()) {
+                (Some(since), true) => Some(since),
+                (Some(since), false) => Some(since.min(kept_since)),
+                (None, false) => Some(kept_since),
+                (None, true) => None,
+            };
+            let due = oldest.map(|t| t + flush_interval);
+            if buf.closed || buf.flush || buf.events.len() >= batch_size || due.is_some_and(|due| due <= Instant::now()) {
+                break;
+            }
+            buf = match due {
+                Some(due) => match wake.wait_timeout(buf, due.saturating_duration_since(Instant::now())) {
+                    Ok((buf, _)) => buf,
+                    Err(_) => return,
+                },
+                None => match wake.wait(buf) {
+                    Ok(buf) => buf,
+                    Err(_) => return,
+                },
+            };
+        }
+        buf.flush = false;
+        buf.since = None;
+        let closed = buf.closed;
+        kept.extend(std::mem::take(&mut buf.events));
+        drop(buf);
+
+        let result = target.send(&mut kept);
+        kept_since = Instant::now();
+        if let Err(e) = result {
+            if closed {
+                tracing::warn!(error = %e, "webhook sink dropped events on shutdown");
+                return;
+            }
+            if let Ok(mut buf) = lock.lock() {
+                buf.error.get_or_insert(e.to_string());
+            }
+        }
+        if closed { return }
+    }
+}
+
+impl Target {
+    // Post `pending`, clearing it on success or on a non-retryable rejection.
+    fn send(&self, pending: &mut Vec<Event>) -> Result<()> {
+        if pending.is_empty() { return Ok(()) }
+        let body = (self.encode)(pending)?;
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let mut request = ureq::post(&self.url).timeout(self.timeout).set("content-type", "application/json");
+            for (name, value) in &self.headers {
+                request = request.set(name, value);
+            }
+            let err = match request.send_bytes(&body) {
+                Ok(_) => {
+                    pending.clear();
+                    return Ok(());
+                }
+                Err(e) => e,
+            };
+            let retryable = match err {
+                ureq::Error::Status(code, _) => code == 429 || code >= 500,
+                ureq::Error::Transport(_) => true,
+            };
+            if !retryable {
+                let dropped = std::mem::take(pending).len();
+                return Err(format!("webhook {} rejected {dropped} events: {err}", self.url).into());
+            }
+            if attempts > self.retries {
+                let excess = pending.len().saturating_sub(self.max_pending);
+                pending.drain(..excess);
+                return Err(format!("webhook {} failed after {attempts} attempts: {err}", self.url).into());
+            }
+            thread::sleep(self.backoff.delay(attempts));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    // Answer each request with the next status, returning the request bodies.
+    fn serve(statuses: Vec<u16>) -> (String, std::thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let mut bodies = Vec::new();
+            for status in statuses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut len = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() { break; }
+                    if let Some((name, value)) = line.split_once(':')
+                        && name.eq_ignore_ascii_case("content-length")
+                    {
+                        len = value.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; len];
+                reader.read_exact(&mut body).unwrap();
+                bodies.push(String::from_utf8(body).unwrap());
+                write!(reader.get_mut(), "HTTP/1.1 {status} X\r\ncontent-length: 0\r\nconnection: close\r\n\r\n").unwrap();
+            }
+            bodies
+        });
+        (url, handle)
+    }
+
+    #[test]
+    fn test_webhook_batches_and_retries() {
+        let (url, server) = serve(vec![503, 200]);
+        let mut sink = WebhookSink::new(url).batch_size(2).retries(1, Backoff::Fixed(Duration::from_millis(1)));
+        sink.write(&[Event::now("auth", "error", "login failed")]).unwrap();
+        sink.write(&[Event::now("auth", "error", "login failed again")]).unwrap();
+        sink.flush().unwrap();
+
+        let bodies = server.join().unwrap();
+        assert_eq!(bodies.len(), 2);
+        let sent: Vec<Event> = serde_json::from_str(&bodies[1]).unwrap();
+        assert_eq!(sent.iter().map(|ev| ev.message.as_str()).collect::<Vec<_>>(), ["login failed", "login failed again"]);
+    }
+
+    #[test]
+    fn test_webhook_keeps_events_when_retries_run_out() {
+        let (url, server) = serve(vec![500, 500, 200]);
+        let mut sink = WebhookSink::new(url).batch_size(1).flush_interval(Duration::from_secs(60)).retries(1, Backoff::None);
+        sink.write(&[Event::now("auth", "error", "kept")]).unwrap();
+        // The write returned before the sender thread even tried; its failure
+        // shows up on a later call.
+        let mut reported = false;
+        for _ in 0..500 {
+            if sink.flush().is_err() {
+                reported = true;
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        assert!(reported);
+        sink.write(&[Event::now("auth", "error", "next")]).unwrap();
+        drop(sink);
+
+        let bodies = server.join().unwrap();
+        assert!(bodies[2].contains("kept") && bodies[2].contains("next"));
+        assert_eq!(bodies.len(), 3);
+    }
+
+    #[test]
+    fn test_webhook_sends_a_partial_batch_after_the_interval() {
+        let (url, server) = serve(vec![200]);
+        let mut sink = WebhookSink::new(url).batch_size(100).flush_interval(Duration::from_millis(50));
+        sink.write(&[Event::now("auth", "error", "alone")]).unwrap();
+        // Nothing flushes the sink; the sender thread posts once the event has waited.
+        let bodies = server.join().unwrap();
+        assert!(bodies[0].contains("alone"));
+        drop(sink);
+    }
+
+    #[test]
+    fn test_webhook_does_not_block_appends() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        // Nobody answers, so every request sits until it times out.
+        let mut sink = WebhookSink::new(url).batch_size(1).queue_size(1).timeout(Duration::from_millis(300)).retries(0, Backoff::None);
+        let started = std::time::Instant::now();
+        sink.write(&[Event::now("auth", "error", "in flight")]).unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        sink.write(&[Event::now("auth", "error", "queued")]).unwrap();
+        assert!(sink.write(&[Event::now("auth", "error", "no room")]).is_err());
+        assert!(started.elapsed() < Duration::from_millis(250));
+        drop(listener);
+    }
+}
@@ -0,0 +1,205 @@
+use super::{Event, Vaultline, VaultlineError};
+use crate::module::Result;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+// What `BackgroundWriter::append` does when the queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backpressure {
+    // Wait for the writer to make room.
+    Block,
+    // Discard the event and count it in `dropped`.
+    DropNewest,
+    // Return `VaultlineError::QueueFull`.
+    Error,
+}
+
+enum Msg {
+    Events(Vec<Event>),
+    Flush(Sender<()>),
+}
+
+// Moves a vaultline onto its own thread so appends only pay for a channel
+// send: the thread batches whatever is queued and does serialization and IO.
+// Write errors are kept and returned by the next `flush` or `close`.
+pub struct BackgroundWriter {
+    tx: Option<SyncSender<Msg>>,
+    capacity: usize,
+    on_full: Backpressure,
+    dropped: Arc<AtomicU64>,
+    error: Arc<Mutex<Option<String>>>,
+    handle: Option<JoinHandle<Vaultline>>,
+}
+
+impl BackgroundWriter {
+    // Queue at most `capacity` pending appends.
+    pub fn spawn(vault: Vaultline, capacity: usize, on_full: Backpressure) -> Self {
+        let (tx, rx) = mpsc::sync_channel(capacity);
+        let error = Arc::new(Mutex::new(None));
+        let thread_error = error.clone();
+        let handle = thread::spawn(move || run(vault, rx, thread_error));
+        Self { tx: Some(tx), capacity, on_full, dropped: Arc::new(AtomicU64::new(0)), error, handle: Some(handle) }
+    }
+
+    pub fn append(&self, event: Event) -> Result<()> {
+        self.append_batch(vec![event])
+    }
+
+    // Queue a batch as one message; it is stored with a single write.
+    pub fn append_batch(&self, events: Vec<Event>) -> Result<()> {
+        let tx = self.tx.as_ref().ok_or("vaultline writer is closed")?;
+        let len = events.len() as u64;
+        let sent = match self.on_full {
+            Backpressure::Block => tx.send(Msg::Events(events)).map_err(|_| ()),
+            Backpressure::DropNewest | Backpressure::Error => match tx.try_send(Msg::Events(events)) {
+                Ok(()) => Ok(()),
+                Err(TrySendError::Full(_)) if self.on_full == Backpressure::DropNewest => {
+                    self.dropped.fetch_add(len, Ordering::Relaxed);
+                    return Ok(());
+                }
+                Err(TrySendError::Full(_)) => return Err(VaultlineError::QueueFull { capacity: self.capacity }.into()),
+                Err(TrySendError::Disconnected(_)) => Err(()),
+            },
+        };
+        sent.map_err(|_| "vaultline writer thread has stopped".into())
+    }
+
+    // Wait until everything queued so far is written and flushed, returning
+    // the first write error since the last call.
+    pub fn flush(&self) -> Result<()> {
+        let tx = self.tx.as_ref().ok_or("vaultline writer is closed")?;
+        let (done_tx, done_rx) = mpsc::channel();
+        tx.send(Msg::Flush(done_tx)).map_err(|_| "vaultline writer thread has stopped")?;
+        done_rx.recv().map_err(|_| "vaultline writer thread has stopped")?;
+        self.take_error()
+    }
+
+    // Events discarded under `Backpressure::DropNewest`.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    // Drain the queue, stop the thread and hand the vaultline back.
+    pub fn close(mut self) -> Result<Vaultline> {
+        self.tx = None;
+        let handle = self.handle.take().ok_or("vaultline writer is closed")?;
+        let mut vault = handle.join().map_err(|_| "vaultline writer thread panicked")?;
+        vault.flush()?;
+        self.take_error()?;
+        Ok(vault)
+    }
+
+    fn take_error(&self) -> Result<()> {
+        match self.error.lock().map_err(|_| "vaultline writer mutex poisoned")?.take() {
+            Some(e) => Err(e.into()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for BackgroundWriter {
+    fn drop(&mut self) {
+        self.tx = None;
+        if let Some(handle) = self.handle.take()
+            && let Ok(mut vault) = handle.join()
+            && let Err(e) = vault.close()
+        {
+            tracing::warn!(error = %e, "vaultline writer dropped without close; pending records may be lost");
+        }
+    }
+}
+
+fn run(mut vault: Vaultline, rx: Receiver<Msg>, error: Arc<Mutex<Option<String>>>) -> Vaultline {
+    let report = |result: Result<()>| {
+        if let Err(e) = result
+            && let Ok(mut slot) = error.lock()
+        {
+            slot.get_or_insert(e.to_string());
+        }
+    };
+    while let Ok(msg) = rx.recv() {
+        let mut waiting = Vec::new();
+        let mut next = Some(msg);
+        // Work through everything already queued before flushing. Each message
+        // is its own batch, so an event one producer got rejected does not
+        // take other producers' events down with it.
+        while let Some(msg) = next {
+            match msg {
+                Msg::Events(events) => report(vault.append_batch(events).map(|_| ())),
+                Msg::Flush(done) => waiting.push(done),
+            }
+            next = rx.try_recv().ok();
+        }
+        if !waiting.is_empty() {
+            report(vault.flush());
+        }
+        for done in waiting {
+            let _ = done.send(());
+        }
+    }
+    vault
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vaultline::Filter;
+
+    #[test]
+    fn writes_off_thread_and_applies_backpressure() {
+        let path = std::env::temp_dir().join(format!("vaultline_writer_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let writer = BackgroundWriter::spawn(Vaultline::new(&path).unwrap(), 64, Backpressure::Block);
+        for i in 0..500 {
+            writer.append(Event::now("epoch", "info", format!("job {i}"))).unwrap();
+        }
+        writer.flush().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 500);
+        let vault = writer.close().unwrap();
+        assert_eq!(vault.query(Filter::default()).count(), 500);
+
+        // Park the writer thread inside a hook so the queue can be filled.
+        let (entered_tx, entered_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let mut parked = Vaultline::new_in_memory();
+        parked.add_hook(move |ev| {
+            let _ = entered_tx.send(());
+            let _ = release_rx.recv();
+            Some(ev)
+        });
+        let writer = BackgroundWriter::spawn(parked, 1, Backpressure::Error);
+        writer.append(Event::now("epoch", "info", "in flight")).unwrap();
+        entered_rx.recv().unwrap();
+        writer.append(Event::now("epoch", "info", "queued")).unwrap();
+        let err = writer.append(Event::now("epoch", "info", "no room")).unwrap_err();
+        assert!(matches!(err.downcast_ref::<VaultlineError>(), Some(VaultlineError::QueueFull { capacity: 1 })));
+        drop(release_tx);
+        assert_eq!(writer.close().unwrap().all().len(), 2);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn rejected_batches_do_not_discard_others() {
+        let path = std::env::temp_dir().join(format!("vaultline_writer_reject_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut vault = Vaultline::new(&path).unwrap();
+        vault.require_kv("auth", vec!["user_id".into()]);
+        vault.set_rollup_window(Some(std::time::Duration::from_secs(60)));
+        let writer = BackgroundWriter::spawn(vault, 64, Backpressure::Block);
+        writer.append(Event::now("epoch", "info", "before")).unwrap();
+        writer.append(Event::now("auth", "info", "no user")).unwrap();
+        writer.append(Event::now("epoch", "info", "after")).unwrap();
+        assert!(writer.flush().is_err());
+        drop(writer);
+
+        let mut reloaded = Vaultline::new(&path).unwrap();
+        reloaded.load_from_disk().unwrap();
+        // Both were still rolled up when the writer was dropped.
+        let mut messages: Vec<&str> = reloaded.all().iter().map(|ev| ev.message.as_str()).collect();
+        messages.sort_unstable();
+        assert_eq!(messages, ["after", "before"]);
+        let _ = std::fs::remove_file(&path);
+    }
+}